@@ -6,6 +6,8 @@ pub enum AMMError {
     SameTokenMint,
     #[msg("Wrong Token Mint")]
     InvalidTokenMint,
+    // No longer raised since add_liquidity trims deposits to the reserve ratio. Kept so
+    // the variants after it keep their error codes.
     #[msg("Token deposit amounts are not proportional to existing pool reserves.")]
     InvalidLiquidity,
     #[msg("Arithmetic operation overflow or division by zero.")]
@@ -22,4 +24,32 @@ pub enum AMMError {
     SlippageExceeded,
     #[msg("Invalid vault account")]
     InvalidVault,
+    #[msg("Fee numerators must add up to less than a non-zero fee denominator")]
+    InvalidFee,
+    #[msg("Signer is not allowed to perform this action")]
+    Unauthorized,
+    #[msg("Initial deposit is too small to cover the locked minimum liquidity")]
+    InsufficientInitialLiquidity,
+    #[msg("Transaction deadline has passed")]
+    DeadlineExceeded,
+    #[msg("Pool is paused")]
+    PoolPaused,
+    #[msg("Swap route accounts are invalid")]
+    InvalidRoute,
+    #[msg("Token A mint must sort before token B mint")]
+    UnsortedMints,
+    #[msg("Pool has an outstanding flash loan")]
+    FlashLoanInProgress,
+    #[msg("Flash loan must be repaid later in the same transaction")]
+    FlashLoanNotRepaid,
+    #[msg("Pool has no outstanding flash loan")]
+    NoFlashLoan,
+    #[msg("Amplification coefficient is out of range for this curve")]
+    InvalidAmplification,
+    #[msg("StableSwap pools need both mints to have the same decimals")]
+    MismatchedDecimals,
+    #[msg("Pool weights are out of range for this curve")]
+    InvalidWeights,
+    #[msg("This instruction is not supported for the pool's curve")]
+    UnsupportedCurve,
 }
@@ -0,0 +1,96 @@
+use anchor_lang::prelude::*;
+
+use crate::state::CurveType;
+
+#[event]
+pub struct PoolInitialized {
+    pub pool: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub lp_mint: Pubkey,
+    pub admin: Pubkey,
+    pub fee_numerator: u64,
+    pub fee_denominator: u64,
+    pub protocol_fee_numerator: u64,
+    pub curve_type: CurveType,
+    pub amp: u64,
+    pub weight_a: u64,
+    pub weight_b: u64,
+}
+
+// Amounts are what actually reached the vaults, reserves are the ones left afterwards
+#[event]
+pub struct LiquidityAdded {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub amount_a: u64,
+    pub amount_b: u64,
+    pub lp_minted: u64,
+    pub reserve_a: u64,
+    pub reserve_b: u64,
+}
+
+#[event]
+pub struct LiquidityWithdrawn {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub amount_a: u64,
+    pub amount_b: u64,
+    pub lp_burned: u64,
+    pub reserve_a: u64,
+    pub reserve_b: u64,
+}
+
+#[event]
+pub struct FlashLoanRepaid {
+    pub pool: Pubkey,
+    pub borrower: Pubkey,
+    pub is_a: bool,
+    pub amount: u64,
+    pub fee: u64,
+}
+
+#[event]
+pub struct Swapped {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub is_a_to_b: bool,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee: u64,
+    pub protocol_fee: u64,
+    pub reserve_a: u64,
+    pub reserve_b: u64,
+}
+
+// Amounts are what left the vaults, which the pool's reserves already excluded
+#[event]
+pub struct ProtocolFeesCollected {
+    pub pool: Pubkey,
+    pub fee_authority: Pubkey,
+    pub amount_a: u64,
+    pub amount_b: u64,
+}
+
+#[event]
+pub struct AdminChanged {
+    pub pool: Pubkey,
+    pub previous_admin: Pubkey,
+    pub new_admin: Pubkey,
+}
+
+#[event]
+pub struct PauseChanged {
+    pub pool: Pubkey,
+    pub admin: Pubkey,
+    pub paused: bool,
+}
+
+// A max_fee_numerator of zero means the pool went back to its static fee
+#[event]
+pub struct DynamicFeeChanged {
+    pub pool: Pubkey,
+    pub admin: Pubkey,
+    pub fee_numerator: u64,
+    pub max_fee_numerator: u64,
+}
@@ -1,99 +1,94 @@
 use crate::error::AMMError;
+use crate::events::LiquidityAdded;
+use crate::quote::{quote_add_liquidity, quote_lp_tokens};
 use crate::state::AMMPool;
-use crate::utils::integer_sqrt;
+use crate::utils::{check_deadline, MINIMUM_LIQUIDITY};
 use anchor_lang::prelude::*;
-use anchor_spl::token::{mint_to, transfer, Mint, MintTo, Token, TokenAccount, Transfer};
+use anchor_spl::token::Token;
+use anchor_spl::token_interface::{
+    mint_to, transfer_checked, Mint, MintTo, TokenAccount, TokenInterface, TransferChecked,
+};
 
-pub fn add_liquidity(ctx: Context<AddLiquidity>, quantity_a: u64, quantity_b: u64) -> Result<()> {
+pub fn add_liquidity(
+    ctx: Context<AddLiquidity>,
+    max_quantity_a: u64,
+    max_quantity_b: u64,
+    min_lp_out: u64,
+    deadline: Option<i64>,
+) -> Result<()> {
+    check_deadline(deadline)?;
+    ctx.accounts.validate_pool_accounts()?;
+    require!(!ctx.accounts.amm_pool.paused, AMMError::PoolPaused);
     require!(
-        ctx.accounts.token_a_mint.key() == ctx.accounts.amm_pool.mint_a,
-        AMMError::InvalidTokenMint
+        ctx.accounts.amm_pool.flash_loan_amount == 0,
+        AMMError::FlashLoanInProgress
     );
+    ctx.accounts
+        .amm_pool
+        .update_price_accumulators(ctx.accounts.vault_a.amount, ctx.accounts.vault_b.amount)?;
+
     require!(
-        ctx.accounts.token_b_mint.key() == ctx.accounts.amm_pool.mint_b,
-        AMMError::InvalidTokenMint
+        max_quantity_a > 0 && max_quantity_b > 0,
+        AMMError::ZeroAmount
     );
 
-    require!(quantity_a > 0 && quantity_b > 0, AMMError::ZeroAmount);
-
-    let liquidity_provider = &mut ctx.accounts.liquidity_provider;
-    let token_account_a = &mut ctx.accounts.token_a_account;
-    let token_account_b = &mut ctx.accounts.token_b_account;
-    let vault_a = &mut ctx.accounts.vault_a;
-    let vault_b = &mut ctx.accounts.vault_b;
-    let amm_pool = &mut &mut ctx.accounts.amm_pool;
-    let lp_token_mint = &mut ctx.accounts.lp_token_mint;
-
-    let tokens_to_issue: u64;
-    if vault_a.amount == 0 && vault_b.amount == 0 {
-        let scaling_factor = 10u128.pow(lp_token_mint.decimals as u32);
-        let total = (quantity_a as u128) * (quantity_b as u128) * scaling_factor;
-        tokens_to_issue = integer_sqrt(total);
-    } else {
-        require!(
-            (quantity_a * vault_b.amount == quantity_b * vault_a.amount),
-            AMMError::InvalidLiquidity
-        );
+    let amm_pool = &ctx.accounts.amm_pool;
+    let (reserve_a, reserve_b) = amm_pool
+        .reserves(ctx.accounts.vault_a.amount, ctx.accounts.vault_b.amount)
+        .ok_or(AMMError::ArithmeticOverflow)?;
 
-        let lp_tokens_to_issue_based_on_token_a = amm_pool
-            .total_lp_issued
-            .checked_mul(quantity_a)
-            .and_then(|result| result.checked_div(vault_a.amount))
-            .ok_or(AMMError::ArithmeticOverflow)?;
+    let is_initial_deposit = amm_pool.total_lp_issued == 0;
+    let lp_decimals = ctx.accounts.lp_token_mint.decimals;
+    // Only the part of the maximums that matches the current reserve ratio is
+    // pulled from the provider, so nothing has to be refunded afterwards.
+    let deposit_quote = quote_add_liquidity(
+        amm_pool,
+        reserve_a,
+        reserve_b,
+        max_quantity_a,
+        max_quantity_b,
+        lp_decimals,
+    )?;
 
-        let lp_tokens_to_issue_based_on_token_b = amm_pool
-            .total_lp_issued
-            .checked_mul(quantity_b)
-            .and_then(|result| result.checked_div(vault_b.amount))
-            .ok_or(AMMError::ArithmeticOverflow)?;
+    // LP tokens are issued against what actually reached the vaults, which is less
+    // than the requested quantities for mints with a transfer fee.
+    let received_a = ctx.accounts.deposit(true, deposit_quote.amount_a)?;
+    let received_b = ctx.accounts.deposit(false, deposit_quote.amount_b)?;
 
-        tokens_to_issue = std::cmp::min(
-            lp_tokens_to_issue_based_on_token_a,
-            lp_tokens_to_issue_based_on_token_b,
-        );
-    }
+    let tokens_to_issue = quote_lp_tokens(
+        &ctx.accounts.amm_pool,
+        reserve_a,
+        reserve_b,
+        received_a,
+        received_b,
+        lp_decimals,
+    )?;
 
-    let transfer_quantiy_a_to_vault_ix = CpiContext::new(
-        ctx.accounts.token_program.to_account_info(),
-        Transfer {
-            from: token_account_a.to_account_info(),
-            to: vault_a.to_account_info(),
-            authority: liquidity_provider.to_account_info(),
-        },
-    );
+    require!(tokens_to_issue > 0, AMMError::ZeroAmount);
+    require!(tokens_to_issue >= min_lp_out, AMMError::SlippageExceeded);
 
-    let transfer_quantiy_b_to_vault_ix = CpiContext::new(
-        ctx.accounts.token_program.to_account_info(),
-        Transfer {
-            from: token_account_b.to_account_info(),
-            to: vault_b.to_account_info(),
-            authority: liquidity_provider.to_account_info(),
-        },
-    );
-    msg!("hi");
-    transfer(transfer_quantiy_a_to_vault_ix, quantity_a)?;
-    transfer(transfer_quantiy_b_to_vault_ix, quantity_b)?;
-
-    let seeds: &[&[u8]] = &[
-        b"authority",
-        amm_pool.mint_a.as_ref(),
-        amm_pool.mint_b.as_ref(),
-        &[ctx.bumps.authority],
-    ];
-    let signer = &[seeds];
-
-    let mint_lp_tokens_ix = CpiContext::new_with_signer(
-        ctx.accounts.token_program.to_account_info(),
-        MintTo {
-            mint: lp_token_mint.to_account_info(),
-            to: ctx.accounts.lp_token_account.to_account_info(),
-            authority: ctx.accounts.authority.to_account_info(),
-        },
-        signer,
-    );
+    ctx.accounts
+        .mint_lp_tokens(ctx.bumps.authority, tokens_to_issue)?;
+
+    if is_initial_deposit {
+        // Counted in the supply but never minted, so nobody can ever redeem it.
+        let amm_pool = &mut ctx.accounts.amm_pool;
+        amm_pool.total_lp_issued = amm_pool
+            .total_lp_issued
+            .checked_add(MINIMUM_LIQUIDITY)
+            .ok_or(AMMError::ArithmeticOverflow)?;
+    }
 
-    mint_to(mint_lp_tokens_ix, tokens_to_issue)?;
-    amm_pool.total_lp_issued += tokens_to_issue;
+    let (reserve_a, reserve_b) = ctx.accounts.reserves_after_transfers()?;
+    emit!(LiquidityAdded {
+        pool: ctx.accounts.amm_pool.key(),
+        user: ctx.accounts.liquidity_provider.key(),
+        amount_a: received_a,
+        amount_b: received_b,
+        lp_minted: tokens_to_issue,
+        reserve_a,
+        reserve_b,
+    });
 
     Ok(())
 }
@@ -102,10 +97,10 @@ pub fn add_liquidity(ctx: Context<AddLiquidity>, quantity_a: u64, quantity_b: u6
 pub struct AddLiquidity<'info> {
     pub liquidity_provider: Signer<'info>,
 
-    #[account()]
-    pub token_a_mint: Account<'info, Mint>,
-    #[account()]
-    pub token_b_mint: Account<'info, Mint>,
+    #[account(mint::token_program = token_a_program)]
+    pub token_a_mint: InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program = token_b_program)]
+    pub token_b_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
@@ -120,44 +115,146 @@ pub struct AddLiquidity<'info> {
     #[account(
         mut,
         associated_token::mint = token_a_mint,
-        associated_token::authority = liquidity_provider)]
-    pub token_a_account: Account<'info, TokenAccount>,
+        associated_token::authority = liquidity_provider,
+        associated_token::token_program = token_a_program)]
+    pub token_a_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         associated_token::mint = token_b_mint,
-        associated_token::authority = liquidity_provider)]
-    pub token_b_account: Account<'info, TokenAccount>,
+        associated_token::authority = liquidity_provider,
+        associated_token::token_program = token_b_program)]
+    pub token_b_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         seeds = [b"vault_token", token_a_mint.key().as_ref(), token_b_mint.key().as_ref(), b"A"],
         bump,
-        token::mint = token_a_mint, 
-        token::authority = authority
+        token::mint = token_a_mint,
+        token::authority = authority,
+        token::token_program = token_a_program
     )]
-    pub vault_a: Account<'info, TokenAccount>,
+    pub vault_a: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         seeds = [b"vault_token", token_a_mint.key().as_ref(), token_b_mint.key().as_ref(), b"B"],
         bump,
         token::mint = token_b_mint,
-        token::authority = authority
+        token::authority = authority,
+        token::token_program = token_b_program
     )]
-    pub vault_b: Account<'info, TokenAccount>,
+    pub vault_b: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         seeds = [b"lp_mint", token_a_mint.key().as_ref(), token_b_mint.key().as_ref()],
         bump,
     )]
-    pub lp_token_mint: Account<'info, Mint>,
+    pub lp_token_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
         associated_token::mint = lp_token_mint,
         associated_token::authority = liquidity_provider)]
-    pub lp_token_account: Account<'info, TokenAccount>,
+    pub lp_token_account: InterfaceAccount<'info, TokenAccount>,
     pub token_program: Program<'info, Token>,
+    pub token_a_program: Interface<'info, TokenInterface>,
+    pub token_b_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> AddLiquidity<'info> {
+    pub fn validate_pool_accounts(&self) -> Result<()> {
+        require!(
+            self.token_a_mint.key() == self.amm_pool.mint_a,
+            AMMError::InvalidTokenMint
+        );
+        require!(
+            self.token_b_mint.key() == self.amm_pool.mint_b,
+            AMMError::InvalidTokenMint
+        );
+        Ok(())
+    }
+
+    // Returns how much of quantity actually arrived in the vault
+    pub fn deposit(&mut self, is_a: bool, quantity: u64) -> Result<u64> {
+        let (from, to, mint, token_program) = if is_a {
+            (
+                &self.token_a_account,
+                &self.vault_a,
+                &self.token_a_mint,
+                &self.token_a_program,
+            )
+        } else {
+            (
+                &self.token_b_account,
+                &self.vault_b,
+                &self.token_b_mint,
+                &self.token_b_program,
+            )
+        };
+        let vault_balance_before = to.amount;
+
+        let transfer_quantity_to_vault_ix = CpiContext::new(
+            token_program.to_account_info(),
+            TransferChecked {
+                from: from.to_account_info(),
+                mint: mint.to_account_info(),
+                to: to.to_account_info(),
+                authority: self.liquidity_provider.to_account_info(),
+            },
+        );
+        transfer_checked(transfer_quantity_to_vault_ix, quantity, mint.decimals)?;
+
+        let vault = if is_a {
+            &mut self.vault_a
+        } else {
+            &mut self.vault_b
+        };
+        vault.reload()?;
+        let quantity_received = vault
+            .amount
+            .checked_sub(vault_balance_before)
+            .ok_or(AMMError::ArithmeticOverflow)?;
+        Ok(quantity_received)
+    }
+
+    // Reserves after this instruction's transfers, re-read from the vaults
+    pub fn reserves_after_transfers(&mut self) -> Result<(u64, u64)> {
+        self.vault_a.reload()?;
+        self.vault_b.reload()?;
+        let reserves = self
+            .amm_pool
+            .reserves(self.vault_a.amount, self.vault_b.amount)
+            .ok_or(AMMError::ArithmeticOverflow)?;
+        Ok(reserves)
+    }
+
+    pub fn mint_lp_tokens(&mut self, authority_bump: u8, tokens_to_issue: u64) -> Result<()> {
+        let seeds: &[&[u8]] = &[
+            b"authority",
+            self.amm_pool.mint_a.as_ref(),
+            self.amm_pool.mint_b.as_ref(),
+            &[authority_bump],
+        ];
+        let signer = &[seeds];
+
+        let mint_lp_tokens_ix = CpiContext::new_with_signer(
+            self.token_program.to_account_info(),
+            MintTo {
+                mint: self.lp_token_mint.to_account_info(),
+                to: self.lp_token_account.to_account_info(),
+                authority: self.authority.to_account_info(),
+            },
+            signer,
+        );
+
+        mint_to(mint_lp_tokens_ix, tokens_to_issue)?;
+        self.amm_pool.total_lp_issued = self
+            .amm_pool
+            .total_lp_issued
+            .checked_add(tokens_to_issue)
+            .ok_or(AMMError::ArithmeticOverflow)?;
+        Ok(())
+    }
 }
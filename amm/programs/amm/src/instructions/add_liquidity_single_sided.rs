@@ -0,0 +1,118 @@
+use anchor_lang::prelude::*;
+
+use crate::error::AMMError;
+use crate::events::LiquidityAdded;
+use crate::instructions::add_liquidity::AddLiquidity;
+use crate::quote::quote_swap_exact_in;
+use crate::state::CurveType;
+use crate::utils::{check_deadline, lp_tokens_for_deposit};
+
+pub fn add_liquidity_single_sided(
+    ctx: Context<AddLiquidity>,
+    quantity: u64,
+    is_a: bool,
+    min_lp_out: u64,
+    deadline: Option<i64>,
+) -> Result<()> {
+    check_deadline(deadline)?;
+    ctx.accounts.validate_pool_accounts()?;
+    require!(!ctx.accounts.amm_pool.paused, AMMError::PoolPaused);
+    require!(
+        ctx.accounts.amm_pool.flash_loan_amount == 0,
+        AMMError::FlashLoanInProgress
+    );
+    ctx.accounts
+        .amm_pool
+        .update_price_accumulators(ctx.accounts.vault_a.amount, ctx.accounts.vault_b.amount)?;
+
+    require!(quantity > 1, AMMError::ZeroAmount);
+    require!(
+        ctx.accounts.amm_pool.curve_type != CurveType::StableSwap,
+        AMMError::UnsupportedCurve
+    );
+
+    let (reserve_a, reserve_b) = ctx
+        .accounts
+        .amm_pool
+        .reserves(ctx.accounts.vault_a.amount, ctx.accounts.vault_b.amount)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    require!(reserve_a > 0 && reserve_b > 0, AMMError::PoolEmpty);
+
+    // Everything below is priced on what actually reached the vault
+    let quantity = ctx.accounts.deposit(is_a, quantity)?;
+    require!(quantity > 1, AMMError::ZeroAmount);
+
+    let (reserve_in, reserve_out) = if is_a {
+        (reserve_a, reserve_b)
+    } else {
+        (reserve_b, reserve_a)
+    };
+
+    // Part of the input is swapped through the curve exactly like `swap` would,
+    // except that the output never leaves the vault. The rest is then deposited
+    // alongside it against the post-swap reserves. How much to swap depends on the
+    // curve and weights, see AMMPool::single_sided_swap_amount.
+    let amm_pool = &mut ctx.accounts.amm_pool;
+    let swap_quantity = amm_pool
+        .single_sided_swap_amount(is_a, quantity, reserve_in)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    let swap_quote = quote_swap_exact_in(
+        amm_pool,
+        reserve_in,
+        reserve_out,
+        swap_quantity,
+        is_a,
+        Clock::get()?.unix_timestamp,
+    )?;
+    let (swapped_out, protocol_fee) = (swap_quote.amount_out, swap_quote.protocol_fee);
+
+    let reserve_in_after_swap = reserve_in
+        .checked_add(swap_quantity)
+        .and_then(|r| r.checked_sub(protocol_fee))
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    let reserve_out_after_swap = reserve_out
+        .checked_sub(swapped_out)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    let deposit_in = quantity
+        .checked_sub(swap_quantity)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+
+    let tokens_to_issue = lp_tokens_for_deposit(
+        deposit_in,
+        swapped_out,
+        reserve_in_after_swap,
+        reserve_out_after_swap,
+        amm_pool.total_lp_issued,
+    )
+    .ok_or(AMMError::ArithmeticOverflow)?;
+
+    require!(tokens_to_issue > 0, AMMError::ZeroAmount);
+    require!(tokens_to_issue >= min_lp_out, AMMError::SlippageExceeded);
+
+    amm_pool
+        .accrue_protocol_fee(is_a, protocol_fee)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+
+    ctx.accounts
+        .mint_lp_tokens(ctx.bumps.authority, tokens_to_issue)?;
+
+    // The internal swap moves the price just like a regular swap does
+    let reserves_before = (reserve_a, reserve_b);
+    let (reserve_a, reserve_b) = ctx.accounts.reserves_after_transfers()?;
+    ctx.accounts
+        .amm_pool
+        .record_price_move(reserves_before, (reserve_a, reserve_b))
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    let (amount_a, amount_b) = if is_a { (quantity, 0) } else { (0, quantity) };
+    emit!(LiquidityAdded {
+        pool: ctx.accounts.amm_pool.key(),
+        user: ctx.accounts.liquidity_provider.key(),
+        amount_a,
+        amount_b,
+        lp_minted: tokens_to_issue,
+        reserve_a,
+        reserve_b,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
+
+use crate::error::AMMError;
+use crate::events::ProtocolFeesCollected;
+use crate::state::AMMPool;
+
+pub fn collect_protocol_fees(ctx: Context<CollectProtocolFees>) -> Result<()> {
+    let amm_pool = &mut ctx.accounts.amm_pool;
+
+    require_keys_eq!(
+        ctx.accounts.fee_authority.key(),
+        amm_pool.fee_authority,
+        AMMError::Unauthorized
+    );
+    require!(
+        amm_pool.protocol_fees_a > 0 || amm_pool.protocol_fees_b > 0,
+        AMMError::ZeroAmount
+    );
+
+    let protocol_fees_a = amm_pool.protocol_fees_a;
+    let protocol_fees_b = amm_pool.protocol_fees_b;
+    amm_pool.protocol_fees_a = 0;
+    amm_pool.protocol_fees_b = 0;
+
+    let seeds: &[&[u8]] = &[
+        b"authority",
+        amm_pool.mint_a.as_ref(),
+        amm_pool.mint_b.as_ref(),
+        &[ctx.bumps.authority],
+    ];
+    let signer = &[seeds];
+
+    if protocol_fees_a > 0 {
+        let transfer_fees_a_ix = CpiContext::new_with_signer(
+            ctx.accounts.token_a_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.vault_a.to_account_info(),
+                mint: ctx.accounts.token_a_mint.to_account_info(),
+                to: ctx.accounts.fee_receiver_a.to_account_info(),
+                authority: ctx.accounts.authority.to_account_info(),
+            },
+            signer,
+        );
+        transfer_checked(
+            transfer_fees_a_ix,
+            protocol_fees_a,
+            ctx.accounts.token_a_mint.decimals,
+        )?;
+    }
+
+    if protocol_fees_b > 0 {
+        let transfer_fees_b_ix = CpiContext::new_with_signer(
+            ctx.accounts.token_b_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.vault_b.to_account_info(),
+                mint: ctx.accounts.token_b_mint.to_account_info(),
+                to: ctx.accounts.fee_receiver_b.to_account_info(),
+                authority: ctx.accounts.authority.to_account_info(),
+            },
+            signer,
+        );
+        transfer_checked(
+            transfer_fees_b_ix,
+            protocol_fees_b,
+            ctx.accounts.token_b_mint.decimals,
+        )?;
+    }
+
+    emit!(ProtocolFeesCollected {
+        pool: ctx.accounts.amm_pool.key(),
+        fee_authority: ctx.accounts.fee_authority.key(),
+        amount_a: protocol_fees_a,
+        amount_b: protocol_fees_b,
+    });
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CollectProtocolFees<'info> {
+    pub fee_authority: Signer<'info>,
+
+    #[account(mint::token_program = token_a_program)]
+    pub token_a_mint: InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program = token_b_program)]
+    pub token_b_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"pool", token_a_mint.key().as_ref(), token_b_mint.key().as_ref()],
+        bump
+    )]
+    pub amm_pool: Account<'info, AMMPool>,
+
+    /// CHECK: PDA authority for signing vault transfers
+    #[account(
+        seeds = [b"authority", token_a_mint.key().as_ref(), token_b_mint.key().as_ref()],
+        bump
+    )]
+    pub authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vault_token", token_a_mint.key().as_ref(), token_b_mint.key().as_ref(), b"A"],
+        bump,
+        token::mint = token_a_mint,
+        token::authority = authority,
+        token::token_program = token_a_program
+    )]
+    pub vault_a: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"vault_token", token_a_mint.key().as_ref(), token_b_mint.key().as_ref(), b"B"],
+        bump,
+        token::mint = token_b_mint,
+        token::authority = authority,
+        token::token_program = token_b_program
+    )]
+    pub vault_b: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, token::mint = token_a_mint, token::token_program = token_a_program)]
+    pub fee_receiver_a: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, token::mint = token_b_mint, token::token_program = token_b_program)]
+    pub fee_receiver_b: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_a_program: Interface<'info, TokenInterface>,
+    pub token_b_program: Interface<'info, TokenInterface>,
+}
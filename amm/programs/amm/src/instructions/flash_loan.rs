@@ -0,0 +1,288 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::sysvar::instructions::{
+    load_current_index_checked, load_instruction_at_checked,
+};
+use anchor_lang::Discriminator;
+use anchor_spl::token_interface::{
+    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
+
+use crate::error::AMMError;
+use crate::events::FlashLoanRepaid;
+use crate::quote::amount_with_transfer_fee;
+use crate::state::AMMPool;
+use crate::utils::calculate_fee;
+
+// Position of amm_pool in the FlashLoan accounts, used to match the repay instruction
+const POOL_ACCOUNT_INDEX: usize = 3;
+
+// Lends `amount` of one side of the pool to the borrower. The transaction has to
+// contain a flash_repay for the same pool further down, and the pool refuses every
+// other instruction until that repayment has gone through.
+pub fn flash_borrow(ctx: Context<FlashLoan>, amount: u64, is_a: bool) -> Result<()> {
+    ctx.accounts.validate_pool_accounts()?;
+    require!(!ctx.accounts.amm_pool.paused, AMMError::PoolPaused);
+    require!(
+        ctx.accounts.amm_pool.flash_loan_amount == 0,
+        AMMError::FlashLoanInProgress
+    );
+    require!(amount > 0, AMMError::ZeroAmount);
+
+    let (reserve_a, reserve_b) = ctx
+        .accounts
+        .amm_pool
+        .reserves(ctx.accounts.vault_a.amount, ctx.accounts.vault_b.amount)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    let reserve = if is_a { reserve_a } else { reserve_b };
+    require!(amount <= reserve, AMMError::InsufficientFundsInPool);
+
+    ctx.accounts.require_repay_instruction()?;
+
+    let amm_pool = &mut ctx.accounts.amm_pool;
+    amm_pool.flash_loan_amount = amount;
+    amm_pool.flash_loan_is_a = is_a;
+
+    ctx.accounts.lend(ctx.bumps.authority, is_a, amount)
+}
+
+// Pays back the outstanding loan plus the pool fee on it. No protocol cut is taken,
+// so the whole fee stays in the vault for liquidity providers.
+pub fn flash_repay(ctx: Context<FlashLoan>) -> Result<()> {
+    ctx.accounts.validate_pool_accounts()?;
+
+    let amm_pool = &ctx.accounts.amm_pool;
+    let amount = amm_pool.flash_loan_amount;
+    let is_a = amm_pool.flash_loan_is_a;
+    require!(amount > 0, AMMError::NoFlashLoan);
+
+    let fee = calculate_fee(amount, amm_pool.fee_numerator, amm_pool.fee_denominator)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    let amount_owed = amount
+        .checked_add(fee)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+
+    let amount_received = ctx.accounts.repay(is_a, amount_owed)?;
+    require!(amount_received >= amount_owed, AMMError::FlashLoanNotRepaid);
+
+    let amm_pool = &mut ctx.accounts.amm_pool;
+    amm_pool.flash_loan_amount = 0;
+    amm_pool.flash_loan_is_a = false;
+
+    emit!(FlashLoanRepaid {
+        pool: amm_pool.key(),
+        borrower: ctx.accounts.borrower.key(),
+        is_a,
+        amount,
+        fee,
+    });
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct FlashLoan<'info> {
+    pub borrower: Signer<'info>,
+
+    #[account(mint::token_program = token_a_program)]
+    pub token_a_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mint::token_program = token_b_program)]
+    pub token_b_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"pool", token_a_mint.key().as_ref(), token_b_mint.key().as_ref()],
+        bump
+    )]
+    pub amm_pool: Account<'info, AMMPool>,
+
+    /// CHECK: PDA authority for signing vault transfers
+    #[account(
+        seeds = [b"authority", token_a_mint.key().as_ref(), token_b_mint.key().as_ref()],
+        bump
+    )]
+    pub authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"vault_token", token_a_mint.key().as_ref(), token_b_mint.key().as_ref(), b"A"],
+        bump,
+        token::token_program = token_a_program
+    )]
+    pub vault_a: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"vault_token", token_a_mint.key().as_ref(), token_b_mint.key().as_ref(), b"B"],
+        bump,
+        token::token_program = token_b_program
+    )]
+    pub vault_b: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_a_mint,
+        associated_token::authority = borrower,
+        associated_token::token_program = token_a_program
+    )]
+    pub borrower_token_account_a: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_b_mint,
+        associated_token::authority = borrower,
+        associated_token::token_program = token_b_program
+    )]
+    pub borrower_token_account_b: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_a_program: Interface<'info, TokenInterface>,
+    pub token_b_program: Interface<'info, TokenInterface>,
+
+    /// CHECK: Instructions sysvar, used to find the matching flash_repay
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+}
+
+impl<'info> FlashLoan<'info> {
+    pub fn validate_pool_accounts(&self) -> Result<()> {
+        require!(
+            self.token_a_mint.key() == self.amm_pool.mint_a,
+            AMMError::InvalidTokenMint
+        );
+        require!(
+            self.token_b_mint.key() == self.amm_pool.mint_b,
+            AMMError::InvalidTokenMint
+        );
+        require!(
+            self.vault_a.key() == self.amm_pool.vault_a,
+            AMMError::InvalidVault
+        );
+        require!(
+            self.vault_b.key() == self.amm_pool.vault_b,
+            AMMError::InvalidVault
+        );
+        Ok(())
+    }
+
+    // The borrow has to be a top-level instruction of this program, followed later in
+    // the same transaction by a top-level flash_repay on the same pool.
+    pub fn require_repay_instruction(&self) -> Result<()> {
+        let instructions = self.instructions.to_account_info();
+        let current_index = load_current_index_checked(&instructions)? as usize;
+        let current_instruction = load_instruction_at_checked(current_index, &instructions)?;
+        require_keys_eq!(
+            current_instruction.program_id,
+            crate::ID,
+            AMMError::FlashLoanNotRepaid
+        );
+
+        let mut index = current_index + 1;
+        while let Ok(instruction) = load_instruction_at_checked(index, &instructions) {
+            let is_matching_repay = instruction.program_id == crate::ID
+                && instruction
+                    .data
+                    .starts_with(crate::instruction::FlashRepay::DISCRIMINATOR)
+                && instruction
+                    .accounts
+                    .get(POOL_ACCOUNT_INDEX)
+                    .is_some_and(|account| account.pubkey == self.amm_pool.key());
+            if is_matching_repay {
+                return Ok(());
+            }
+            index += 1;
+        }
+        err!(AMMError::FlashLoanNotRepaid)
+    }
+
+    pub fn lend(&self, authority_bump: u8, is_a: bool, amount: u64) -> Result<()> {
+        let (vault, borrower_token_account, mint, token_program) = if is_a {
+            (
+                &self.vault_a,
+                &self.borrower_token_account_a,
+                &self.token_a_mint,
+                &self.token_a_program,
+            )
+        } else {
+            (
+                &self.vault_b,
+                &self.borrower_token_account_b,
+                &self.token_b_mint,
+                &self.token_b_program,
+            )
+        };
+
+        let seeds: &[&[u8]] = &[
+            b"authority",
+            self.amm_pool.mint_a.as_ref(),
+            self.amm_pool.mint_b.as_ref(),
+            &[authority_bump],
+        ];
+        let signer = &[seeds];
+
+        let transfer_loan_to_borrower_ix = CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            TransferChecked {
+                from: vault.to_account_info(),
+                mint: mint.to_account_info(),
+                to: borrower_token_account.to_account_info(),
+                authority: self.authority.to_account_info(),
+            },
+            signer,
+        );
+        transfer_checked(transfer_loan_to_borrower_ix, amount, mint.decimals)
+    }
+
+    // Sends amount_owed back to the vault, grossed up for any transfer fee, and returns
+    // how much actually arrived
+    pub fn repay(&mut self, is_a: bool, amount_owed: u64) -> Result<u64> {
+        let (vault, borrower_token_account, mint, token_program) = if is_a {
+            (
+                &self.vault_a,
+                &self.borrower_token_account_a,
+                &self.token_a_mint,
+                &self.token_a_program,
+            )
+        } else {
+            (
+                &self.vault_b,
+                &self.borrower_token_account_b,
+                &self.token_b_mint,
+                &self.token_b_program,
+            )
+        };
+        let vault_balance_before = vault.amount;
+        let mint_info = mint.to_account_info();
+        let amount_to_transfer = amount_with_transfer_fee(
+            mint_info.owner,
+            &mint_info.try_borrow_data()?,
+            Clock::get()?.epoch,
+            amount_owed,
+        )?;
+
+        let transfer_repayment_to_vault_ix = CpiContext::new(
+            token_program.to_account_info(),
+            TransferChecked {
+                from: borrower_token_account.to_account_info(),
+                mint: mint.to_account_info(),
+                to: vault.to_account_info(),
+                authority: self.borrower.to_account_info(),
+            },
+        );
+        transfer_checked(
+            transfer_repayment_to_vault_ix,
+            amount_to_transfer,
+            mint.decimals,
+        )?;
+
+        let vault = if is_a {
+            &mut self.vault_a
+        } else {
+            &mut self.vault_b
+        };
+        vault.reload()?;
+        let amount_received = vault
+            .amount
+            .checked_sub(vault_balance_before)
+            .ok_or(AMMError::ArithmeticOverflow)?;
+        Ok(amount_received)
+    }
+}
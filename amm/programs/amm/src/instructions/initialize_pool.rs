@@ -1,13 +1,68 @@
 use crate::error::AMMError;
-use crate::state::AMMPool;
+use crate::events::PoolInitialized;
+use crate::state::{AMMPool, CurveConfig, CurveType, PoolRegistry, PoolRegistryEntry};
+use crate::utils::{MAX_AMP, MIN_AMP, MIN_WEIGHT, WAD};
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Mint, Token, TokenAccount};
+use anchor_spl::token::Token;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 
-pub fn initialize_pool(ctx: Context<InitPool>) -> Result<()> {
+pub fn initialize_pool(
+    ctx: Context<InitPool>,
+    fee_numerator: u64,
+    fee_denominator: u64,
+    protocol_fee_numerator: u64,
+    fee_authority: Pubkey,
+    curve: CurveConfig,
+) -> Result<()> {
     require!(
         ctx.accounts.token_a_mint.key() != ctx.accounts.token_b_mint.key(),
         AMMError::SameTokenMint
     );
+    // A pair gets exactly one pool, with the lower mint address always on the A side
+    require!(
+        ctx.accounts.token_a_mint.key() < ctx.accounts.token_b_mint.key(),
+        AMMError::UnsortedMints
+    );
+    let total_fee_numerator = fee_numerator
+        .checked_add(protocol_fee_numerator)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    require!(
+        fee_denominator > 0 && total_fee_numerator < fee_denominator,
+        AMMError::InvalidFee
+    );
+
+    let CurveConfig {
+        curve_type,
+        amp,
+        weight_a,
+    } = curve;
+    if curve_type != CurveType::StableSwap {
+        require!(amp == 0, AMMError::InvalidAmplification);
+    }
+    if curve_type != CurveType::Weighted {
+        require!(weight_a == 0, AMMError::InvalidWeights);
+    }
+    match curve_type {
+        CurveType::ConstantProduct => {}
+        CurveType::Weighted => {
+            // weight_b is whatever is left of WAD, and neither side may drop below 1%
+            require!(
+                weight_a >= MIN_WEIGHT && weight_a <= WAD as u64 - MIN_WEIGHT,
+                AMMError::InvalidWeights
+            );
+        }
+        CurveType::StableSwap => {
+            require!(
+                (MIN_AMP..=MAX_AMP).contains(&amp),
+                AMMError::InvalidAmplification
+            );
+            // The invariant treats one unit of A as worth one unit of B
+            require!(
+                ctx.accounts.token_a_mint.decimals == ctx.accounts.token_b_mint.decimals,
+                AMMError::MismatchedDecimals
+            );
+        }
+    }
 
     let pool = &mut ctx.accounts.amm_pool;
     pool.mint_a = ctx.accounts.token_a_mint.key();
@@ -16,8 +71,55 @@ pub fn initialize_pool(ctx: Context<InitPool>) -> Result<()> {
     pool.vault_b = ctx.accounts.vault_b.key();
     pool.lp_mint = ctx.accounts.lp_token_mint.key();
     pool.total_lp_issued = 0;
+    pool.fee_numerator = fee_numerator;
+    pool.fee_denominator = fee_denominator;
+    pool.fee_authority = fee_authority;
+    pool.protocol_fee_numerator = protocol_fee_numerator;
+    pool.protocol_fees_a = 0;
+    pool.protocol_fees_b = 0;
+    pool.admin = ctx.accounts.initializer.key();
+    pool.paused = false;
+    pool.price_a_cumulative = 0;
+    pool.price_b_cumulative = 0;
+    pool.last_update_ts = Clock::get()?.unix_timestamp;
+    pool.flash_loan_amount = 0;
+    pool.flash_loan_is_a = false;
+    pool.curve_type = curve_type;
+    pool.amp = amp;
+    pool.weight_a = weight_a;
+    pool.weight_b = if curve_type == CurveType::Weighted {
+        WAD as u64 - weight_a
+    } else {
+        0
+    };
+    pool.max_fee_numerator = 0;
+    pool.volatility_bps = 0;
     pool.bump = ctx.bumps.amm_pool;
     pool.pool_authority = ctx.accounts.authority.key();
+
+    let registry_entry = &mut ctx.accounts.registry_entry;
+    registry_entry.pool = pool.key();
+    registry_entry.bump = ctx.bumps.registry_entry;
+    let registry = &mut ctx.accounts.pool_registry;
+    registry.pool_count = registry
+        .pool_count
+        .checked_add(1)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+
+    emit!(PoolInitialized {
+        pool: pool.key(),
+        mint_a: pool.mint_a,
+        mint_b: pool.mint_b,
+        lp_mint: pool.lp_mint,
+        admin: pool.admin,
+        fee_numerator,
+        fee_denominator,
+        protocol_fee_numerator,
+        curve_type,
+        amp,
+        weight_a: pool.weight_a,
+        weight_b: pool.weight_b,
+    });
     Ok(())
 }
 
@@ -28,19 +130,20 @@ pub struct InitPool<'info> {
     /// CHECK: This holds the complete authority for vault A and B and lp_mint_token
     #[account(seeds = [b"authority", token_a_mint.key().as_ref(), token_b_mint.key().as_ref()], bump)]
     pub authority: UncheckedAccount<'info>,
-    #[account()]
-    pub token_a_mint: Account<'info, Mint>,
-    #[account()]
-    pub token_b_mint: Account<'info, Mint>,
+    #[account(mint::token_program = token_a_program)]
+    pub token_a_mint: InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program = token_b_program)]
+    pub token_b_mint: InterfaceAccount<'info, Mint>,
     #[account(
         init,
         payer = initializer,
         seeds = [b"vault_token", token_a_mint.key().as_ref(), token_b_mint.key().as_ref(), b"A"],
         bump,
-        token::mint = token_a_mint, 
-        token::authority = authority
+        token::mint = token_a_mint,
+        token::authority = authority,
+        token::token_program = token_a_program
     )]
-    pub vault_a: Account<'info, TokenAccount>,
+    pub vault_a: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         init,
@@ -48,9 +151,10 @@ pub struct InitPool<'info> {
         seeds = [b"vault_token", token_a_mint.key().as_ref(), token_b_mint.key().as_ref(), b"B"],
         bump,
         token::mint = token_b_mint,
-        token::authority = authority
+        token::authority = authority,
+        token::token_program = token_b_program
     )]
-    pub vault_b: Account<'info, TokenAccount>,
+    pub vault_b: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         init,
@@ -61,18 +165,31 @@ pub struct InitPool<'info> {
         mint::authority = authority,
         mint::freeze_authority = authority
     )]
-    pub lp_token_mint: Account<'info, Mint>,
+    pub lp_token_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         init,
         payer = initializer,
-        space = 8 + (32 * 6) + 16 + 1,
+        space = 8 + (32 * 8) + (8 * 6) + 1 + 1 + (16 * 2) + 8 + 8 + 1 + 1 + 8 + 8 + 8 + 8 + 8,
         seeds = [b"pool",
         token_a_mint.key().as_ref(),
         token_b_mint.key().as_ref()],
         bump)]
     pub amm_pool: Account<'info, AMMPool>,
 
+    #[account(mut, seeds = [b"registry"], bump = pool_registry.bump)]
+    pub pool_registry: Account<'info, PoolRegistry>,
+
+    #[account(
+        init,
+        payer = initializer,
+        space = 8 + 32 + 1,
+        seeds = [b"registry", pool_registry.pool_count.to_le_bytes().as_ref()],
+        bump)]
+    pub registry_entry: Box<Account<'info, PoolRegistryEntry>>,
+
     pub system_program: Program<'info, System>,
     pub token_program: Program<'info, Token>,
+    pub token_a_program: Interface<'info, TokenInterface>,
+    pub token_b_program: Interface<'info, TokenInterface>,
 }
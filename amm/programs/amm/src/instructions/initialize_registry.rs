@@ -0,0 +1,25 @@
+use crate::state::PoolRegistry;
+use anchor_lang::prelude::*;
+
+pub fn initialize_registry(ctx: Context<InitRegistry>) -> Result<()> {
+    let registry = &mut ctx.accounts.pool_registry;
+    registry.pool_count = 0;
+    registry.bump = ctx.bumps.pool_registry;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitRegistry<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + 8 + 1,
+        seeds = [b"registry"],
+        bump)]
+    pub pool_registry: Account<'info, PoolRegistry>,
+
+    pub system_program: Program<'info, System>,
+}
@@ -1,9 +1,26 @@
 pub mod add_liquidity;
+pub mod add_liquidity_single_sided;
+pub mod collect_protocol_fees;
+pub mod flash_loan;
 pub mod initialize_pool;
+pub mod initialize_registry;
+pub mod observe;
+pub mod pause_pool;
+pub mod set_admin;
+pub mod set_dynamic_fee;
 pub mod swap;
+pub mod swap_exact_out;
+pub mod swap_route;
 pub mod withdraw_liquidity;
+pub mod withdraw_liquidity_single;
 
 pub use add_liquidity::*;
+pub use collect_protocol_fees::*;
+pub use flash_loan::*;
 pub use initialize_pool::*;
+pub use initialize_registry::*;
+pub use observe::*;
+pub use set_admin::*;
 pub use swap::*;
+pub use swap_route::*;
 pub use withdraw_liquidity::*;
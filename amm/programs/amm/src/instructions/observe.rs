@@ -0,0 +1,60 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount};
+
+use crate::error::AMMError;
+use crate::oracle::PriceObservation;
+use crate::state::AMMPool;
+
+// Read-only view of the cumulative prices as of now. Two observations taken some time
+// apart give the TWAP over that window through `oracle::twap_x64`.
+pub fn observe(ctx: Context<Observe>) -> Result<PriceObservation> {
+    let amm_pool = &ctx.accounts.amm_pool;
+    require!(
+        ctx.accounts.vault_a.key() == amm_pool.vault_a
+            && ctx.accounts.vault_b.key() == amm_pool.vault_b,
+        AMMError::InvalidVault
+    );
+    // Reserves are short by the borrowed amount until the loan is repaid
+    require!(
+        amm_pool.flash_loan_amount == 0,
+        AMMError::FlashLoanInProgress
+    );
+
+    // Same reserves update_price_accumulators advances with, so the extrapolated
+    // observation lines up with the stored one
+    let (reserve_a, reserve_b) = amm_pool
+        .reserves(ctx.accounts.vault_a.amount, ctx.accounts.vault_b.amount)
+        .and_then(|(reserve_a, reserve_b)| amm_pool.oracle_reserves(reserve_a, reserve_b))
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    let observation = amm_pool
+        .observation()
+        .advance(reserve_a, reserve_b, Clock::get()?.unix_timestamp)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    Ok(observation)
+}
+
+#[derive(Accounts)]
+pub struct Observe<'info> {
+    #[account()]
+    pub token_a_mint: InterfaceAccount<'info, Mint>,
+    #[account()]
+    pub token_b_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"pool", token_a_mint.key().as_ref(), token_b_mint.key().as_ref()],
+        bump
+    )]
+    pub amm_pool: Account<'info, AMMPool>,
+
+    #[account(
+        seeds = [b"vault_token", token_a_mint.key().as_ref(), token_b_mint.key().as_ref(), b"A"],
+        bump
+    )]
+    pub vault_a: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"vault_token", token_a_mint.key().as_ref(), token_b_mint.key().as_ref(), b"B"],
+        bump
+    )]
+    pub vault_b: InterfaceAccount<'info, TokenAccount>,
+}
@@ -0,0 +1,29 @@
+use anchor_lang::prelude::*;
+
+use crate::events::PauseChanged;
+use crate::instructions::set_admin::PoolAdmin;
+
+// Stops swaps and deposits. Proportional withdrawals stay open so LPs can always exit.
+pub fn pause_pool(ctx: Context<PoolAdmin>) -> Result<()> {
+    ctx.accounts.validate_admin()?;
+    ctx.accounts.amm_pool.paused = true;
+    ctx.accounts.emit_pause_changed();
+    Ok(())
+}
+
+pub fn unpause_pool(ctx: Context<PoolAdmin>) -> Result<()> {
+    ctx.accounts.validate_admin()?;
+    ctx.accounts.amm_pool.paused = false;
+    ctx.accounts.emit_pause_changed();
+    Ok(())
+}
+
+impl<'info> PoolAdmin<'info> {
+    fn emit_pause_changed(&self) {
+        emit!(PauseChanged {
+            pool: self.amm_pool.key(),
+            admin: self.admin.key(),
+            paused: self.amm_pool.paused,
+        });
+    }
+}
@@ -0,0 +1,47 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::error::AMMError;
+use crate::events::AdminChanged;
+use crate::state::AMMPool;
+
+pub fn set_admin(ctx: Context<PoolAdmin>, new_admin: Pubkey) -> Result<()> {
+    ctx.accounts.validate_admin()?;
+    let previous_admin = ctx.accounts.amm_pool.admin;
+    ctx.accounts.amm_pool.admin = new_admin;
+
+    emit!(AdminChanged {
+        pool: ctx.accounts.amm_pool.key(),
+        previous_admin,
+        new_admin,
+    });
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct PoolAdmin<'info> {
+    pub admin: Signer<'info>,
+
+    #[account()]
+    pub token_a_mint: InterfaceAccount<'info, Mint>,
+    #[account()]
+    pub token_b_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"pool", token_a_mint.key().as_ref(), token_b_mint.key().as_ref()],
+        bump
+    )]
+    pub amm_pool: Account<'info, AMMPool>,
+}
+
+impl<'info> PoolAdmin<'info> {
+    pub fn validate_admin(&self) -> Result<()> {
+        require_keys_eq!(
+            self.admin.key(),
+            self.amm_pool.admin,
+            AMMError::Unauthorized
+        );
+        Ok(())
+    }
+}
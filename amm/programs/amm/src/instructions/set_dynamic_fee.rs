@@ -0,0 +1,32 @@
+use anchor_lang::prelude::*;
+
+use crate::error::AMMError;
+use crate::events::DynamicFeeChanged;
+use crate::instructions::set_admin::PoolAdmin;
+
+// Switches the pool to dynamic fees, letting the LP fee climb from fee_numerator up to
+// max_fee_numerator with volatility. Passing zero goes back to the static fee.
+pub fn set_dynamic_fee(ctx: Context<PoolAdmin>, max_fee_numerator: u64) -> Result<()> {
+    ctx.accounts.validate_admin()?;
+
+    let amm_pool = &mut ctx.accounts.amm_pool;
+    if max_fee_numerator > 0 {
+        let max_total_fee_numerator = max_fee_numerator
+            .checked_add(amm_pool.protocol_fee_numerator)
+            .ok_or(AMMError::ArithmeticOverflow)?;
+        require!(
+            max_fee_numerator >= amm_pool.fee_numerator
+                && max_total_fee_numerator < amm_pool.fee_denominator,
+            AMMError::InvalidFee
+        );
+    }
+    amm_pool.max_fee_numerator = max_fee_numerator;
+
+    emit!(DynamicFeeChanged {
+        pool: amm_pool.key(),
+        admin: ctx.accounts.admin.key(),
+        fee_numerator: amm_pool.fee_numerator,
+        max_fee_numerator,
+    });
+    Ok(())
+}
@@ -1,18 +1,36 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{transfer, Mint, Token, TokenAccount, Transfer};
+use anchor_spl::token_interface::{
+    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
 
 use crate::error::AMMError;
+use crate::events::Swapped;
+use crate::quote::quote_swap_exact_in;
 use crate::state::AMMPool;
+use crate::utils::check_deadline;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SwapResult {
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee: u64,
+    pub protocol_fee: u64,
+    // Total fee rate charged, out of the pool's fee_denominator
+    pub fee_numerator: u64,
+}
 
 pub fn swap(
     ctx: Context<Swap>,
     quantity: u64,
     min_slippage_quantity: u64,
     is_a_to_b: bool,
-) -> Result<()> {
+    deadline: Option<i64>,
+) -> Result<SwapResult> {
+    check_deadline(deadline)?;
     require!(quantity > 0, AMMError::ZeroAmount);
 
-    // Constant Product Formula: x * y = k
+    // Constant Product Formula: x * y = k (StableSwap and weighted pools swap along
+    // their own invariants instead, see AMMPool::curve_amount_out)
     // When a user swaps dx amount of token x (quantity), he should recieve dy back token y:
     // - dx: amount of input tokens user provides
     // - dy: amount of output tokens user receives
@@ -23,92 +41,64 @@ pub fn swap(
     //     y - dy = (x * y) / (x + dx)
     //     dy = y - (x * y) / (x + dx)
     //     dy = (y * dx) / (x + dx)
+    // The pool fee is taken out of dx before it hits the curve, but the full quantity
+    // still lands in the vault so the fee accrues to liquidity providers. The protocol's
+    // cut is booked on the pool and kept out of x and y until it is collected.
+    // dx is whatever actually arrived in the vault, so a Token-2022 transfer fee on the
+    // input mint is paid by the trader rather than by the pool.
 
+    ctx.accounts.validate_pool_accounts()?;
+    require!(!ctx.accounts.amm_pool.paused, AMMError::PoolPaused);
     require!(
-        ctx.accounts.token_a_mint.key() == ctx.accounts.amm_pool.mint_a,
-        AMMError::InvalidTokenMint
-    );
-    require!(
-        ctx.accounts.token_b_mint.key() == ctx.accounts.amm_pool.mint_b,
-        AMMError::InvalidTokenMint
+        ctx.accounts.amm_pool.flash_loan_amount == 0,
+        AMMError::FlashLoanInProgress
     );
+    ctx.accounts
+        .amm_pool
+        .update_price_accumulators(ctx.accounts.vault_a.amount, ctx.accounts.vault_b.amount)?;
 
+    let (reserve_in, reserve_out) = ctx.accounts.directional_reserves(is_a_to_b)?;
     require!(
-        ctx.accounts.vault_a.key() == ctx.accounts.amm_pool.vault_a,
-        AMMError::InvalidVault
-    );
-    require!(
-        ctx.accounts.vault_b.key() == ctx.accounts.amm_pool.vault_b,
-        AMMError::InvalidVault
+        reserve_out > min_slippage_quantity,
+        AMMError::InsufficientFundsInPool
     );
 
-    let (vault_in, vault_out, user_account_in, user_account_out) = if is_a_to_b {
-        require!(
-            ctx.accounts.vault_b.amount > min_slippage_quantity,
-            AMMError::InsufficientFundsInPool
-        );
-        (
-            &ctx.accounts.vault_a,
-            &ctx.accounts.vault_b,
-            &ctx.accounts.user_token_account_a,
-            &ctx.accounts.user_token_account_b,
-        )
-    } else {
-        require!(
-            ctx.accounts.vault_a.amount > min_slippage_quantity,
-            AMMError::InsufficientFundsInPool
-        );
-        (
-            &ctx.accounts.vault_b,
-            &ctx.accounts.vault_a,
-            &ctx.accounts.user_token_account_b,
-            &ctx.accounts.user_token_account_a,
-        )
-    };
-
-    let token_user_receives = vault_out
-        .amount
-        .checked_mul(quantity)
-        .and_then(|p| p.checked_div(vault_in.amount.checked_add(quantity)?))
-        .ok_or(AMMError::ArithmeticOverflow)?;
+    let quantity_received = ctx.accounts.receive_input(is_a_to_b, quantity)?;
 
+    let swap_quote = quote_swap_exact_in(
+        &ctx.accounts.amm_pool,
+        reserve_in,
+        reserve_out,
+        quantity_received,
+        is_a_to_b,
+        Clock::get()?.unix_timestamp,
+    )?;
+    // Slippage is checked on what reaches the user, which a transfer fee on the output
+    // mint makes less than what leaves the vault
+    let token_user_receives =
+        ctx.accounts
+            .send_output(ctx.bumps.authority, is_a_to_b, swap_quote.amount_out)?;
     require!(
         token_user_receives >= min_slippage_quantity,
         AMMError::SlippageExceeded
     );
 
-    let transfer_tokens_from_user_to_vault_ix = CpiContext::new(
-        ctx.accounts.token_program.to_account_info(),
-        Transfer {
-            from: user_account_in.to_account_info(),
-            to: vault_in.to_account_info(),
-            authority: ctx.accounts.user.to_account_info(),
-        },
-    );
-    transfer(transfer_tokens_from_user_to_vault_ix, quantity)?;
-
-    let token_a_mint_key = ctx.accounts.token_a_mint.key();
-    let token_b_mint_key = ctx.accounts.token_b_mint.key();
-    let seeds: &[&[u8]] = &[
-        b"authority",
-        token_a_mint_key.as_ref(),
-        token_b_mint_key.as_ref(),
-        &[ctx.bumps.authority],
-    ];
-    let signer = &[seeds];
-
-    let transfer_tokens_from_vault_to_user_ix = CpiContext::new_with_signer(
-        ctx.accounts.token_program.to_account_info(),
-        Transfer {
-            from: vault_out.to_account_info(),
-            to: user_account_out.to_account_info(),
-            authority: ctx.accounts.authority.to_account_info(),
-        },
-        signer,
-    );
-    transfer(transfer_tokens_from_vault_to_user_ix, token_user_receives)?;
+    ctx.accounts
+        .amm_pool
+        .accrue_protocol_fee(is_a_to_b, swap_quote.protocol_fee)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    ctx.accounts
+        .record_price_move(is_a_to_b, reserve_in, reserve_out)?;
 
-    Ok(())
+    let result = SwapResult {
+        amount_in: quantity,
+        amount_out: token_user_receives,
+        fee: swap_quote.fee,
+        protocol_fee: swap_quote.protocol_fee,
+        fee_numerator: swap_quote.fee_numerator,
+    };
+    ctx.accounts.emit_swapped(is_a_to_b, &result)?;
+    Ok(result)
 }
 
 #[derive(Accounts)]
@@ -116,11 +106,11 @@ pub struct Swap<'info> {
     #[account()]
     pub user: Signer<'info>,
 
-    #[account()]
-    pub token_a_mint: Account<'info, Mint>,
+    #[account(mint::token_program = token_a_program)]
+    pub token_a_mint: InterfaceAccount<'info, Mint>,
 
-    #[account()]
-    pub token_b_mint: Account<'info, Mint>,
+    #[account(mint::token_program = token_b_program)]
+    pub token_b_mint: InterfaceAccount<'info, Mint>,
 
     /// CHECK: PDA authority for signing vault transfers
     #[account(
@@ -132,30 +122,34 @@ pub struct Swap<'info> {
     #[account(
         mut,
         associated_token::mint = token_a_mint,
-        associated_token::authority = user
+        associated_token::authority = user,
+        associated_token::token_program = token_a_program
     )]
-    pub user_token_account_a: Account<'info, TokenAccount>,
+    pub user_token_account_a: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         associated_token::mint = token_b_mint,
-        associated_token::authority = user
+        associated_token::authority = user,
+        associated_token::token_program = token_b_program
     )]
-    pub user_token_account_b: Account<'info, TokenAccount>,
+    pub user_token_account_b: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         seeds = [b"vault_token", token_a_mint.key().as_ref(), token_b_mint.key().as_ref(), b"A"],
-        bump
+        bump,
+        token::token_program = token_a_program
     )]
-    pub vault_a: Account<'info, TokenAccount>,
+    pub vault_a: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         seeds = [b"vault_token", token_a_mint.key().as_ref(), token_b_mint.key().as_ref(), b"B"],
-        bump
+        bump,
+        token::token_program = token_b_program
     )]
-    pub vault_b: Account<'info, TokenAccount>,
+    pub vault_b: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
@@ -164,5 +158,201 @@ pub struct Swap<'info> {
     )]
     pub amm_pool: Account<'info, AMMPool>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_a_program: Interface<'info, TokenInterface>,
+    pub token_b_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> Swap<'info> {
+    pub fn validate_pool_accounts(&self) -> Result<()> {
+        require!(
+            self.token_a_mint.key() == self.amm_pool.mint_a,
+            AMMError::InvalidTokenMint
+        );
+        require!(
+            self.token_b_mint.key() == self.amm_pool.mint_b,
+            AMMError::InvalidTokenMint
+        );
+
+        require!(
+            self.vault_a.key() == self.amm_pool.vault_a,
+            AMMError::InvalidVault
+        );
+        require!(
+            self.vault_b.key() == self.amm_pool.vault_b,
+            AMMError::InvalidVault
+        );
+        Ok(())
+    }
+
+    // (reserve_in, reserve_out) for the requested direction, net of protocol fees
+    pub fn directional_reserves(&self, is_a_to_b: bool) -> Result<(u64, u64)> {
+        let (reserve_a, reserve_b) = self
+            .amm_pool
+            .reserves(self.vault_a.amount, self.vault_b.amount)
+            .ok_or(AMMError::ArithmeticOverflow)?;
+        Ok(if is_a_to_b {
+            (reserve_a, reserve_b)
+        } else {
+            (reserve_b, reserve_a)
+        })
+    }
+
+    // Reserves after this instruction's transfers, re-read from the vaults
+    pub fn reserves_after_transfers(&mut self) -> Result<(u64, u64)> {
+        self.vault_a.reload()?;
+        self.vault_b.reload()?;
+        let reserves = self
+            .amm_pool
+            .reserves(self.vault_a.amount, self.vault_b.amount)
+            .ok_or(AMMError::ArithmeticOverflow)?;
+        Ok(reserves)
+    }
+
+    // Feeds the price move of this swap into the pool's volatility, which the next
+    // swaps pay for when the pool is in dynamic fee mode
+    pub fn record_price_move(
+        &mut self,
+        is_a_to_b: bool,
+        reserve_in_before: u64,
+        reserve_out_before: u64,
+    ) -> Result<()> {
+        let reserves_before = if is_a_to_b {
+            (reserve_in_before, reserve_out_before)
+        } else {
+            (reserve_out_before, reserve_in_before)
+        };
+        let reserves_after = self.reserves_after_transfers()?;
+        self.amm_pool
+            .record_price_move(reserves_before, reserves_after)
+            .ok_or(AMMError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    pub fn emit_swapped(&mut self, is_a_to_b: bool, result: &SwapResult) -> Result<()> {
+        let (reserve_a, reserve_b) = self.reserves_after_transfers()?;
+        emit!(Swapped {
+            pool: self.amm_pool.key(),
+            user: self.user.key(),
+            is_a_to_b,
+            amount_in: result.amount_in,
+            amount_out: result.amount_out,
+            fee: result.fee,
+            protocol_fee: result.protocol_fee,
+            reserve_a,
+            reserve_b,
+        });
+        Ok(())
+    }
+
+    // Moves amount_in from the user into the input vault and returns how much of it
+    // actually arrived, which is less than amount_in for mints with a transfer fee.
+    pub fn receive_input(&mut self, is_a_to_b: bool, amount_in: u64) -> Result<u64> {
+        let (user_account_in, vault_in, mint_in, token_program_in) = if is_a_to_b {
+            (
+                &self.user_token_account_a,
+                &self.vault_a,
+                &self.token_a_mint,
+                &self.token_a_program,
+            )
+        } else {
+            (
+                &self.user_token_account_b,
+                &self.vault_b,
+                &self.token_b_mint,
+                &self.token_b_program,
+            )
+        };
+        let vault_balance_before = vault_in.amount;
+
+        let transfer_tokens_from_user_to_vault_ix = CpiContext::new(
+            token_program_in.to_account_info(),
+            TransferChecked {
+                from: user_account_in.to_account_info(),
+                mint: mint_in.to_account_info(),
+                to: vault_in.to_account_info(),
+                authority: self.user.to_account_info(),
+            },
+        );
+        transfer_checked(
+            transfer_tokens_from_user_to_vault_ix,
+            amount_in,
+            mint_in.decimals,
+        )?;
+
+        let vault_in = if is_a_to_b {
+            &mut self.vault_a
+        } else {
+            &mut self.vault_b
+        };
+        vault_in.reload()?;
+        let amount_received = vault_in
+            .amount
+            .checked_sub(vault_balance_before)
+            .ok_or(AMMError::ArithmeticOverflow)?;
+        Ok(amount_received)
+    }
+
+    // Pays amount_out from the output vault to the user and returns how much of it the
+    // user actually received, which is less for mints with a transfer fee.
+    pub fn send_output(
+        &mut self,
+        authority_bump: u8,
+        is_a_to_b: bool,
+        amount_out: u64,
+    ) -> Result<u64> {
+        let (vault_out, user_account_out, mint_out, token_program_out) = if is_a_to_b {
+            (
+                &self.vault_b,
+                &self.user_token_account_b,
+                &self.token_b_mint,
+                &self.token_b_program,
+            )
+        } else {
+            (
+                &self.vault_a,
+                &self.user_token_account_a,
+                &self.token_a_mint,
+                &self.token_a_program,
+            )
+        };
+
+        let token_a_mint_key = self.token_a_mint.key();
+        let token_b_mint_key = self.token_b_mint.key();
+        let seeds: &[&[u8]] = &[
+            b"authority",
+            token_a_mint_key.as_ref(),
+            token_b_mint_key.as_ref(),
+            &[authority_bump],
+        ];
+        let signer = &[seeds];
+        let user_balance_before = user_account_out.amount;
+
+        let transfer_tokens_from_vault_to_user_ix = CpiContext::new_with_signer(
+            token_program_out.to_account_info(),
+            TransferChecked {
+                from: vault_out.to_account_info(),
+                mint: mint_out.to_account_info(),
+                to: user_account_out.to_account_info(),
+                authority: self.authority.to_account_info(),
+            },
+            signer,
+        );
+        transfer_checked(
+            transfer_tokens_from_vault_to_user_ix,
+            amount_out,
+            mint_out.decimals,
+        )?;
+
+        let user_account_out = if is_a_to_b {
+            &mut self.user_token_account_b
+        } else {
+            &mut self.user_token_account_a
+        };
+        user_account_out.reload()?;
+        let amount_received = user_account_out
+            .amount
+            .checked_sub(user_balance_before)
+            .ok_or(AMMError::ArithmeticOverflow)?;
+        Ok(amount_received)
+    }
 }
@@ -0,0 +1,104 @@
+use anchor_lang::prelude::*;
+
+use crate::error::AMMError;
+use crate::instructions::swap::{Swap, SwapResult};
+use crate::quote::{amount_with_transfer_fee, quote_swap_exact_out};
+use crate::utils::check_deadline;
+
+pub fn swap_exact_out(
+    ctx: Context<Swap>,
+    amount_out: u64,
+    max_amount_in: u64,
+    is_a_to_b: bool,
+    deadline: Option<i64>,
+) -> Result<SwapResult> {
+    check_deadline(deadline)?;
+    require!(amount_out > 0, AMMError::ZeroAmount);
+
+    // Inverting the constant product formula for a fixed dy:
+    //     (x + dx)(y - dy) = x * y
+    //     dx = (x * dy) / (y - dy)
+    // dx is rounded up so the pool never pays out dy for less than the curve demands,
+    // then grossed up by the pool and protocol fees which are deducted first on the
+    // exact-input path as well, and finally by the input mint's transfer fee, if any.
+    // dy itself is grossed up by the output mint's transfer fee beforehand.
+    ctx.accounts.validate_pool_accounts()?;
+    require!(!ctx.accounts.amm_pool.paused, AMMError::PoolPaused);
+    require!(
+        ctx.accounts.amm_pool.flash_loan_amount == 0,
+        AMMError::FlashLoanInProgress
+    );
+    ctx.accounts
+        .amm_pool
+        .update_price_accumulators(ctx.accounts.vault_a.amount, ctx.accounts.vault_b.amount)?;
+
+    // The pool pays out enough for amount_out to be left once the output mint's transfer
+    // fee, if any, has been withheld
+    let (mint_in, mint_out) = if is_a_to_b {
+        (
+            ctx.accounts.token_a_mint.to_account_info(),
+            ctx.accounts.token_b_mint.to_account_info(),
+        )
+    } else {
+        (
+            ctx.accounts.token_b_mint.to_account_info(),
+            ctx.accounts.token_a_mint.to_account_info(),
+        )
+    };
+    let clock = Clock::get()?;
+    let amount_to_send = amount_with_transfer_fee(
+        mint_out.owner,
+        &mint_out.try_borrow_data()?,
+        clock.epoch,
+        amount_out,
+    )?;
+
+    let (reserve_in, reserve_out) = ctx.accounts.directional_reserves(is_a_to_b)?;
+    let swap_quote = quote_swap_exact_out(
+        &ctx.accounts.amm_pool,
+        reserve_in,
+        reserve_out,
+        amount_to_send,
+        is_a_to_b,
+        clock.unix_timestamp,
+    )?;
+    let amount_in = swap_quote.amount_in;
+
+    let amount_to_transfer = amount_with_transfer_fee(
+        mint_in.owner,
+        &mint_in.try_borrow_data()?,
+        clock.epoch,
+        amount_in,
+    )?;
+    require!(
+        amount_to_transfer <= max_amount_in,
+        AMMError::SlippageExceeded
+    );
+
+    let amount_received = ctx.accounts.receive_input(is_a_to_b, amount_to_transfer)?;
+    require!(amount_received >= amount_in, AMMError::SlippageExceeded);
+
+    let amount_user_receives =
+        ctx.accounts
+            .send_output(ctx.bumps.authority, is_a_to_b, amount_to_send)?;
+    require!(
+        amount_user_receives >= amount_out,
+        AMMError::SlippageExceeded
+    );
+    ctx.accounts
+        .amm_pool
+        .accrue_protocol_fee(is_a_to_b, swap_quote.protocol_fee)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    ctx.accounts
+        .record_price_move(is_a_to_b, reserve_in, reserve_out)?;
+
+    let result = SwapResult {
+        amount_in: amount_to_transfer,
+        amount_out: amount_user_receives,
+        fee: swap_quote.fee,
+        protocol_fee: swap_quote.protocol_fee,
+        fee_numerator: swap_quote.fee_numerator,
+    };
+    ctx.accounts.emit_swapped(is_a_to_b, &result)?;
+    Ok(result)
+}
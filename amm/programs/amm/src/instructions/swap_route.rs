@@ -0,0 +1,342 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
+
+use crate::error::AMMError;
+use crate::events::Swapped;
+use crate::quote::quote_swap_exact_in;
+use crate::state::AMMPool;
+use crate::utils::check_deadline;
+
+// amm_pool, authority, vault_a, vault_b, token_a_mint, token_b_mint, token_a_program,
+// token_b_program for every pool along the route, in trading order
+const ACCOUNTS_PER_HOP: usize = 8;
+
+pub fn swap_route<'info>(
+    ctx: Context<'_, '_, 'info, 'info, SwapRoute<'info>>,
+    amount_in: u64,
+    min_amount_out: u64,
+    deadline: Option<i64>,
+) -> Result<u64> {
+    check_deadline(deadline)?;
+    require!(amount_in > 0, AMMError::ZeroAmount);
+
+    let remaining_accounts = ctx.remaining_accounts;
+    require!(
+        !remaining_accounts.is_empty()
+            && remaining_accounts
+                .chunks_exact(ACCOUNTS_PER_HOP)
+                .remainder()
+                .is_empty(),
+        AMMError::InvalidRoute
+    );
+    require_keys_neq!(
+        ctx.accounts.user_token_in.key(),
+        ctx.accounts.user_token_out.key(),
+        AMMError::InvalidRoute
+    );
+
+    // Each pool is priced on the reserves it held before the route started, so a pool
+    // may only appear once.
+    let mut hops: Vec<RouteHop<'info>> =
+        Vec::with_capacity(remaining_accounts.len() / ACCOUNTS_PER_HOP);
+    let mut mint_in = ctx.accounts.user_token_in.mint;
+    for hop_accounts in remaining_accounts.chunks(ACCOUNTS_PER_HOP) {
+        let hop = RouteHop::load(hop_accounts, mint_in)?;
+        require!(
+            hops.iter()
+                .all(|previous| previous.amm_pool.key() != hop.amm_pool.key()),
+            AMMError::InvalidRoute
+        );
+        mint_in = hop.mint_out().key();
+        hops.push(hop);
+    }
+    require_keys_eq!(
+        ctx.accounts.user_token_out.mint,
+        mint_in,
+        AMMError::InvalidRoute
+    );
+
+    let first_hop = &mut hops[0];
+    let vault_balance_before = first_hop.vault_in().amount;
+    let transfer_tokens_from_user_to_vault_ix = CpiContext::new(
+        first_hop.token_program_in().to_account_info(),
+        TransferChecked {
+            from: ctx.accounts.user_token_in.to_account_info(),
+            mint: first_hop.mint_in().to_account_info(),
+            to: first_hop.vault_in().to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        },
+    );
+    transfer_checked(
+        transfer_tokens_from_user_to_vault_ix,
+        amount_in,
+        first_hop.mint_in().decimals,
+    )?;
+    let mut amount = first_hop.received_since(vault_balance_before)?;
+
+    // Every hop pays its output straight into the next pool's input vault, or into the
+    // user's account on the last one, and the next hop trades what actually arrived.
+    let now = Clock::get()?.unix_timestamp;
+    for hop_index in 0..hops.len() {
+        let (hop, remaining_hops) = hops[hop_index..]
+            .split_first_mut()
+            .ok_or(AMMError::InvalidRoute)?;
+
+        let swap_quote = quote_swap_exact_in(
+            &hop.amm_pool,
+            hop.reserve_in,
+            hop.reserve_out,
+            amount,
+            hop.is_a_to_b,
+            now,
+        )?;
+        let amount_out = swap_quote.amount_out;
+        hop.amm_pool
+            .accrue_protocol_fee(hop.is_a_to_b, swap_quote.protocol_fee)
+            .ok_or(AMMError::ArithmeticOverflow)?;
+
+        let amount_received = match remaining_hops.first_mut() {
+            Some(next_hop) => {
+                let vault_balance_before = next_hop.vault_in().amount;
+                hop.send_output(next_hop.vault_in().to_account_info(), amount_out)?;
+                next_hop.received_since(vault_balance_before)?
+            }
+            None => {
+                let user_balance_before = ctx.accounts.user_token_out.amount;
+                hop.send_output(ctx.accounts.user_token_out.to_account_info(), amount_out)?;
+                ctx.accounts.user_token_out.reload()?;
+                ctx.accounts
+                    .user_token_out
+                    .amount
+                    .checked_sub(user_balance_before)
+                    .ok_or(AMMError::ArithmeticOverflow)?
+            }
+        };
+
+        hop.vault_a.reload()?;
+        hop.vault_b.reload()?;
+        let (reserve_a, reserve_b) = hop
+            .amm_pool
+            .reserves(hop.vault_a.amount, hop.vault_b.amount)
+            .ok_or(AMMError::ArithmeticOverflow)?;
+        let reserves_before = if hop.is_a_to_b {
+            (hop.reserve_in, hop.reserve_out)
+        } else {
+            (hop.reserve_out, hop.reserve_in)
+        };
+        hop.amm_pool
+            .record_price_move(reserves_before, (reserve_a, reserve_b))
+            .ok_or(AMMError::ArithmeticOverflow)?;
+        emit!(Swapped {
+            pool: hop.amm_pool.key(),
+            user: ctx.accounts.user.key(),
+            is_a_to_b: hop.is_a_to_b,
+            amount_in: amount,
+            amount_out,
+            fee: swap_quote.fee,
+            protocol_fee: swap_quote.protocol_fee,
+            reserve_a,
+            reserve_b,
+        });
+        hop.amm_pool.exit(&crate::ID)?;
+
+        amount = amount_received;
+    }
+
+    require!(amount >= min_amount_out, AMMError::SlippageExceeded);
+    Ok(amount)
+}
+
+#[derive(Accounts)]
+pub struct SwapRoute<'info> {
+    pub user: Signer<'info>,
+
+    #[account(mut, token::authority = user)]
+    pub user_token_in: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, token::authority = user)]
+    pub user_token_out: InterfaceAccount<'info, TokenAccount>,
+}
+
+struct RouteHop<'info> {
+    amm_pool: Account<'info, AMMPool>,
+    authority: AccountInfo<'info>,
+    authority_bump: u8,
+    vault_a: InterfaceAccount<'info, TokenAccount>,
+    vault_b: InterfaceAccount<'info, TokenAccount>,
+    token_a_mint: InterfaceAccount<'info, Mint>,
+    token_b_mint: InterfaceAccount<'info, Mint>,
+    token_a_program: Interface<'info, TokenInterface>,
+    token_b_program: Interface<'info, TokenInterface>,
+    is_a_to_b: bool,
+    // Pricing reserves from before the route touched this pool
+    reserve_in: u64,
+    reserve_out: u64,
+}
+
+impl<'info> RouteHop<'info> {
+    // Checks the same relationships the Swap accounts constraints enforce, since
+    // remaining accounts come in unchecked
+    fn load(accounts: &'info [AccountInfo<'info>], mint_in: Pubkey) -> Result<Self> {
+        let [amm_pool, authority, vault_a, vault_b, token_a_mint, token_b_mint, token_a_program, token_b_program] =
+            accounts
+        else {
+            return err!(AMMError::InvalidRoute);
+        };
+
+        require!(amm_pool.is_writable, AMMError::InvalidRoute);
+        let mut amm_pool = Account::<AMMPool>::try_from(amm_pool)?;
+        require!(!amm_pool.paused, AMMError::PoolPaused);
+        require!(
+            amm_pool.flash_loan_amount == 0,
+            AMMError::FlashLoanInProgress
+        );
+
+        require_keys_eq!(
+            token_a_mint.key(),
+            amm_pool.mint_a,
+            AMMError::InvalidTokenMint
+        );
+        require_keys_eq!(
+            token_b_mint.key(),
+            amm_pool.mint_b,
+            AMMError::InvalidTokenMint
+        );
+        require_keys_eq!(vault_a.key(), amm_pool.vault_a, AMMError::InvalidVault);
+        require_keys_eq!(vault_b.key(), amm_pool.vault_b, AMMError::InvalidVault);
+
+        let (expected_authority, authority_bump) = Pubkey::find_program_address(
+            &[
+                b"authority",
+                amm_pool.mint_a.as_ref(),
+                amm_pool.mint_b.as_ref(),
+            ],
+            &crate::ID,
+        );
+        require_keys_eq!(authority.key(), expected_authority, AMMError::InvalidRoute);
+
+        let token_a_program = Interface::<TokenInterface>::try_from(token_a_program)?;
+        let token_b_program = Interface::<TokenInterface>::try_from(token_b_program)?;
+        require_keys_eq!(
+            *token_a_mint.owner,
+            token_a_program.key(),
+            AMMError::InvalidRoute
+        );
+        require_keys_eq!(
+            *token_b_mint.owner,
+            token_b_program.key(),
+            AMMError::InvalidRoute
+        );
+
+        let is_a_to_b = if mint_in == amm_pool.mint_a {
+            true
+        } else {
+            require_keys_eq!(mint_in, amm_pool.mint_b, AMMError::InvalidRoute);
+            false
+        };
+
+        let vault_a = InterfaceAccount::<TokenAccount>::try_from(vault_a)?;
+        let vault_b = InterfaceAccount::<TokenAccount>::try_from(vault_b)?;
+        amm_pool.update_price_accumulators(vault_a.amount, vault_b.amount)?;
+        let (reserve_a, reserve_b) = amm_pool
+            .reserves(vault_a.amount, vault_b.amount)
+            .ok_or(AMMError::ArithmeticOverflow)?;
+        let (reserve_in, reserve_out) = if is_a_to_b {
+            (reserve_a, reserve_b)
+        } else {
+            (reserve_b, reserve_a)
+        };
+
+        Ok(RouteHop {
+            amm_pool,
+            authority: authority.clone(),
+            authority_bump,
+            vault_a,
+            vault_b,
+            token_a_mint: InterfaceAccount::<Mint>::try_from(token_a_mint)?,
+            token_b_mint: InterfaceAccount::<Mint>::try_from(token_b_mint)?,
+            token_a_program,
+            token_b_program,
+            is_a_to_b,
+            reserve_in,
+            reserve_out,
+        })
+    }
+
+    fn vault_in(&self) -> &InterfaceAccount<'info, TokenAccount> {
+        if self.is_a_to_b {
+            &self.vault_a
+        } else {
+            &self.vault_b
+        }
+    }
+
+    fn mint_in(&self) -> &InterfaceAccount<'info, Mint> {
+        if self.is_a_to_b {
+            &self.token_a_mint
+        } else {
+            &self.token_b_mint
+        }
+    }
+
+    fn mint_out(&self) -> &InterfaceAccount<'info, Mint> {
+        if self.is_a_to_b {
+            &self.token_b_mint
+        } else {
+            &self.token_a_mint
+        }
+    }
+
+    fn token_program_in(&self) -> &Interface<'info, TokenInterface> {
+        if self.is_a_to_b {
+            &self.token_a_program
+        } else {
+            &self.token_b_program
+        }
+    }
+
+    // How much arrived in the input vault since it held vault_balance_before
+    fn received_since(&mut self, vault_balance_before: u64) -> Result<u64> {
+        let vault_in = if self.is_a_to_b {
+            &mut self.vault_a
+        } else {
+            &mut self.vault_b
+        };
+        vault_in.reload()?;
+        let amount_received = vault_in
+            .amount
+            .checked_sub(vault_balance_before)
+            .ok_or(AMMError::ArithmeticOverflow)?;
+        Ok(amount_received)
+    }
+
+    fn send_output(&self, destination: AccountInfo<'info>, amount_out: u64) -> Result<()> {
+        let (vault_out, mint_out, token_program_out) = if self.is_a_to_b {
+            (&self.vault_b, &self.token_b_mint, &self.token_b_program)
+        } else {
+            (&self.vault_a, &self.token_a_mint, &self.token_a_program)
+        };
+
+        let seeds: &[&[u8]] = &[
+            b"authority",
+            self.amm_pool.mint_a.as_ref(),
+            self.amm_pool.mint_b.as_ref(),
+            &[self.authority_bump],
+        ];
+        let signer = &[seeds];
+
+        let transfer_tokens_from_vault_ix = CpiContext::new_with_signer(
+            token_program_out.to_account_info(),
+            TransferChecked {
+                from: vault_out.to_account_info(),
+                mint: mint_out.to_account_info(),
+                to: destination,
+                authority: self.authority.clone(),
+            },
+            signer,
+        );
+        transfer_checked(transfer_tokens_from_vault_ix, amount_out, mint_out.decimals)
+    }
+}
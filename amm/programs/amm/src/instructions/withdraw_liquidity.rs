@@ -1,111 +1,70 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{
-    burn, close_account, transfer, Burn, CloseAccount, Mint, Token, TokenAccount, Transfer,
+use anchor_spl::token::Token;
+use anchor_spl::token_interface::{
+    burn, close_account, transfer_checked, Burn, CloseAccount, Mint, TokenAccount, TokenInterface,
+    TransferChecked,
 };
 
-use crate::{amm, error::AMMError, state::AMMPool};
+use crate::{error::AMMError, events::LiquidityWithdrawn, quote::quote_withdraw, state::AMMPool};
 
-pub fn withdraw_liquidity(ctx: Context<WithdrawLiquidity>, lp_token_quantity: u64) -> Result<()> {
-    let liquidity_provider = &mut ctx.accounts.liquidity_provider;
-    let token_account_a = &mut ctx.accounts.token_a_account;
-    let token_account_b = &mut ctx.accounts.token_b_account;
-    let vault_a = &mut ctx.accounts.vault_a;
-    let vault_b = &mut ctx.accounts.vault_b;
-    let amm_pool = &mut ctx.accounts.amm_pool;
-    let lp_token_account = &mut ctx.accounts.lp_token_account;
-
-    require!(
-        vault_a.amount > 0 && vault_b.amount > 0,
-        AMMError::PoolEmpty
-    );
+pub fn withdraw_liquidity(
+    ctx: Context<WithdrawLiquidity>,
+    lp_token_quantity: u64,
+    min_amount_a: u64,
+    min_amount_b: u64,
+) -> Result<()> {
+    ctx.accounts.validate_pool_accounts()?;
     require!(
-        lp_token_quantity <= lp_token_account.amount,
-        AMMError::InsufficientLPTokens
-    );
-    require!(
-        ctx.accounts.token_a_mint.key() == amm_pool.mint_a,
-        AMMError::InvalidTokenMint
-    );
-    require!(
-        ctx.accounts.token_b_mint.key() == amm_pool.mint_b,
-        AMMError::InvalidTokenMint
+        ctx.accounts.amm_pool.flash_loan_amount == 0,
+        AMMError::FlashLoanInProgress
     );
+    ctx.accounts
+        .amm_pool
+        .update_price_accumulators(ctx.accounts.vault_a.amount, ctx.accounts.vault_b.amount)?;
 
-    let token_a_quantity_to_release = lp_token_quantity
-        .checked_mul(vault_a.amount)
-        .and_then(|v| v.checked_div(amm_pool.total_lp_issued))
-        .ok_or(AMMError::ArithmeticOverflow)?;
-
-    let token_b_quantity_to_release = lp_token_quantity
-        .checked_mul(vault_b.amount)
-        .and_then(|v| v.checked_div(amm_pool.total_lp_issued))
-        .ok_or(AMMError::ArithmeticOverflow)?;
-
-    let burn_redeemed_lp_tokens = CpiContext::new(
-        ctx.accounts.token_program.to_account_info(),
-        Burn {
-            mint: ctx.accounts.lp_token_mint.to_account_info(),
-            from: lp_token_account.to_account_info(),
-            authority: liquidity_provider.to_account_info(),
-        },
-    );
+    let (token_a_quantity_to_release, token_b_quantity_to_release) =
+        ctx.accounts.redeemable_amounts(lp_token_quantity)?;
 
-    burn(burn_redeemed_lp_tokens, lp_token_quantity)?;
-    amm_pool.total_lp_issued -= lp_token_quantity;
-
-    let seeds: &[&[u8]] = &[
-        b"authority",
-        amm_pool.mint_a.as_ref(),
-        amm_pool.mint_b.as_ref(),
-        &[ctx.bumps.authority],
-    ];
-    let signer_seeds = &[seeds];
-
-    let transfer_quantiy_a_to_user_ix = CpiContext::new_with_signer(
-        ctx.accounts.token_program.to_account_info(),
-        Transfer {
-            from: vault_a.to_account_info(),
-            to: token_account_a.to_account_info(),
-            authority: ctx.accounts.authority.to_account_info(),
-        },
-        signer_seeds,
-    );
-
-    let transfer_quantiy_b_to_user_ix = CpiContext::new_with_signer(
-        ctx.accounts.token_program.to_account_info(),
-        Transfer {
-            from: vault_b.to_account_info(),
-            to: token_account_b.to_account_info(),
-            authority: ctx.accounts.authority.to_account_info(),
-        },
-        signer_seeds,
+    ctx.accounts.burn_lp_tokens(lp_token_quantity)?;
+    let token_a_received =
+        ctx.accounts
+            .pay_out(ctx.bumps.authority, true, token_a_quantity_to_release)?;
+    let token_b_received =
+        ctx.accounts
+            .pay_out(ctx.bumps.authority, false, token_b_quantity_to_release)?;
+    // The bounds apply to what reaches the liquidity provider, which a transfer fee on
+    // either mint makes less than what leaves the vaults
+    require!(
+        token_a_received >= min_amount_a && token_b_received >= min_amount_b,
+        AMMError::SlippageExceeded
     );
+    ctx.accounts
+        .close_lp_account_if_emptied(lp_token_quantity)?;
 
-    transfer(transfer_quantiy_a_to_user_ix, token_a_quantity_to_release)?;
-    transfer(transfer_quantiy_b_to_user_ix, token_b_quantity_to_release)?;
+    let (reserve_a, reserve_b) = ctx.accounts.reserves_after_transfers()?;
+    emit!(LiquidityWithdrawn {
+        pool: ctx.accounts.amm_pool.key(),
+        user: ctx.accounts.liquidity_provider.key(),
+        amount_a: token_a_quantity_to_release,
+        amount_b: token_b_quantity_to_release,
+        lp_burned: lp_token_quantity,
+        reserve_a,
+        reserve_b,
+    });
 
-    if lp_token_quantity == lp_token_account.amount {
-        let close_ctx = CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            CloseAccount {
-                account: lp_token_account.to_account_info(),
-                destination: ctx.accounts.liquidity_provider.to_account_info(),
-                authority: ctx.accounts.liquidity_provider.to_account_info(),
-            },
-        );
-        close_account(close_ctx)?;
-    }
     Ok(())
 }
 
 #[derive(Accounts)]
 pub struct WithdrawLiquidity<'info> {
+    // Receives the rent of the LP token account when a withdrawal empties it
+    #[account(mut)]
     pub liquidity_provider: Signer<'info>,
 
-    #[account()]
-    pub token_a_mint: Account<'info, Mint>,
-    #[account()]
-    pub token_b_mint: Account<'info, Mint>,
+    #[account(mint::token_program = token_a_program)]
+    pub token_a_mint: InterfaceAccount<'info, Mint>,
+    #[account(mint::token_program = token_b_program)]
+    pub token_b_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
@@ -120,44 +79,181 @@ pub struct WithdrawLiquidity<'info> {
     #[account(
         mut,
         associated_token::mint = token_a_mint,
-        associated_token::authority = liquidity_provider)]
-    pub token_a_account: Account<'info, TokenAccount>,
+        associated_token::authority = liquidity_provider,
+        associated_token::token_program = token_a_program)]
+    pub token_a_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         associated_token::mint = token_b_mint,
-        associated_token::authority = liquidity_provider)]
-    pub token_b_account: Account<'info, TokenAccount>,
+        associated_token::authority = liquidity_provider,
+        associated_token::token_program = token_b_program)]
+    pub token_b_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         seeds = [b"vault_token", token_a_mint.key().as_ref(), token_b_mint.key().as_ref(), b"A"],
         bump,
-        token::mint = token_a_mint, 
-        token::authority = authority
+        token::mint = token_a_mint,
+        token::authority = authority,
+        token::token_program = token_a_program
     )]
-    pub vault_a: Account<'info, TokenAccount>,
+    pub vault_a: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         seeds = [b"vault_token", token_a_mint.key().as_ref(), token_b_mint.key().as_ref(), b"B"],
         bump,
         token::mint = token_b_mint,
-        token::authority = authority
+        token::authority = authority,
+        token::token_program = token_b_program
     )]
-    pub vault_b: Account<'info, TokenAccount>,
+    pub vault_b: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         seeds = [b"lp_mint", token_a_mint.key().as_ref(), token_b_mint.key().as_ref()],
         bump,
     )]
-    pub lp_token_mint: Account<'info, Mint>,
+    pub lp_token_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
         associated_token::mint = lp_token_mint,
         associated_token::authority = liquidity_provider)]
-    pub lp_token_account: Account<'info, TokenAccount>,
+    pub lp_token_account: InterfaceAccount<'info, TokenAccount>,
     pub token_program: Program<'info, Token>,
+    pub token_a_program: Interface<'info, TokenInterface>,
+    pub token_b_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> WithdrawLiquidity<'info> {
+    pub fn validate_pool_accounts(&self) -> Result<()> {
+        require!(
+            self.token_a_mint.key() == self.amm_pool.mint_a,
+            AMMError::InvalidTokenMint
+        );
+        require!(
+            self.token_b_mint.key() == self.amm_pool.mint_b,
+            AMMError::InvalidTokenMint
+        );
+        Ok(())
+    }
+
+    // Proportional (token A, token B) owed for burning lp_token_quantity
+    pub fn redeemable_amounts(&self, lp_token_quantity: u64) -> Result<(u64, u64)> {
+        let (reserve_a, reserve_b) = self
+            .amm_pool
+            .reserves(self.vault_a.amount, self.vault_b.amount)
+            .ok_or(AMMError::ArithmeticOverflow)?;
+
+        require!(reserve_a > 0 && reserve_b > 0, AMMError::PoolEmpty);
+        require!(
+            lp_token_quantity <= self.lp_token_account.amount,
+            AMMError::InsufficientLPTokens
+        );
+
+        let withdraw_quote =
+            quote_withdraw(&self.amm_pool, reserve_a, reserve_b, lp_token_quantity)?;
+        Ok((withdraw_quote.amount_a, withdraw_quote.amount_b))
+    }
+
+    pub fn burn_lp_tokens(&mut self, lp_token_quantity: u64) -> Result<()> {
+        let burn_redeemed_lp_tokens = CpiContext::new(
+            self.token_program.to_account_info(),
+            Burn {
+                mint: self.lp_token_mint.to_account_info(),
+                from: self.lp_token_account.to_account_info(),
+                authority: self.liquidity_provider.to_account_info(),
+            },
+        );
+
+        burn(burn_redeemed_lp_tokens, lp_token_quantity)?;
+        self.amm_pool.total_lp_issued = self
+            .amm_pool
+            .total_lp_issued
+            .checked_sub(lp_token_quantity)
+            .ok_or(AMMError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    // Sends quantity from the vault to the liquidity provider and returns how much
+    // actually arrived
+    pub fn pay_out(&mut self, authority_bump: u8, is_a: bool, quantity: u64) -> Result<u64> {
+        let (from, to, mint, token_program) = if is_a {
+            (
+                &self.vault_a,
+                &self.token_a_account,
+                &self.token_a_mint,
+                &self.token_a_program,
+            )
+        } else {
+            (
+                &self.vault_b,
+                &self.token_b_account,
+                &self.token_b_mint,
+                &self.token_b_program,
+            )
+        };
+
+        let seeds: &[&[u8]] = &[
+            b"authority",
+            self.amm_pool.mint_a.as_ref(),
+            self.amm_pool.mint_b.as_ref(),
+            &[authority_bump],
+        ];
+        let signer_seeds = &[seeds];
+        let user_balance_before = to.amount;
+
+        let transfer_quantity_to_user_ix = CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            TransferChecked {
+                from: from.to_account_info(),
+                mint: mint.to_account_info(),
+                to: to.to_account_info(),
+                authority: self.authority.to_account_info(),
+            },
+            signer_seeds,
+        );
+        transfer_checked(transfer_quantity_to_user_ix, quantity, mint.decimals)?;
+
+        let to = if is_a {
+            &mut self.token_a_account
+        } else {
+            &mut self.token_b_account
+        };
+        to.reload()?;
+        let quantity_received = to
+            .amount
+            .checked_sub(user_balance_before)
+            .ok_or(AMMError::ArithmeticOverflow)?;
+        Ok(quantity_received)
+    }
+
+    // Reserves after this instruction's transfers, re-read from the vaults
+    pub fn reserves_after_transfers(&mut self) -> Result<(u64, u64)> {
+        self.vault_a.reload()?;
+        self.vault_b.reload()?;
+        let reserves = self
+            .amm_pool
+            .reserves(self.vault_a.amount, self.vault_b.amount)
+            .ok_or(AMMError::ArithmeticOverflow)?;
+        Ok(reserves)
+    }
+
+    // lp_token_account.amount still holds the pre-burn balance here
+    pub fn close_lp_account_if_emptied(&self, lp_token_quantity: u64) -> Result<()> {
+        if lp_token_quantity == self.lp_token_account.amount {
+            let close_ctx = CpiContext::new(
+                self.token_program.to_account_info(),
+                CloseAccount {
+                    account: self.lp_token_account.to_account_info(),
+                    destination: self.liquidity_provider.to_account_info(),
+                    authority: self.liquidity_provider.to_account_info(),
+                },
+            );
+            close_account(close_ctx)?;
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,109 @@
+use anchor_lang::prelude::*;
+
+use crate::error::AMMError;
+use crate::events::LiquidityWithdrawn;
+use crate::instructions::withdraw_liquidity::WithdrawLiquidity;
+use crate::quote::quote_swap_exact_in;
+
+pub fn withdraw_liquidity_single(
+    ctx: Context<WithdrawLiquidity>,
+    lp_token_quantity: u64,
+    want_a: bool,
+    min_out: u64,
+) -> Result<()> {
+    ctx.accounts.validate_pool_accounts()?;
+    // Swapping the unwanted side back in is still a trade against the pool, so unlike
+    // the proportional withdraw_liquidity it is blocked while the pool is paused.
+    require!(!ctx.accounts.amm_pool.paused, AMMError::PoolPaused);
+    require!(
+        ctx.accounts.amm_pool.flash_loan_amount == 0,
+        AMMError::FlashLoanInProgress
+    );
+    ctx.accounts
+        .amm_pool
+        .update_price_accumulators(ctx.accounts.vault_a.amount, ctx.accounts.vault_b.amount)?;
+
+    let (token_a_quantity, token_b_quantity) =
+        ctx.accounts.redeemable_amounts(lp_token_quantity)?;
+
+    let amm_pool = &mut ctx.accounts.amm_pool;
+    let (reserve_a, reserve_b) = amm_pool
+        .reserves(ctx.accounts.vault_a.amount, ctx.accounts.vault_b.amount)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    let reserve_a_after_withdraw = reserve_a
+        .checked_sub(token_a_quantity)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    let reserve_b_after_withdraw = reserve_b
+        .checked_sub(token_b_quantity)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+
+    // The unwanted share is swapped back into the pool (fees included) against the
+    // reserves that remain once both proportional shares have been taken out.
+    let (wanted_quantity, unwanted_quantity, reserve_in, reserve_out) = if want_a {
+        (
+            token_a_quantity,
+            token_b_quantity,
+            reserve_b_after_withdraw,
+            reserve_a_after_withdraw,
+        )
+    } else {
+        (
+            token_b_quantity,
+            token_a_quantity,
+            reserve_a_after_withdraw,
+            reserve_b_after_withdraw,
+        )
+    };
+
+    let swap_quote = quote_swap_exact_in(
+        amm_pool,
+        reserve_in,
+        reserve_out,
+        unwanted_quantity,
+        !want_a,
+        Clock::get()?.unix_timestamp,
+    )?;
+    let (swapped_out, protocol_fee) = (swap_quote.amount_out, swap_quote.protocol_fee);
+
+    let quantity_to_release = wanted_quantity
+        .checked_add(swapped_out)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+
+    amm_pool
+        .accrue_protocol_fee(!want_a, protocol_fee)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+
+    ctx.accounts.burn_lp_tokens(lp_token_quantity)?;
+    let quantity_received =
+        ctx.accounts
+            .pay_out(ctx.bumps.authority, want_a, quantity_to_release)?;
+    // Checked on what reaches the liquidity provider, net of any transfer fee
+    require!(quantity_received >= min_out, AMMError::SlippageExceeded);
+    ctx.accounts
+        .close_lp_account_if_emptied(lp_token_quantity)?;
+
+    // The proportional part keeps the price where it was, so the whole move comes from
+    // swapping the unwanted share back in
+    let reserves_before = (reserve_a, reserve_b);
+    let (reserve_a, reserve_b) = ctx.accounts.reserves_after_transfers()?;
+    ctx.accounts
+        .amm_pool
+        .record_price_move(reserves_before, (reserve_a, reserve_b))
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    let (amount_a, amount_b) = if want_a {
+        (quantity_to_release, 0)
+    } else {
+        (0, quantity_to_release)
+    };
+    emit!(LiquidityWithdrawn {
+        pool: ctx.accounts.amm_pool.key(),
+        user: ctx.accounts.liquidity_provider.key(),
+        amount_a,
+        amount_b,
+        lp_burned: lp_token_quantity,
+        reserve_a,
+        reserve_b,
+    });
+
+    Ok(())
+}
@@ -1,43 +1,180 @@
 use anchor_lang::prelude::*;
 
-mod error;
+pub mod error;
+mod events;
 mod instructions;
-mod state;
+pub mod oracle;
+pub mod quote;
+pub mod state;
 mod utils;
 
 use instructions::*;
+use oracle::PriceObservation;
+use state::CurveConfig;
 
 declare_id!("8igYFZBtEYMLPmeeWNo1aFKwhMQfh7aEBJFVspu4vSff");
 
-#[program]
-pub mod amm {
+// Anchor 0.31 expands `#[program]` into IDL handlers that call the deprecated
+// `AccountInfo::realloc`. They are generated next to the program module rather than
+// inside it, so the allow goes on a module wrapping both.
+#[allow(deprecated)]
+mod anchor_program {
     use super::*;
 
-    pub fn initialize_pool(ctx: Context<InitPool>) -> Result<()> {
-        instructions::initialize_pool::initialize_pool(ctx)
-    }
+    #[program]
+    pub mod amm {
+        use super::*;
 
-    pub fn add_liquidity(
-        ctx: Context<AddLiquidity>,
-        quantity_a: u64,
-        quantity_b: u64,
-    ) -> Result<()> {
-        instructions::add_liquidity::add_liquidity(ctx, quantity_a, quantity_b)
-    }
+        pub fn initialize_registry(ctx: Context<InitRegistry>) -> Result<()> {
+            instructions::initialize_registry::initialize_registry(ctx)
+        }
 
-    pub fn withdraw_liquidity(
-        ctx: Context<WithdrawLiquidity>,
-        lp_token_quantity: u64,
-    ) -> Result<()> {
-        instructions::withdraw_liquidity::withdraw_liquidity(ctx, lp_token_quantity)
-    }
+        pub fn initialize_pool(
+            ctx: Context<InitPool>,
+            fee_numerator: u64,
+            fee_denominator: u64,
+            protocol_fee_numerator: u64,
+            fee_authority: Pubkey,
+            curve: CurveConfig,
+        ) -> Result<()> {
+            instructions::initialize_pool::initialize_pool(
+                ctx,
+                fee_numerator,
+                fee_denominator,
+                protocol_fee_numerator,
+                fee_authority,
+                curve,
+            )
+        }
+
+        pub fn add_liquidity(
+            ctx: Context<AddLiquidity>,
+            max_quantity_a: u64,
+            max_quantity_b: u64,
+            min_lp_out: u64,
+            deadline: Option<i64>,
+        ) -> Result<()> {
+            instructions::add_liquidity::add_liquidity(
+                ctx,
+                max_quantity_a,
+                max_quantity_b,
+                min_lp_out,
+                deadline,
+            )
+        }
+
+        pub fn add_liquidity_single_sided(
+            ctx: Context<AddLiquidity>,
+            quantity: u64,
+            is_a: bool,
+            min_lp_out: u64,
+            deadline: Option<i64>,
+        ) -> Result<()> {
+            instructions::add_liquidity_single_sided::add_liquidity_single_sided(
+                ctx, quantity, is_a, min_lp_out, deadline,
+            )
+        }
+
+        pub fn withdraw_liquidity(
+            ctx: Context<WithdrawLiquidity>,
+            lp_token_quantity: u64,
+            min_amount_a: u64,
+            min_amount_b: u64,
+        ) -> Result<()> {
+            instructions::withdraw_liquidity::withdraw_liquidity(
+                ctx,
+                lp_token_quantity,
+                min_amount_a,
+                min_amount_b,
+            )
+        }
+
+        pub fn withdraw_liquidity_single(
+            ctx: Context<WithdrawLiquidity>,
+            lp_token_quantity: u64,
+            want_a: bool,
+            min_out: u64,
+        ) -> Result<()> {
+            instructions::withdraw_liquidity_single::withdraw_liquidity_single(
+                ctx,
+                lp_token_quantity,
+                want_a,
+                min_out,
+            )
+        }
+
+        pub fn swap(
+            ctx: Context<Swap>,
+            quantity: u64,
+            minimum_slippage_quantity: u64,
+            is_a_to_b: bool,
+            deadline: Option<i64>,
+        ) -> Result<SwapResult> {
+            instructions::swap::swap(
+                ctx,
+                quantity,
+                minimum_slippage_quantity,
+                is_a_to_b,
+                deadline,
+            )
+        }
+
+        pub fn swap_exact_out(
+            ctx: Context<Swap>,
+            amount_out: u64,
+            max_amount_in: u64,
+            is_a_to_b: bool,
+            deadline: Option<i64>,
+        ) -> Result<SwapResult> {
+            instructions::swap_exact_out::swap_exact_out(
+                ctx,
+                amount_out,
+                max_amount_in,
+                is_a_to_b,
+                deadline,
+            )
+        }
+
+        pub fn swap_route<'info>(
+            ctx: Context<'_, '_, 'info, 'info, SwapRoute<'info>>,
+            amount_in: u64,
+            min_amount_out: u64,
+            deadline: Option<i64>,
+        ) -> Result<u64> {
+            instructions::swap_route::swap_route(ctx, amount_in, min_amount_out, deadline)
+        }
+
+        pub fn flash_borrow(ctx: Context<FlashLoan>, amount: u64, is_a: bool) -> Result<()> {
+            instructions::flash_loan::flash_borrow(ctx, amount, is_a)
+        }
+
+        pub fn flash_repay(ctx: Context<FlashLoan>) -> Result<()> {
+            instructions::flash_loan::flash_repay(ctx)
+        }
+
+        pub fn collect_protocol_fees(ctx: Context<CollectProtocolFees>) -> Result<()> {
+            instructions::collect_protocol_fees::collect_protocol_fees(ctx)
+        }
+
+        pub fn set_admin(ctx: Context<PoolAdmin>, new_admin: Pubkey) -> Result<()> {
+            instructions::set_admin::set_admin(ctx, new_admin)
+        }
+
+        pub fn set_dynamic_fee(ctx: Context<PoolAdmin>, max_fee_numerator: u64) -> Result<()> {
+            instructions::set_dynamic_fee::set_dynamic_fee(ctx, max_fee_numerator)
+        }
+
+        pub fn pause_pool(ctx: Context<PoolAdmin>) -> Result<()> {
+            instructions::pause_pool::pause_pool(ctx)
+        }
+
+        pub fn unpause_pool(ctx: Context<PoolAdmin>) -> Result<()> {
+            instructions::pause_pool::unpause_pool(ctx)
+        }
 
-    pub fn swap(
-        ctx: Context<Swap>,
-        quantity: u64,
-        minimum_slippage_quantity: u64,
-        is_a_to_b: bool,
-    ) -> Result<()> {
-        instructions::swap::swap(ctx, quantity, minimum_slippage_quantity, is_a_to_b)
+        pub fn observe(ctx: Context<Observe>) -> Result<PriceObservation> {
+            instructions::observe::observe(ctx)
+        }
     }
 }
+pub use anchor_program::*;
@@ -0,0 +1,53 @@
+use anchor_lang::prelude::*;
+
+// Snapshot of a pool's cumulative prices. The accumulators are allowed to wrap, so only
+// the difference between two observations of the same pool carries any meaning.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PriceObservation {
+    pub price_a_cumulative: u128,
+    pub price_b_cumulative: u128,
+    pub timestamp: i64,
+}
+
+// Price of one unit of the base token in quote tokens as a Q64.64 fixed point number
+pub fn price_x64(reserve_base: u64, reserve_quote: u64) -> Option<u128> {
+    ((reserve_quote as u128) << 64).checked_div(reserve_base as u128)
+}
+
+impl PriceObservation {
+    // Rolls the accumulators forward to `now`, weighting the price implied by the given
+    // reserves with the seconds elapsed since the last observation.
+    pub fn advance(&self, reserve_a: u64, reserve_b: u64, now: i64) -> Option<PriceObservation> {
+        let elapsed = u128::try_from(now.checked_sub(self.timestamp)?).ok()?;
+        let mut next = *self;
+        next.timestamp = now;
+        if elapsed > 0 && reserve_a > 0 && reserve_b > 0 {
+            next.price_a_cumulative = self
+                .price_a_cumulative
+                .wrapping_add(price_x64(reserve_a, reserve_b)?.wrapping_mul(elapsed));
+            next.price_b_cumulative = self
+                .price_b_cumulative
+                .wrapping_add(price_x64(reserve_b, reserve_a)?.wrapping_mul(elapsed));
+        }
+        Some(next)
+    }
+}
+
+// Time-weighted average (price of A in B, price of B in A) as Q64.64 between two
+// observations of the same pool, `earlier` having been taken first.
+pub fn twap_x64(earlier: &PriceObservation, later: &PriceObservation) -> Option<(u128, u128)> {
+    let elapsed = u128::try_from(later.timestamp.checked_sub(earlier.timestamp)?).ok()?;
+    if elapsed == 0 {
+        return None;
+    }
+    Some((
+        later
+            .price_a_cumulative
+            .wrapping_sub(earlier.price_a_cumulative)
+            / elapsed,
+        later
+            .price_b_cumulative
+            .wrapping_sub(earlier.price_b_cumulative)
+            / elapsed,
+    ))
+}
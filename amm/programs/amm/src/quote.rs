@@ -0,0 +1,220 @@
+// Pricing shared by the instruction handlers and off-chain clients. Every quote takes the
+// pool account and its reserves net of accrued protocol fees (see AMMPool::reserves), in
+// the swap direction for the swap quotes, so a client holding the same state gets exactly
+// the amounts the program will compute.
+//
+// Swap quotes also take the unix timestamp the swap executes at: dynamic fee pools price
+// with volatility_bps decayed by the time elapsed since last_update_ts, which the program
+// applies before every instruction (see AMMPool::decayed).
+//
+// Token-2022 transfer fees are not included: the amounts here are the ones that reach or
+// leave the vaults. amount_with_transfer_fee grosses an amount up for them.
+use anchor_lang::prelude::*;
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{transfer_fee::TransferFeeConfig, BaseStateWithExtensions, StateWithExtensions},
+};
+use anchor_spl::token_2022::Token2022;
+
+use crate::error::AMMError;
+use crate::state::AMMPool;
+use crate::utils::{
+    amount_before_fee, lp_tokens_for_deposit, proportional_amount, protocol_fee_share,
+    reserve_share,
+};
+
+pub use crate::utils::MINIMUM_LIQUIDITY;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SwapQuote {
+    pub amount_in: u64,
+    pub amount_out: u64,
+    // Total swap fee taken from amount_in, protocol_fee included
+    pub fee: u64,
+    pub protocol_fee: u64,
+    // Total fee rate charged, out of the pool's fee_denominator
+    pub fee_numerator: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LiquidityQuote {
+    pub amount_a: u64,
+    pub amount_b: u64,
+    pub lp_tokens: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WithdrawQuote {
+    pub amount_a: u64,
+    pub amount_b: u64,
+}
+
+// Output of swapping amount_in, as it arrives in the input vault, at time `now`
+pub fn quote_swap_exact_in(
+    pool: &AMMPool,
+    reserve_in: u64,
+    reserve_out: u64,
+    amount_in: u64,
+    is_a_to_b: bool,
+    now: i64,
+) -> Result<SwapQuote> {
+    let pool = &pool.decayed(now);
+    let fee_numerator = pool
+        .total_fee_numerator()
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    let (fee, protocol_fee) = pool
+        .swap_fees(amount_in)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    let amount_in_after_fee = amount_in
+        .checked_sub(fee)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    let amount_out = pool
+        .curve_amount_out(is_a_to_b, amount_in_after_fee, reserve_in, reserve_out)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    Ok(SwapQuote {
+        amount_in,
+        amount_out,
+        fee,
+        protocol_fee,
+        fee_numerator,
+    })
+}
+
+// Input that has to arrive in the input vault for the pool to pay out amount_out at
+// time `now`
+pub fn quote_swap_exact_out(
+    pool: &AMMPool,
+    reserve_in: u64,
+    reserve_out: u64,
+    amount_out: u64,
+    is_a_to_b: bool,
+    now: i64,
+) -> Result<SwapQuote> {
+    let pool = &pool.decayed(now);
+    require!(reserve_out > amount_out, AMMError::InsufficientFundsInPool);
+
+    let amount_in_after_fee = pool
+        .curve_amount_in(is_a_to_b, amount_out, reserve_in, reserve_out)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    let fee_numerator = pool
+        .total_fee_numerator()
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    let amount_in = amount_before_fee(amount_in_after_fee, fee_numerator, pool.fee_denominator)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    let fee = amount_in
+        .checked_sub(amount_in_after_fee)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    let lp_fee_numerator = pool
+        .lp_fee_numerator()
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    let protocol_fee = protocol_fee_share(fee, lp_fee_numerator, pool.protocol_fee_numerator)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    Ok(SwapQuote {
+        amount_in,
+        amount_out,
+        fee,
+        protocol_fee,
+        fee_numerator,
+    })
+}
+
+// LP tokens issued for amount_a and amount_b arriving in the vaults. The first deposit
+// into an empty pool gets the curve's initial liquidity less MINIMUM_LIQUIDITY.
+pub fn quote_lp_tokens(
+    pool: &AMMPool,
+    reserve_a: u64,
+    reserve_b: u64,
+    amount_a: u64,
+    amount_b: u64,
+    lp_decimals: u8,
+) -> Result<u64> {
+    if pool.total_lp_issued == 0 {
+        let initial_liquidity = pool
+            .initial_liquidity(amount_a, amount_b, lp_decimals)
+            .ok_or(AMMError::ArithmeticOverflow)?;
+        require!(
+            initial_liquidity > MINIMUM_LIQUIDITY,
+            AMMError::InsufficientInitialLiquidity
+        );
+        return Ok(initial_liquidity - MINIMUM_LIQUIDITY);
+    }
+    let lp_tokens = lp_tokens_for_deposit(
+        amount_a,
+        amount_b,
+        reserve_a,
+        reserve_b,
+        pool.total_lp_issued,
+    )
+    .ok_or(AMMError::ArithmeticOverflow)?;
+    Ok(lp_tokens)
+}
+
+// Amounts add_liquidity pulls for the given maximums, and the LP tokens they earn.
+// Only the part of the maximums that matches the current reserve ratio is used, except
+// on the first deposit which sets that ratio.
+pub fn quote_add_liquidity(
+    pool: &AMMPool,
+    reserve_a: u64,
+    reserve_b: u64,
+    max_amount_a: u64,
+    max_amount_b: u64,
+    lp_decimals: u8,
+) -> Result<LiquidityQuote> {
+    let (amount_a, amount_b) = if pool.total_lp_issued == 0 {
+        (max_amount_a, max_amount_b)
+    } else {
+        let optimal_amount_b = proportional_amount(max_amount_a, reserve_a, reserve_b)
+            .ok_or(AMMError::ArithmeticOverflow)?;
+        if optimal_amount_b <= max_amount_b {
+            (max_amount_a, optimal_amount_b)
+        } else {
+            let optimal_amount_a = proportional_amount(max_amount_b, reserve_b, reserve_a)
+                .ok_or(AMMError::ArithmeticOverflow)?;
+            (optimal_amount_a, max_amount_b)
+        }
+    };
+    let lp_tokens = quote_lp_tokens(pool, reserve_a, reserve_b, amount_a, amount_b, lp_decimals)?;
+    Ok(LiquidityQuote {
+        amount_a,
+        amount_b,
+        lp_tokens,
+    })
+}
+
+// Proportional share of both reserves paid out for burning lp_token_quantity
+pub fn quote_withdraw(
+    pool: &AMMPool,
+    reserve_a: u64,
+    reserve_b: u64,
+    lp_token_quantity: u64,
+) -> Result<WithdrawQuote> {
+    require!(reserve_a > 0 && reserve_b > 0, AMMError::PoolEmpty);
+    let amount_a = reserve_share(lp_token_quantity, reserve_a, pool.total_lp_issued)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    let amount_b = reserve_share(lp_token_quantity, reserve_b, pool.total_lp_issued)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    Ok(WithdrawQuote { amount_a, amount_b })
+}
+
+// Gross amount that has to be sent so that `amount` still arrives after the Token-2022
+// transfer fee a mint charges in `epoch` is withheld. Takes the mint account's owner and
+// data; mints without the extension return amount unchanged.
+pub fn amount_with_transfer_fee(
+    mint_owner: &Pubkey,
+    mint_data: &[u8],
+    epoch: u64,
+    amount: u64,
+) -> Result<u64> {
+    if *mint_owner != Token2022::id() {
+        return Ok(amount);
+    }
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(mint_data)?;
+    let Ok(transfer_fee_config) = mint_state.get_extension::<TransferFeeConfig>() else {
+        return Ok(amount);
+    };
+    let gross_amount = transfer_fee_config
+        .get_epoch_fee(epoch)
+        .calculate_pre_fee_amount(amount)
+        .ok_or(AMMError::ArithmeticOverflow)?;
+    Ok(gross_amount)
+}
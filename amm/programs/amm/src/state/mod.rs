@@ -1,5 +1,35 @@
 use anchor_lang::prelude::*;
 
+use crate::error::AMMError;
+use crate::oracle::PriceObservation;
+use crate::utils::{
+    calculate_fee, constant_product_amount_in, constant_product_amount_out, decay_volatility,
+    dynamic_fee_numerator, integer_sqrt, pow_wad, price_move_bps, protocol_fee_share,
+    stable_swap_amount_in, stable_swap_amount_out, stable_swap_d, weighted_amount_in,
+    weighted_amount_out, weighted_invariant, MAX_VOLATILITY_BPS, WAD,
+};
+
+// Invariant a pool prices its swaps with
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CurveType {
+    // x * y = k
+    ConstantProduct,
+    // Curve's StableSwap invariant with amplification coefficient AMMPool::amp
+    StableSwap,
+    // x^wx * y^wy = k with weights AMMPool::weight_a and AMMPool::weight_b
+    Weighted,
+}
+
+// Curve settings chosen when a pool is created
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct CurveConfig {
+    pub curve_type: CurveType,
+    // StableSwap amplification coefficient, zero for the other curves
+    pub amp: u64,
+    // Weight of token A out of WAD for weighted pools, zero for the other curves
+    pub weight_a: u64,
+}
+
 #[account]
 pub struct AMMPool {
     pub mint_a: Pubkey,
@@ -9,5 +39,269 @@ pub struct AMMPool {
     pub lp_mint: Pubkey,
     pub pool_authority: Pubkey,
     pub total_lp_issued: u64,
+    pub fee_numerator: u64,
+    pub fee_denominator: u64,
+    pub fee_authority: Pubkey,
+    pub protocol_fee_numerator: u64,
+    pub protocol_fees_a: u64,
+    pub protocol_fees_b: u64,
+    pub admin: Pubkey,
+    pub paused: bool,
+    pub price_a_cumulative: u128,
+    pub price_b_cumulative: u128,
+    pub last_update_ts: i64,
+    pub flash_loan_amount: u64,
+    pub flash_loan_is_a: bool,
+    pub curve_type: CurveType,
+    pub amp: u64,
+    // Normalized to WAD, only set for weighted pools
+    pub weight_a: u64,
+    pub weight_b: u64,
+    // Ceiling of the dynamic LP fee, fee_numerator being its floor. Zero keeps the fee static.
+    pub max_fee_numerator: u64,
+    pub volatility_bps: u64,
     pub bump: u8,
 }
+
+// Number of pools created by the program. Pool i is listed in the PoolRegistryEntry at
+// [b"registry", i as u64 little endian], so clients can enumerate every pool by fetching
+// entries 0..pool_count, while creating a pool only ever touches fixed-size accounts.
+#[account]
+pub struct PoolRegistry {
+    pub pool_count: u64,
+    pub bump: u8,
+}
+
+#[account]
+pub struct PoolRegistryEntry {
+    pub pool: Pubkey,
+    pub bump: u8,
+}
+
+impl AMMPool {
+    // Accrued protocol fees sit in the vaults but belong to the treasury,
+    // so they are excluded from the reserves used for pricing and LP shares.
+    pub fn reserves(&self, vault_a_amount: u64, vault_b_amount: u64) -> Option<(u64, u64)> {
+        Some((
+            vault_a_amount.checked_sub(self.protocol_fees_a)?,
+            vault_b_amount.checked_sub(self.protocol_fees_b)?,
+        ))
+    }
+
+    // LP fee numerator charged on swaps right now. Pools in dynamic fee mode raise it
+    // from fee_numerator towards max_fee_numerator as volatility builds up.
+    pub fn lp_fee_numerator(&self) -> Option<u64> {
+        if self.max_fee_numerator == 0 {
+            return Some(self.fee_numerator);
+        }
+        dynamic_fee_numerator(
+            self.fee_numerator,
+            self.max_fee_numerator,
+            self.volatility_bps,
+        )
+    }
+
+    pub fn total_fee_numerator(&self) -> Option<u64> {
+        self.lp_fee_numerator()?
+            .checked_add(self.protocol_fee_numerator)
+    }
+
+    // (total fee, protocol's share of it) charged on amount_in of a swap
+    pub fn swap_fees(&self, amount_in: u64) -> Option<(u64, u64)> {
+        let fee = calculate_fee(amount_in, self.total_fee_numerator()?, self.fee_denominator)?;
+        let protocol_fee =
+            protocol_fee_share(fee, self.lp_fee_numerator()?, self.protocol_fee_numerator)?;
+        Some((fee, protocol_fee))
+    }
+
+    // Adds the price move of a swap to the volatility accumulator
+    pub fn record_price_move(
+        &mut self,
+        (reserve_a_before, reserve_b_before): (u64, u64),
+        (reserve_a_after, reserve_b_after): (u64, u64),
+    ) -> Option<()> {
+        let move_bps = price_move_bps(
+            reserve_a_before,
+            reserve_b_before,
+            reserve_a_after,
+            reserve_b_after,
+        )?;
+        self.volatility_bps = self
+            .volatility_bps
+            .saturating_add(move_bps)
+            .min(MAX_VOLATILITY_BPS);
+        Some(())
+    }
+
+    pub fn accrue_protocol_fee(&mut self, is_a: bool, protocol_fee: u64) -> Option<()> {
+        if is_a {
+            self.protocol_fees_a = self.protocol_fees_a.checked_add(protocol_fee)?;
+        } else {
+            self.protocol_fees_b = self.protocol_fees_b.checked_add(protocol_fee)?;
+        }
+        Some(())
+    }
+
+    // (weight_in, weight_out) of a weighted pool for the given direction
+    pub fn directional_weights(&self, is_a_to_b: bool) -> (u64, u64) {
+        if is_a_to_b {
+            (self.weight_a, self.weight_b)
+        } else {
+            (self.weight_b, self.weight_a)
+        }
+    }
+
+    // Output of the pool's curve for amount_in, which already has the swap fee taken out
+    pub fn curve_amount_out(
+        &self,
+        is_a_to_b: bool,
+        amount_in: u64,
+        reserve_in: u64,
+        reserve_out: u64,
+    ) -> Option<u64> {
+        match self.curve_type {
+            CurveType::ConstantProduct => {
+                constant_product_amount_out(amount_in, reserve_in, reserve_out)
+            }
+            CurveType::StableSwap => {
+                stable_swap_amount_out(self.amp, amount_in, reserve_in, reserve_out)
+            }
+            CurveType::Weighted => {
+                let (weight_in, weight_out) = self.directional_weights(is_a_to_b);
+                weighted_amount_out(amount_in, reserve_in, reserve_out, weight_in, weight_out)
+            }
+        }
+    }
+
+    // Input the pool's curve needs, before the swap fee, to pay out amount_out
+    pub fn curve_amount_in(
+        &self,
+        is_a_to_b: bool,
+        amount_out: u64,
+        reserve_in: u64,
+        reserve_out: u64,
+    ) -> Option<u64> {
+        match self.curve_type {
+            CurveType::ConstantProduct => {
+                constant_product_amount_in(amount_out, reserve_in, reserve_out)
+            }
+            CurveType::StableSwap => {
+                stable_swap_amount_in(self.amp, amount_out, reserve_in, reserve_out)
+            }
+            CurveType::Weighted => {
+                let (weight_in, weight_out) = self.directional_weights(is_a_to_b);
+                weighted_amount_in(amount_out, reserve_in, reserve_out, weight_in, weight_out)
+            }
+        }
+    }
+
+    // Part of a single-sided deposit of `quantity` to swap into the other token so that the
+    // rest matches the reserve ratio after the swap. Ignoring fees, the deposit has to grow
+    // the input reserve by the output token's weight:
+    //     s = x * ((1 + q / x)^w_out - 1)
+    // with w_out = 1/2 for constant product pools. StableSwap has no closed form for it.
+    pub fn single_sided_swap_amount(
+        &self,
+        is_a: bool,
+        quantity: u64,
+        reserve_in: u64,
+    ) -> Option<u64> {
+        let reserve_in = reserve_in as u128;
+        let grown_reserve_in = reserve_in.checked_add(quantity as u128)?;
+        let swap_quantity = match self.curve_type {
+            CurveType::ConstantProduct => (integer_sqrt(reserve_in.checked_mul(grown_reserve_in)?)
+                as u128)
+                .checked_sub(reserve_in)?,
+            CurveType::Weighted => {
+                let (_, weight_out) = self.directional_weights(is_a);
+                let growth = grown_reserve_in.checked_mul(WAD)?.checked_div(reserve_in)?;
+                let power = pow_wad(growth, weight_out as u128)?;
+                (reserve_in.checked_mul(power)? / WAD).saturating_sub(reserve_in)
+            }
+            CurveType::StableSwap => return None,
+        };
+        u64::try_from(swap_quantity).ok()
+    }
+
+    // LP supply created by the first deposit. Constant product pools issue the scaled
+    // geometric mean of the deposit, weighted pools its weighted geometric mean scaled
+    // the same way, and StableSwap pools their invariant D.
+    pub fn initial_liquidity(&self, amount_a: u64, amount_b: u64, lp_decimals: u8) -> Option<u64> {
+        match self.curve_type {
+            CurveType::ConstantProduct => {
+                let scaling_factor = 10u128.pow(lp_decimals as u32);
+                let total = (amount_a as u128)
+                    .checked_mul(amount_b as u128)?
+                    .checked_mul(scaling_factor)?;
+                Some(integer_sqrt(total))
+            }
+            CurveType::StableSwap => {
+                u64::try_from(stable_swap_d(self.amp, amount_a, amount_b)?).ok()
+            }
+            CurveType::Weighted => {
+                let scaling_factor = integer_sqrt(10u128.pow(lp_decimals as u32));
+                let invariant =
+                    weighted_invariant(amount_a, amount_b, self.weight_a, self.weight_b)?;
+                u64::try_from(invariant.checked_mul(scaling_factor as u128)?).ok()
+            }
+        }
+    }
+
+    // Reserves the price oracle reads. The spot price of a weighted pool is
+    // (y / wy) / (x / wx), so each reserve is scaled by the other side's weight.
+    pub fn oracle_reserves(&self, reserve_a: u64, reserve_b: u64) -> Option<(u64, u64)> {
+        if self.curve_type != CurveType::Weighted {
+            return Some((reserve_a, reserve_b));
+        }
+        let scaled_a = (reserve_a as u128).checked_mul(self.weight_b as u128)? / WAD;
+        let scaled_b = (reserve_b as u128).checked_mul(self.weight_a as u128)? / WAD;
+        Some((u64::try_from(scaled_a).ok()?, u64::try_from(scaled_b).ok()?))
+    }
+
+    // volatility_bps as it stands at `now`, after the decay every instruction applies for
+    // the time elapsed since last_update_ts
+    pub fn decayed_volatility(&self, now: i64) -> u64 {
+        decay_volatility(self.volatility_bps, now.saturating_sub(self.last_update_ts))
+    }
+
+    // Copy of the pool with its volatility decayed to `now`, which is what the program
+    // prices a swap at `now` with
+    pub fn decayed(&self, now: i64) -> AMMPool {
+        AMMPool {
+            volatility_bps: self.decayed_volatility(now),
+            ..self.clone()
+        }
+    }
+
+    pub fn observation(&self) -> PriceObservation {
+        PriceObservation {
+            price_a_cumulative: self.price_a_cumulative,
+            price_b_cumulative: self.price_b_cumulative,
+            timestamp: self.last_update_ts,
+        }
+    }
+
+    // Has to run before an instruction moves any tokens, so the price accumulated over
+    // the elapsed time is the one that actually held during it. Volatility decays over
+    // the same interval.
+    pub fn update_price_accumulators(
+        &mut self,
+        vault_a_amount: u64,
+        vault_b_amount: u64,
+    ) -> Result<()> {
+        let (reserve_a, reserve_b) = self
+            .reserves(vault_a_amount, vault_b_amount)
+            .and_then(|(reserve_a, reserve_b)| self.oracle_reserves(reserve_a, reserve_b))
+            .ok_or(AMMError::ArithmeticOverflow)?;
+        let now = Clock::get()?.unix_timestamp;
+        self.volatility_bps = self.decayed_volatility(now);
+        let observation = self
+            .observation()
+            .advance(reserve_a, reserve_b, now)
+            .ok_or(AMMError::ArithmeticOverflow)?;
+        self.price_a_cumulative = observation.price_a_cumulative;
+        self.price_b_cumulative = observation.price_b_cumulative;
+        self.last_update_ts = observation.timestamp;
+        Ok(())
+    }
+}
@@ -1,12 +1,469 @@
+use anchor_lang::prelude::*;
+
+use crate::error::AMMError;
+
+// LP tokens permanently locked by the first deposit, so the LP supply can never be
+// drained back to a handful of units and re-priced by a donation to the vaults.
+pub const MINIMUM_LIQUIDITY: u64 = 1_000;
+
+pub fn check_deadline(deadline: Option<i64>) -> Result<()> {
+    if let Some(deadline) = deadline {
+        require!(
+            Clock::get()?.unix_timestamp <= deadline,
+            AMMError::DeadlineExceeded
+        );
+    }
+    Ok(())
+}
+
 pub fn integer_sqrt(value: u128) -> u64 {
     if value == 0 {
         return 0;
     }
     let mut x = value;
-    let mut y = (x + 1) / 2;
+    let mut y = x.div_ceil(2);
     while y < x {
         x = y;
         y = (x + value / x) / 2;
     }
     x as u64
 }
+
+// In a module of its own because the macro expansion names `Result`, which the Anchor
+// prelude shadows here. The allowed lints fire inside the generated code.
+#[allow(clippy::assign_op_pattern, clippy::manual_div_ceil)]
+mod wide {
+    uint::construct_uint! {
+        pub struct U256(4);
+    }
+}
+use wide::U256;
+
+// Bounds on the StableSwap amplification coefficient. They do not keep the Newton
+// iterations below inside u128: at MAX_AMP the A * n^n * (x + y) * D term alone overflows
+// it once each side holds around 5 * 10^16 base units, so the iterations run in U256 and
+// only D and y have to fit back into u128.
+pub const MIN_AMP: u64 = 1;
+pub const MAX_AMP: u64 = 10_000;
+
+const STABLE_SWAP_MAX_ITERATIONS: usize = 255;
+
+// StableSwap invariant D of a two token pool, found with Newton's method on
+//     A * n^n * (x + y) + D = A * n^n * D + D^(n + 1) / (n^n * x * y),   n = 2
+pub fn stable_swap_d(amp: u64, balance_a: u64, balance_b: u64) -> Option<u128> {
+    let balance_a = U256::from(balance_a);
+    let balance_b = U256::from(balance_b);
+    let sum = balance_a.checked_add(balance_b)?;
+    if sum.is_zero() {
+        return Some(0);
+    }
+    let ann = U256::from(amp).checked_mul(U256::from(4))?;
+
+    let mut d = sum;
+    for _ in 0..STABLE_SWAP_MAX_ITERATIONS {
+        // D^3 / (4 * x * y)
+        let d_product = d.checked_mul(d)?.checked_mul(d)?.checked_div(
+            balance_a
+                .checked_mul(balance_b)?
+                .checked_mul(U256::from(4))?,
+        )?;
+        let previous_d = d;
+        let numerator = ann
+            .checked_mul(sum)?
+            .checked_add(d_product.checked_mul(U256::from(2))?)?
+            .checked_mul(d)?;
+        let denominator = ann
+            .checked_sub(U256::one())?
+            .checked_mul(d)?
+            .checked_add(d_product.checked_mul(U256::from(3))?)?;
+        d = numerator.checked_div(denominator)?;
+        if abs_diff(d, previous_d) <= U256::one() {
+            return u128::try_from(d).ok();
+        }
+    }
+    None
+}
+
+// Balance the other side of a StableSwap pool needs so the invariant stays at d once
+// this side holds `balance`. Newton's method on y^2 + (b - D) * y = c with
+//     b = x + D / (A * n^n),   c = D^3 / (n^n * x * A * n^n)
+pub fn stable_swap_y(amp: u64, balance: u128, d: u128) -> Option<u128> {
+    let balance = U256::from(balance);
+    let d = U256::from(d);
+    let ann = U256::from(amp).checked_mul(U256::from(4))?;
+    let c = d
+        .checked_mul(d)?
+        .checked_mul(d)?
+        .checked_div(balance.checked_mul(ann)?.checked_mul(U256::from(4))?)?;
+    let b = balance.checked_add(d.checked_div(ann)?)?;
+
+    let mut y = d;
+    for _ in 0..STABLE_SWAP_MAX_ITERATIONS {
+        let previous_y = y;
+        y = y.checked_mul(y)?.checked_add(c)?.checked_div(
+            y.checked_mul(U256::from(2))?
+                .checked_add(b)?
+                .checked_sub(d)?,
+        )?;
+        if abs_diff(y, previous_y) <= U256::one() {
+            return u128::try_from(y).ok();
+        }
+    }
+    None
+}
+
+fn abs_diff(a: U256, b: U256) -> U256 {
+    if a > b {
+        a - b
+    } else {
+        b - a
+    }
+}
+
+// StableSwap counterpart of constant_product_amount_out. One unit is held back to
+// cover the rounding of the Newton iterations, so the invariant can only grow.
+pub fn stable_swap_amount_out(
+    amp: u64,
+    amount_in: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+) -> Option<u64> {
+    let d = stable_swap_d(amp, reserve_in, reserve_out)?;
+    let new_reserve_in = (reserve_in as u128).checked_add(amount_in as u128)?;
+    let new_reserve_out = stable_swap_y(amp, new_reserve_in, d)?;
+    let amount_out = (reserve_out as u128)
+        .saturating_sub(new_reserve_out)
+        .saturating_sub(1);
+    u64::try_from(amount_out).ok()
+}
+
+// StableSwap counterpart of constant_product_amount_in, rounded up by one unit
+pub fn stable_swap_amount_in(
+    amp: u64,
+    amount_out: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+) -> Option<u64> {
+    let d = stable_swap_d(amp, reserve_in, reserve_out)?;
+    let new_reserve_out = (reserve_out as u128).checked_sub(amount_out as u128)?;
+    if new_reserve_out == 0 {
+        return None;
+    }
+    let new_reserve_in = stable_swap_y(amp, new_reserve_out, d)?;
+    let amount_in = new_reserve_in
+        .checked_sub(reserve_in as u128)?
+        .checked_add(1)?;
+    u64::try_from(amount_in).ok()
+}
+
+// Rounded up so that dust-sized swaps can't slip through without paying a fee.
+pub fn calculate_fee(amount: u64, fee_numerator: u64, fee_denominator: u64) -> Option<u64> {
+    let fee = (amount as u128)
+        .checked_mul(fee_numerator as u128)?
+        .checked_add((fee_denominator as u128).checked_sub(1)?)?
+        .checked_div(fee_denominator as u128)?;
+    u64::try_from(fee).ok()
+}
+
+// Portion of an already charged fee that goes to the protocol rather than the LPs.
+pub fn protocol_fee_share(
+    fee: u64,
+    fee_numerator: u64,
+    protocol_fee_numerator: u64,
+) -> Option<u64> {
+    let total_numerator = (fee_numerator as u128).checked_add(protocol_fee_numerator as u128)?;
+    if total_numerator == 0 {
+        return Some(0);
+    }
+    let protocol_fee = (fee as u128)
+        .checked_mul(protocol_fee_numerator as u128)?
+        .checked_div(total_numerator)?;
+    u64::try_from(protocol_fee).ok()
+}
+
+// Fixed point scale of the weighted pool math, 1.0 = 10^18. Pool weights use the same
+// scale and always add up to exactly WAD.
+pub const WAD: u128 = 1_000_000_000_000_000_000;
+pub const MIN_WEIGHT: u64 = (WAD / 100) as u64;
+
+const LN_2_WAD: i128 = 693_147_180_559_945_309;
+// pow_wad is accurate to well below this (1e-14). Results are padded by it, in the
+// pool's favour, before they are used to price a swap.
+const MAX_POW_RELATIVE_ERROR: u128 = 10_000;
+
+// Natural logarithm of a positive WAD fixed point number
+pub fn ln_wad(x: u128) -> Option<i128> {
+    if x == 0 {
+        return None;
+    }
+    // x = m * 2^k with m in [1, 2)
+    let mut k: i128 = 0;
+    let mut m = x;
+    while m >= 2 * WAD {
+        m /= 2;
+        k += 1;
+    }
+    while m < WAD {
+        m *= 2;
+        k -= 1;
+    }
+
+    // ln(m) = 2 * atanh(z) = 2 * (z + z^3 / 3 + z^5 / 5 + ...) with z = (m - 1) / (m + 1),
+    // which stays below 1/3 so the series converges quickly
+    let z = (m - WAD) * WAD / (m + WAD);
+    let z_squared = z * z / WAD;
+    let mut term = z;
+    let mut sum = 0u128;
+    let mut n = 1u128;
+    while term > 0 {
+        sum += term / n;
+        term = term * z_squared / WAD;
+        n += 2;
+    }
+    Some(k * LN_2_WAD + 2 * sum as i128)
+}
+
+// e^x for a WAD fixed point x, None once the result no longer fits a u128
+pub fn exp_wad(x: i128) -> Option<u128> {
+    // x = k * ln(2) + r with r in [0, ln(2)), so e^x = 2^k * e^r
+    let k = x.div_euclid(LN_2_WAD);
+    let r = x.rem_euclid(LN_2_WAD) as u128;
+
+    let mut term = WAD;
+    let mut sum = WAD;
+    let mut n = 1u128;
+    while term > 0 {
+        term = term * r / WAD / n;
+        sum += term;
+        n += 1;
+    }
+
+    if k >= 0 {
+        let shift = u32::try_from(k).ok()?;
+        if shift >= sum.leading_zeros() {
+            return None;
+        }
+        Some(sum << shift)
+    } else {
+        Some(sum >> (-k).min(127))
+    }
+}
+
+// base^exponent for WAD fixed point numbers, as e^(exponent * ln(base))
+pub fn pow_wad(base: u128, exponent: u128) -> Option<u128> {
+    if exponent == 0 {
+        return Some(WAD);
+    }
+    if base == 0 {
+        return Some(0);
+    }
+    let ln_base = ln_wad(base)?;
+    // Split the exponent so the product stays inside i128 for any weight ratio
+    let whole = ln_base.checked_mul(i128::try_from(exponent / WAD).ok()?)?;
+    let fraction = ln_base.checked_mul((exponent % WAD) as i128)? / WAD as i128;
+    exp_wad(whole.checked_add(fraction)?)
+}
+
+// pow_wad rounded up past its worst case error
+fn pow_wad_up(base: u128, exponent: u128) -> Option<u128> {
+    let power = pow_wad(base, exponent)?;
+    power
+        .checked_add(power.checked_mul(MAX_POW_RELATIVE_ERROR)? / WAD)?
+        .checked_add(1)
+}
+
+// Weighted product invariant x^wx * y^wy, which doubles as the pool's value in units
+// of its tokens
+pub fn weighted_invariant(
+    balance_a: u64,
+    balance_b: u64,
+    weight_a: u64,
+    weight_b: u64,
+) -> Option<u128> {
+    // e^(wx * ln(x) + wy * ln(y)), so only one exponentiation loses precision
+    let ln_a = ln_wad((balance_a as u128).checked_mul(WAD)?)?;
+    let ln_b = ln_wad((balance_b as u128).checked_mul(WAD)?)?;
+    let exponent = ln_a
+        .checked_mul(weight_a as i128)?
+        .checked_add(ln_b.checked_mul(weight_b as i128)?)?
+        / WAD as i128;
+    Some(exp_wad(exponent)? / WAD)
+}
+
+// Weighted counterpart of constant_product_amount_out:
+//     (x + dx)^wx * (y - dy)^wy = x^wx * y^wy
+//     dy = y * (1 - (x / (x + dx))^(wx / wy))
+// Base and power are rounded up and the exponent down, each of which lowers dy.
+pub fn weighted_amount_out(
+    amount_in: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+    weight_in: u64,
+    weight_out: u64,
+) -> Option<u64> {
+    let base = (reserve_in as u128)
+        .checked_mul(WAD)?
+        .div_ceil((reserve_in as u128).checked_add(amount_in as u128)?);
+    let exponent = (weight_in as u128)
+        .checked_mul(WAD)?
+        .checked_div(weight_out as u128)?;
+    let power = pow_wad_up(base, exponent)?.min(WAD);
+    let amount_out = (reserve_out as u128).checked_mul(WAD - power)? / WAD;
+    u64::try_from(amount_out).ok()
+}
+
+// Weighted counterpart of constant_product_amount_in:
+//     dx = x * ((y / (y - dy))^(wy / wx) - 1)
+// rounded up throughout
+pub fn weighted_amount_in(
+    amount_out: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+    weight_in: u64,
+    weight_out: u64,
+) -> Option<u64> {
+    let remaining_out = (reserve_out as u128).checked_sub(amount_out as u128)?;
+    if remaining_out == 0 {
+        return None;
+    }
+    let base = (reserve_out as u128)
+        .checked_mul(WAD)?
+        .div_ceil(remaining_out);
+    let exponent = (weight_out as u128)
+        .checked_mul(WAD)?
+        .div_ceil(weight_in as u128);
+    let power = pow_wad_up(base, exponent)?;
+    let amount_in = (reserve_in as u128)
+        .checked_mul(power.checked_sub(WAD)?)?
+        .div_ceil(WAD);
+    u64::try_from(amount_in).ok()
+}
+
+// Dynamic fee tuning. A pool's volatility is the sum of its recent price moves in basis
+// points, fading out linearly over VOLATILITY_DECAY_PERIOD seconds without trading. The
+// fee reaches its ceiling once volatility builds up to MAX_VOLATILITY_BPS.
+pub const VOLATILITY_DECAY_PERIOD: i64 = 300;
+pub const MAX_VOLATILITY_BPS: u64 = 500;
+const BPS: u128 = 10_000;
+
+// What is left of volatility after `elapsed` seconds. Applied on every pool update, so
+// frequent updates compound into a roughly exponential decay.
+pub fn decay_volatility(volatility: u64, elapsed: i64) -> u64 {
+    let remaining = VOLATILITY_DECAY_PERIOD.saturating_sub(elapsed.max(0)) as u128;
+    ((volatility as u128) * remaining / VOLATILITY_DECAY_PERIOD as u128) as u64
+}
+
+// Relative change of the price of A in B between two sets of reserves, in basis points
+//     |b1 / a1 - b0 / a0| / (b0 / a0) = |a0 * b1 - a1 * b0| / (a1 * b0)
+pub fn price_move_bps(
+    reserve_a_before: u64,
+    reserve_b_before: u64,
+    reserve_a_after: u64,
+    reserve_b_after: u64,
+) -> Option<u64> {
+    let a1_b0 = U256::from(reserve_a_after).checked_mul(U256::from(reserve_b_before))?;
+    let a0_b1 = U256::from(reserve_a_before).checked_mul(U256::from(reserve_b_after))?;
+    if a1_b0.is_zero() {
+        return Some(0);
+    }
+    let move_bps = abs_diff(a1_b0, a0_b1)
+        .checked_mul(U256::from(BPS))?
+        .checked_div(a1_b0)?;
+    Some(u64::try_from(move_bps).unwrap_or(u64::MAX))
+}
+
+// Fee numerator between floor and ceiling, in proportion to volatility
+pub fn dynamic_fee_numerator(floor: u64, ceiling: u64, volatility: u64) -> Option<u64> {
+    let range = ceiling.checked_sub(floor)? as u128;
+    let increase = range
+        .checked_mul(volatility.min(MAX_VOLATILITY_BPS) as u128)?
+        .checked_div(MAX_VOLATILITY_BPS as u128)?;
+    floor.checked_add(u64::try_from(increase).ok()?)
+}
+
+// dy = (y * dx) / (x + dx), see instructions::swap for the derivation
+pub fn constant_product_amount_out(
+    amount_in: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+) -> Option<u64> {
+    let amount_out = (reserve_out as u128)
+        .checked_mul(amount_in as u128)?
+        .checked_div((reserve_in as u128).checked_add(amount_in as u128)?)?;
+    u64::try_from(amount_out).ok()
+}
+
+// dx = ceil((x * dy) / (y - dy)), the smallest input for which the curve pays out dy
+pub fn constant_product_amount_in(
+    amount_out: u64,
+    reserve_in: u64,
+    reserve_out: u64,
+) -> Option<u64> {
+    let denominator = (reserve_out as u128).checked_sub(amount_out as u128)?;
+    if denominator == 0 {
+        return None;
+    }
+    let amount_in = (reserve_in as u128)
+        .checked_mul(amount_out as u128)?
+        .div_ceil(denominator);
+    u64::try_from(amount_in).ok()
+}
+
+// Inverse of deducting calculate_fee: the smallest gross amount that still leaves
+// amount_after_fee once a fee of fee_numerator / fee_denominator is taken out.
+pub fn amount_before_fee(
+    amount_after_fee: u64,
+    fee_numerator: u64,
+    fee_denominator: u64,
+) -> Option<u64> {
+    let remaining_numerator = (fee_denominator as u128).checked_sub(fee_numerator as u128)?;
+    if remaining_numerator == 0 {
+        return None;
+    }
+    let amount = (amount_after_fee as u128)
+        .checked_mul(fee_denominator as u128)?
+        .div_ceil(remaining_numerator);
+    u64::try_from(amount).ok()
+}
+
+// Amount of the other token matching `amount` at the current reserve ratio, rounded up
+// so a depositor can never get a share of the pool for less than it is worth.
+pub fn proportional_amount(amount: u64, reserve_from: u64, reserve_to: u64) -> Option<u64> {
+    if reserve_from == 0 {
+        return None;
+    }
+    let amount_to = (amount as u128)
+        .checked_mul(reserve_to as u128)?
+        .div_ceil(reserve_from as u128);
+    u64::try_from(amount_to).ok()
+}
+
+// LP tokens owed for depositing amount_a and amount_b into a pool with the given
+// reserves, limited by whichever side is the smaller share of its reserve.
+pub fn lp_tokens_for_deposit(
+    amount_a: u64,
+    amount_b: u64,
+    reserve_a: u64,
+    reserve_b: u64,
+    total_lp_issued: u64,
+) -> Option<u64> {
+    let lp_tokens_based_on_token_a = (total_lp_issued as u128)
+        .checked_mul(amount_a as u128)?
+        .checked_div(reserve_a as u128)?;
+    let lp_tokens_based_on_token_b = (total_lp_issued as u128)
+        .checked_mul(amount_b as u128)?
+        .checked_div(reserve_b as u128)?;
+    u64::try_from(std::cmp::min(
+        lp_tokens_based_on_token_a,
+        lp_tokens_based_on_token_b,
+    ))
+    .ok()
+}
+
+// Share of a reserve owned by lp_token_quantity out of total_lp_issued, rounded down
+pub fn reserve_share(lp_token_quantity: u64, reserve: u64, total_lp_issued: u64) -> Option<u64> {
+    let share = (lp_token_quantity as u128)
+        .checked_mul(reserve as u128)?
+        .checked_div(total_lp_issued as u128)?;
+    u64::try_from(share).ok()
+}
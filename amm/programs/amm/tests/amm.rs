@@ -0,0 +1,345 @@
+// End-to-end tests for the pool lifecycle: initialize_pool, add_liquidity, swap,
+// withdraw_liquidity and observe, on the solana-program-test runtime in runtime/mod.rs.
+mod fixture;
+mod runtime;
+
+use amm::error::AMMError;
+use amm::oracle::twap_x64;
+use amm::quote::{quote_swap_exact_in, quote_withdraw, MINIMUM_LIQUIDITY};
+use amm::state::{CurveType, PoolRegistry, PoolRegistryEntry};
+
+use fixture::{
+    assert_amm_error, registry_entry, weighted, FixtureOptions, PoolFixture, FEE_NUMERATOR, WAD,
+};
+
+#[tokio::test]
+async fn initialize_pool_creates_and_registers_pool() {
+    let mut fixture = PoolFixture::with_pool().await;
+
+    let pool = fixture.pool_state().await;
+    assert_eq!(pool.mint_a, fixture.pool.mint_a);
+    assert_eq!(pool.mint_b, fixture.pool.mint_b);
+    assert_eq!(pool.vault_a, fixture.pool.vault_a);
+    assert_eq!(pool.vault_b, fixture.pool.vault_b);
+    assert_eq!(pool.lp_mint, fixture.pool.lp_mint);
+    assert_eq!(pool.pool_authority, fixture.pool.authority);
+    assert_eq!(pool.fee_numerator, FEE_NUMERATOR);
+    assert_eq!(pool.total_lp_issued, 0);
+    assert_eq!(pool.curve_type, CurveType::ConstantProduct);
+
+    let registry: PoolRegistry = fixture.runtime.anchor_account(&fixture.registry).await;
+    assert_eq!(registry.pool_count, 1);
+    let entry: PoolRegistryEntry = fixture.runtime.anchor_account(&registry_entry(0)).await;
+    assert_eq!(entry.pool, fixture.pool.amm_pool);
+    assert_eq!(fixture.runtime.token_supply(&fixture.pool.lp_mint).await, 0);
+}
+
+#[tokio::test]
+async fn initialize_pool_rejects_unsorted_mints() {
+    let mut fixture = PoolFixture::new().await;
+    let (mint_a, mint_b) = (fixture.pool.mint_a, fixture.pool.mint_b);
+
+    let result = fixture.initialize_pool_with_mints(mint_b, mint_a).await;
+    assert_amm_error(result, AMMError::UnsortedMints);
+}
+
+#[tokio::test]
+async fn initialize_pool_rejects_same_mint() {
+    let mut fixture = PoolFixture::new().await;
+    let mint_a = fixture.pool.mint_a;
+
+    let result = fixture.initialize_pool_with_mints(mint_a, mint_a).await;
+    assert_amm_error(result, AMMError::SameTokenMint);
+}
+
+#[tokio::test]
+async fn add_liquidity_mints_lp_and_locks_minimum_liquidity() {
+    let mut fixture = PoolFixture::with_pool().await;
+    fixture
+        .add_liquidity(1_000_000, 4_000_000, 0)
+        .await
+        .unwrap();
+
+    // sqrt(1e6 * 4e6 * 10^6) for a first deposit, less the locked minimum
+    let expected_lp = 2_000_000_000 - MINIMUM_LIQUIDITY;
+    assert_eq!(fixture.balance(fixture.user_lp()).await, expected_lp);
+    assert_eq!(
+        fixture.runtime.token_supply(&fixture.pool.lp_mint).await,
+        expected_lp
+    );
+    assert_eq!(
+        fixture.pool_state().await.total_lp_issued,
+        expected_lp + MINIMUM_LIQUIDITY
+    );
+    assert_eq!(fixture.balance(fixture.pool.vault_a).await, 1_000_000);
+    assert_eq!(fixture.balance(fixture.pool.vault_b).await, 4_000_000);
+}
+
+#[tokio::test]
+async fn add_liquidity_only_pulls_the_proportional_amounts() {
+    let mut fixture = PoolFixture::with_liquidity(1_000_000, 4_000_000).await;
+    let lp_before = fixture.balance(fixture.user_lp()).await;
+
+    // B is offered in excess of the 1:4 ratio, so only 2_000_000 of it is taken
+    fixture.add_liquidity(500_000, 3_000_000, 0).await.unwrap();
+
+    assert_eq!(fixture.balance(fixture.pool.vault_a).await, 1_500_000);
+    assert_eq!(fixture.balance(fixture.pool.vault_b).await, 6_000_000);
+    assert_eq!(
+        fixture.balance(fixture.user_lp()).await - lp_before,
+        1_000_000_000
+    );
+}
+
+#[tokio::test]
+async fn add_liquidity_fails_below_min_lp_out() {
+    let mut fixture = PoolFixture::with_pool().await;
+
+    let result = fixture
+        .add_liquidity(1_000_000, 4_000_000, 2_000_000_000)
+        .await;
+    assert_amm_error(result, AMMError::SlippageExceeded);
+    assert_eq!(fixture.balance(fixture.pool.vault_a).await, 0);
+    assert_eq!(fixture.pool_state().await.total_lp_issued, 0);
+}
+
+#[tokio::test]
+async fn add_liquidity_rejects_zero_amounts() {
+    let mut fixture = PoolFixture::with_pool().await;
+
+    let result = fixture.add_liquidity(0, 4_000_000, 0).await;
+    assert_amm_error(result, AMMError::ZeroAmount);
+}
+
+// InvalidLiquidity is no longer raised by add_liquidity: deposits that don't match the
+// reserve ratio are trimmed to it instead (see the proportional test above).
+
+#[tokio::test]
+async fn swap_pays_out_the_quoted_amount() {
+    let mut fixture = PoolFixture::with_liquidity(10_000_000, 10_000_000).await;
+    let user_b_before = fixture.balance(fixture.user_token_b()).await;
+    let pool = fixture.pool_state().await;
+    let now = fixture.runtime.unix_timestamp().await;
+    let quote = quote_swap_exact_in(&pool, 10_000_000, 10_000_000, 1_000_000, true, now).unwrap();
+
+    fixture
+        .swap(1_000_000, quote.amount_out, true)
+        .await
+        .unwrap();
+
+    // 1_000_000 less the 0.3% fee against a 10M/10M constant product pool
+    assert_eq!(quote.amount_out, 906_610);
+    assert_eq!(quote.fee, 3_000);
+    assert_eq!(
+        fixture.balance(fixture.user_token_b()).await - user_b_before,
+        quote.amount_out
+    );
+    assert_eq!(fixture.balance(fixture.pool.vault_a).await, 11_000_000);
+    assert_eq!(
+        fixture.balance(fixture.pool.vault_b).await,
+        10_000_000 - quote.amount_out
+    );
+
+    let result = fixture.swap_result();
+    assert_eq!(result.amount_in, 1_000_000);
+    assert_eq!(result.amount_out, quote.amount_out);
+    assert_eq!(result.fee, quote.fee);
+    assert_eq!(result.protocol_fee, 0);
+    assert_eq!(result.fee_numerator, FEE_NUMERATOR);
+}
+
+#[tokio::test]
+async fn swap_b_to_a_grows_the_invariant() {
+    let mut fixture = PoolFixture::with_liquidity(10_000_000, 10_000_000).await;
+
+    fixture.swap(2_000_000, 0, false).await.unwrap();
+
+    let reserve_a = fixture.balance(fixture.pool.vault_a).await as u128;
+    let reserve_b = fixture.balance(fixture.pool.vault_b).await as u128;
+    assert_eq!(reserve_b, 12_000_000);
+    assert!(reserve_a * reserve_b > 10_000_000u128 * 10_000_000);
+}
+
+#[tokio::test]
+async fn swap_fails_below_minimum_out() {
+    let mut fixture = PoolFixture::with_liquidity(10_000_000, 10_000_000).await;
+    let pool = fixture.pool_state().await;
+    let now = fixture.runtime.unix_timestamp().await;
+    let quote = quote_swap_exact_in(&pool, 10_000_000, 10_000_000, 1_000_000, true, now).unwrap();
+
+    let result = fixture.swap(1_000_000, quote.amount_out + 1, true).await;
+    assert_amm_error(result, AMMError::SlippageExceeded);
+    assert_eq!(fixture.balance(fixture.pool.vault_a).await, 10_000_000);
+    assert_eq!(fixture.balance(fixture.pool.vault_b).await, 10_000_000);
+}
+
+#[tokio::test]
+async fn swap_quote_decays_volatility_like_the_program() {
+    let mut fixture = PoolFixture::with_liquidity(10_000_000, 10_000_000).await;
+    fixture.set_dynamic_fee(FEE_NUMERATOR * 10).await.unwrap();
+    fixture.swap(1_000_000, 0, true).await.unwrap();
+    fixture.runtime.advance_clock(150).await;
+
+    let pool = fixture.pool_state().await;
+    let reserve_a = fixture.balance(fixture.pool.vault_a).await;
+    let reserve_b = fixture.balance(fixture.pool.vault_b).await;
+    let now = fixture.runtime.unix_timestamp().await;
+    let quote = quote_swap_exact_in(&pool, reserve_b, reserve_a, 1_000_000, false, now).unwrap();
+    // Quoting on the stored volatility would overcharge, half of it has decayed by now
+    let stale_quote = quote_swap_exact_in(
+        &pool,
+        reserve_b,
+        reserve_a,
+        1_000_000,
+        false,
+        pool.last_update_ts,
+    )
+    .unwrap();
+    assert!(quote.fee_numerator < stale_quote.fee_numerator);
+    let user_a_before = fixture.balance(fixture.user_token_a()).await;
+
+    fixture
+        .swap(1_000_000, quote.amount_out, false)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        fixture.balance(fixture.user_token_a()).await - user_a_before,
+        quote.amount_out
+    );
+    assert_eq!(fixture.swap_result().fee_numerator, quote.fee_numerator);
+}
+
+#[tokio::test]
+async fn swap_rejects_zero_quantity() {
+    let mut fixture = PoolFixture::with_liquidity(10_000_000, 10_000_000).await;
+
+    let result = fixture.swap(0, 0, true).await;
+    assert_amm_error(result, AMMError::ZeroAmount);
+}
+
+#[tokio::test]
+async fn withdraw_liquidity_returns_a_proportional_share() {
+    let mut fixture = PoolFixture::with_liquidity(1_000_000, 4_000_000).await;
+    fixture.swap(100_000, 0, true).await.unwrap();
+
+    let user_a_before = fixture.balance(fixture.user_token_a()).await;
+    let user_b_before = fixture.balance(fixture.user_token_b()).await;
+    let lp_balance = fixture.balance(fixture.user_lp()).await;
+    let to_burn = lp_balance / 2;
+    let reserve_a = fixture.balance(fixture.pool.vault_a).await;
+    let reserve_b = fixture.balance(fixture.pool.vault_b).await;
+    let pool = fixture.pool_state().await;
+    let expected = quote_withdraw(&pool, reserve_a, reserve_b, to_burn).unwrap();
+
+    fixture
+        .withdraw_liquidity(to_burn, expected.amount_a, expected.amount_b)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        fixture.balance(fixture.user_token_a()).await - user_a_before,
+        expected.amount_a
+    );
+    assert_eq!(
+        fixture.balance(fixture.user_token_b()).await - user_b_before,
+        expected.amount_b
+    );
+    assert_eq!(
+        fixture.balance(fixture.user_lp()).await,
+        lp_balance - to_burn
+    );
+    assert_eq!(
+        fixture.pool_state().await.total_lp_issued,
+        lp_balance - to_burn + MINIMUM_LIQUIDITY
+    );
+}
+
+#[tokio::test]
+async fn withdraw_liquidity_leaves_the_locked_minimum_behind() {
+    let mut fixture = PoolFixture::with_liquidity(1_000_000, 4_000_000).await;
+    let lp_balance = fixture.balance(fixture.user_lp()).await;
+
+    fixture.withdraw_liquidity(lp_balance, 0, 0).await.unwrap();
+
+    assert_eq!(
+        fixture.pool_state().await.total_lp_issued,
+        MINIMUM_LIQUIDITY
+    );
+    assert!(fixture.balance(fixture.pool.vault_a).await > 0);
+    assert!(fixture.balance(fixture.pool.vault_b).await > 0);
+}
+
+#[tokio::test]
+async fn withdraw_liquidity_fails_without_enough_lp_tokens() {
+    let mut fixture = PoolFixture::with_liquidity(1_000_000, 4_000_000).await;
+    let lp_balance = fixture.balance(fixture.user_lp()).await;
+
+    let result = fixture.withdraw_liquidity(lp_balance + 1, 0, 0).await;
+    assert_amm_error(result, AMMError::InsufficientLPTokens);
+}
+
+#[tokio::test]
+async fn withdraw_liquidity_fails_below_minimum_amounts() {
+    let mut fixture = PoolFixture::with_liquidity(1_000_000, 4_000_000).await;
+    let lp_balance = fixture.balance(fixture.user_lp()).await;
+
+    let result = fixture
+        .withdraw_liquidity(lp_balance / 2, 1_000_000, 0)
+        .await;
+    assert_amm_error(result, AMMError::SlippageExceeded);
+    assert_eq!(fixture.balance(fixture.user_lp()).await, lp_balance);
+}
+
+#[tokio::test]
+async fn single_sided_add_records_the_price_move() {
+    let mut fixture = PoolFixture::with_liquidity(1_000_000_000, 1_000_000_000).await;
+    assert_eq!(fixture.pool_state().await.volatility_bps, 0);
+
+    fixture
+        .add_liquidity_single_sided(10_000_000, true, 0)
+        .await
+        .unwrap();
+
+    // Half of the 1% deposit is swapped in, moving the price by about 1%
+    let volatility_bps = fixture.pool_state().await.volatility_bps;
+    assert!((90..=110).contains(&volatility_bps), "{volatility_bps}");
+}
+
+#[tokio::test]
+async fn single_sided_withdraw_records_the_price_move() {
+    let mut fixture = PoolFixture::with_liquidity(1_000_000_000, 1_000_000_000).await;
+    let lp_balance = fixture.balance(fixture.user_lp()).await;
+
+    fixture
+        .withdraw_liquidity_single(lp_balance / 100, true, 0)
+        .await
+        .unwrap();
+
+    // Swapping the B share back in pays out about 2% of the A reserve in total while
+    // the B reserve ends up where it started, so the price moves by about 2%
+    let volatility_bps = fixture.pool_state().await.volatility_bps;
+    assert!((190..=210).contains(&volatility_bps), "{volatility_bps}");
+}
+
+#[tokio::test]
+async fn observe_prices_weighted_pools_by_their_weights() {
+    // 80/20 pool with equal reserves, where one A is worth four B
+    let mut fixture = PoolFixture::with_options(FixtureOptions {
+        curve: weighted(WAD / 5 * 4),
+        ..FixtureOptions::default()
+    })
+    .await;
+    fixture
+        .add_liquidity(1_000_000_000, 1_000_000_000, 0)
+        .await
+        .unwrap();
+
+    let earlier = fixture.observe().await;
+    fixture.runtime.advance_clock(100).await;
+    let later = fixture.observe().await;
+
+    let (price_a_x64, price_b_x64) = twap_x64(&earlier, &later).unwrap();
+    assert_eq!(price_a_x64 >> 64, 4);
+    assert_eq!(price_b_x64, (1u128 << 64) / 4);
+}
@@ -0,0 +1,140 @@
+// StableSwap and weighted pools: swaps along their invariants and single-sided deposits,
+// on the solana-program-test runtime in runtime/mod.rs.
+mod fixture;
+mod runtime;
+
+use amm::error::AMMError;
+use amm::quote::{quote_swap_exact_in, MINIMUM_LIQUIDITY};
+use amm::state::CurveType;
+
+use fixture::{assert_amm_error, stable_swap, weighted, FixtureOptions, PoolFixture, WAD};
+
+#[tokio::test]
+async fn stable_swap_pays_out_close_to_one_for_one() {
+    let mut fixture = PoolFixture::with_options(FixtureOptions {
+        curve: stable_swap(100),
+        ..FixtureOptions::default()
+    })
+    .await;
+    fixture
+        .add_liquidity(100_000_000, 100_000_000, 0)
+        .await
+        .unwrap();
+    let pool = fixture.pool_state().await;
+    assert_eq!(pool.curve_type, CurveType::StableSwap);
+    let now = fixture.runtime.unix_timestamp().await;
+    let quote = quote_swap_exact_in(&pool, 100_000_000, 100_000_000, 1_000_000, true, now).unwrap();
+    let user_b_before = fixture.balance(fixture.user_token_b()).await;
+
+    fixture
+        .swap(1_000_000, quote.amount_out, true)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        fixture.balance(fixture.user_token_b()).await - user_b_before,
+        quote.amount_out
+    );
+    // A 1% trade on a constant product pool this size would lose another ~1% to the curve
+    assert!(quote.amount_out > 996_000, "{}", quote.amount_out);
+    assert!(quote.amount_out < 997_000, "{}", quote.amount_out);
+}
+
+#[tokio::test]
+async fn stable_swap_at_max_amp_handles_large_reserves() {
+    // Well past the point where A * n^n * (x + y) * D leaves u128 at MAX_AMP
+    let reserve = 5_000_000_000_000_000_000;
+    let mut fixture = PoolFixture::with_options(FixtureOptions {
+        curve: stable_swap(10_000),
+        user_balance: u64::MAX / 2,
+        ..FixtureOptions::default()
+    })
+    .await;
+    fixture.add_liquidity(reserve, reserve, 0).await.unwrap();
+
+    let balance_b = fixture.balance(fixture.user_token_b()).await;
+    fixture.swap(1_000_000_000_000, 0, true).await.unwrap();
+    let received = fixture.balance(fixture.user_token_b()).await - balance_b;
+
+    // A balanced pool this flat pays out almost one for one, less the 0.3% fee
+    assert!(received <= 997_000_000_000);
+    assert!(received > 996_000_000_000);
+}
+
+#[tokio::test]
+async fn stable_swap_rejects_single_sided_deposits() {
+    let mut fixture = PoolFixture::with_options(FixtureOptions {
+        curve: stable_swap(100),
+        ..FixtureOptions::default()
+    })
+    .await;
+    fixture
+        .add_liquidity(100_000_000, 100_000_000, 0)
+        .await
+        .unwrap();
+
+    let result = fixture.add_liquidity_single_sided(1_000_000, true, 0).await;
+    assert_amm_error(result, AMMError::UnsupportedCurve);
+}
+
+#[tokio::test]
+async fn weighted_swap_pays_out_the_quoted_amount() {
+    // 80/20 pool with equal reserves, where one A is worth four B
+    let mut fixture = PoolFixture::with_options(FixtureOptions {
+        curve: weighted(WAD / 5 * 4),
+        ..FixtureOptions::default()
+    })
+    .await;
+    fixture
+        .add_liquidity(1_000_000_000, 1_000_000_000, 0)
+        .await
+        .unwrap();
+    let pool = fixture.pool_state().await;
+    assert_eq!(pool.curve_type, CurveType::Weighted);
+    let now = fixture.runtime.unix_timestamp().await;
+    let quote =
+        quote_swap_exact_in(&pool, 1_000_000_000, 1_000_000_000, 1_000_000, true, now).unwrap();
+    let user_b_before = fixture.balance(fixture.user_token_b()).await;
+
+    fixture
+        .swap(1_000_000, quote.amount_out, true)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        fixture.balance(fixture.user_token_b()).await - user_b_before,
+        quote.amount_out
+    );
+    // Just under four B for every A that is left once the 0.3% fee is taken out
+    assert!(quote.amount_out > 3_970_000, "{}", quote.amount_out);
+    assert!(quote.amount_out < 3_988_000, "{}", quote.amount_out);
+}
+
+#[tokio::test]
+async fn weighted_single_sided_add_earns_its_share_of_the_pool_value() {
+    let mut fixture = PoolFixture::with_options(FixtureOptions {
+        curve: weighted(WAD / 5 * 4),
+        ..FixtureOptions::default()
+    })
+    .await;
+    fixture
+        .add_liquidity(1_000_000_000, 1_000_000_000, 0)
+        .await
+        .unwrap();
+    let lp_before = fixture.balance(fixture.user_lp()).await;
+    let total_lp_before = fixture.pool_state().await.total_lp_issued;
+    assert_eq!(lp_before + MINIMUM_LIQUIDITY, total_lp_before);
+
+    fixture
+        .add_liquidity_single_sided(10_000_000, true, 0)
+        .await
+        .unwrap();
+
+    // The whole deposit stays in the pool. A holds 80% of the pool's value, so 1% more A
+    // is worth about 0.8% of the pool, less the fee on the part swapped into B.
+    let reserve_a = fixture.balance(fixture.pool.vault_a).await;
+    assert_eq!(reserve_a, 1_010_000_000);
+    let minted = fixture.balance(fixture.user_lp()).await - lp_before;
+    let share_bps = minted as u128 * 10_000 / total_lp_before as u128;
+    assert!((79..=80).contains(&share_bps), "{share_bps}");
+}
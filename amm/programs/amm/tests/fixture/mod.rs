@@ -0,0 +1,686 @@
+// Pools, funded users and the AMM instructions the test files send, on top of the
+// runtime in runtime/mod.rs.
+#![allow(dead_code)]
+
+use amm::error::AMMError;
+use amm::oracle::PriceObservation;
+use amm::state::{AMMPool, CurveConfig, CurveType, PoolRegistry};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    instruction::{AccountMeta, Instruction},
+    sysvar,
+};
+use anchor_lang::{system_program, InstructionData, ToAccountMetas};
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+use anchor_spl::token::spl_token;
+use anchor_spl::token_2022::spl_token_2022;
+use solana_sdk::signature::{Keypair, Signer};
+
+use crate::runtime::{TestRuntime, TransactionFailure};
+
+pub const DECIMALS: u8 = 6;
+pub const FEE_NUMERATOR: u64 = 3;
+pub const FEE_DENOMINATOR: u64 = 1000;
+pub const USER_BALANCE: u64 = 1_000_000_000_000;
+pub const WAD: u64 = 1_000_000_000_000_000_000;
+
+pub type TestResult = std::result::Result<(), TransactionFailure>;
+
+// Mirror of the program's SwapResult, which is returned through return data
+#[derive(AnchorDeserialize)]
+pub struct SwapResult {
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee: u64,
+    pub protocol_fee: u64,
+    pub fee_numerator: u64,
+}
+
+pub fn pda(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &amm::ID).0
+}
+
+pub fn registry_entry(index: u64) -> Pubkey {
+    pda(&[b"registry", &index.to_le_bytes()])
+}
+
+pub fn assert_amm_error(result: TestResult, error: AMMError) {
+    let failure = result.expect_err("the transaction should have failed");
+    assert_eq!(
+        failure.custom_error(),
+        Some(u32::from(error)),
+        "{failure:#?}"
+    );
+}
+
+pub fn constant_product() -> CurveConfig {
+    CurveConfig {
+        curve_type: CurveType::ConstantProduct,
+        amp: 0,
+        weight_a: 0,
+    }
+}
+
+pub fn stable_swap(amp: u64) -> CurveConfig {
+    CurveConfig {
+        curve_type: CurveType::StableSwap,
+        amp,
+        weight_a: 0,
+    }
+}
+
+pub fn weighted(weight_a: u64) -> CurveConfig {
+    CurveConfig {
+        curve_type: CurveType::Weighted,
+        amp: 0,
+        weight_a,
+    }
+}
+
+// Token program a test mint lives under, and the transfer fee it charges if it is a
+// Token-2022 transfer fee mint
+#[derive(Clone, Copy, Debug)]
+pub struct MintKind {
+    pub token_program: Pubkey,
+    pub transfer_fee_bps: Option<u16>,
+}
+
+impl MintKind {
+    pub const SPL_TOKEN: MintKind = MintKind {
+        token_program: spl_token::ID,
+        transfer_fee_bps: None,
+    };
+
+    pub fn transfer_fee(transfer_fee_bps: u16) -> Self {
+        MintKind {
+            token_program: spl_token_2022::ID,
+            transfer_fee_bps: Some(transfer_fee_bps),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct FixtureOptions {
+    pub curve: CurveConfig,
+    // Applied to the mints after they are sorted into the A and B side
+    pub mint_a: MintKind,
+    pub mint_b: MintKind,
+    pub user_balance: u64,
+}
+
+impl Default for FixtureOptions {
+    fn default() -> Self {
+        FixtureOptions {
+            curve: constant_product(),
+            mint_a: MintKind::SPL_TOKEN,
+            mint_b: MintKind::SPL_TOKEN,
+            user_balance: USER_BALANCE,
+        }
+    }
+}
+
+// Addresses of one pool, all derived from its two mints
+#[derive(Clone, Copy, Debug)]
+pub struct Pool {
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub token_a_program: Pubkey,
+    pub token_b_program: Pubkey,
+    pub authority: Pubkey,
+    pub amm_pool: Pubkey,
+    pub vault_a: Pubkey,
+    pub vault_b: Pubkey,
+    pub lp_mint: Pubkey,
+}
+
+impl Pool {
+    pub fn new(
+        mint_a: Pubkey,
+        token_a_program: Pubkey,
+        mint_b: Pubkey,
+        token_b_program: Pubkey,
+    ) -> Self {
+        let (a, b) = (mint_a.as_ref(), mint_b.as_ref());
+        Pool {
+            mint_a,
+            mint_b,
+            token_a_program,
+            token_b_program,
+            authority: pda(&[b"authority", a, b]),
+            amm_pool: pda(&[b"pool", a, b]),
+            vault_a: pda(&[b"vault_token", a, b, b"A"]),
+            vault_b: pda(&[b"vault_token", a, b, b"B"]),
+            lp_mint: pda(&[b"lp_mint", a, b]),
+        }
+    }
+
+    pub fn token_a(&self, owner: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(owner, &self.mint_a, &self.token_a_program)
+    }
+
+    pub fn token_b(&self, owner: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(owner, &self.mint_b, &self.token_b_program)
+    }
+
+    pub fn lp(&self, owner: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(owner, &self.lp_mint, &spl_token::ID)
+    }
+
+    fn add_liquidity_accounts(&self, user: &Pubkey) -> Vec<AccountMeta> {
+        amm::accounts::AddLiquidity {
+            liquidity_provider: *user,
+            token_a_mint: self.mint_a,
+            token_b_mint: self.mint_b,
+            amm_pool: self.amm_pool,
+            authority: self.authority,
+            token_a_account: self.token_a(user),
+            token_b_account: self.token_b(user),
+            vault_a: self.vault_a,
+            vault_b: self.vault_b,
+            lp_token_mint: self.lp_mint,
+            lp_token_account: self.lp(user),
+            token_program: spl_token::ID,
+            token_a_program: self.token_a_program,
+            token_b_program: self.token_b_program,
+        }
+        .to_account_metas(None)
+    }
+
+    fn withdraw_liquidity_accounts(&self, user: &Pubkey) -> Vec<AccountMeta> {
+        amm::accounts::WithdrawLiquidity {
+            liquidity_provider: *user,
+            token_a_mint: self.mint_a,
+            token_b_mint: self.mint_b,
+            amm_pool: self.amm_pool,
+            authority: self.authority,
+            token_a_account: self.token_a(user),
+            token_b_account: self.token_b(user),
+            vault_a: self.vault_a,
+            vault_b: self.vault_b,
+            lp_token_mint: self.lp_mint,
+            lp_token_account: self.lp(user),
+            token_program: spl_token::ID,
+            token_a_program: self.token_a_program,
+            token_b_program: self.token_b_program,
+        }
+        .to_account_metas(None)
+    }
+
+    fn swap_accounts(&self, user: &Pubkey) -> Vec<AccountMeta> {
+        amm::accounts::Swap {
+            user: *user,
+            token_a_mint: self.mint_a,
+            token_b_mint: self.mint_b,
+            authority: self.authority,
+            user_token_account_a: self.token_a(user),
+            user_token_account_b: self.token_b(user),
+            vault_a: self.vault_a,
+            vault_b: self.vault_b,
+            amm_pool: self.amm_pool,
+            token_a_program: self.token_a_program,
+            token_b_program: self.token_b_program,
+        }
+        .to_account_metas(None)
+    }
+
+    fn flash_loan_accounts(&self, borrower: &Pubkey) -> Vec<AccountMeta> {
+        amm::accounts::FlashLoan {
+            borrower: *borrower,
+            token_a_mint: self.mint_a,
+            token_b_mint: self.mint_b,
+            amm_pool: self.amm_pool,
+            authority: self.authority,
+            vault_a: self.vault_a,
+            vault_b: self.vault_b,
+            borrower_token_account_a: self.token_a(borrower),
+            borrower_token_account_b: self.token_b(borrower),
+            token_a_program: self.token_a_program,
+            token_b_program: self.token_b_program,
+            instructions: sysvar::instructions::ID,
+        }
+        .to_account_metas(None)
+    }
+
+    // Remaining accounts swap_route expects for a hop through this pool
+    pub fn route_hop_accounts(&self) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new(self.amm_pool, false),
+            AccountMeta::new_readonly(self.authority, false),
+            AccountMeta::new(self.vault_a, false),
+            AccountMeta::new(self.vault_b, false),
+            AccountMeta::new_readonly(self.mint_a, false),
+            AccountMeta::new_readonly(self.mint_b, false),
+            AccountMeta::new_readonly(self.token_a_program, false),
+            AccountMeta::new_readonly(self.token_b_program, false),
+        ]
+    }
+
+    pub fn add_liquidity_single_sided_instruction(
+        &self,
+        user: &Pubkey,
+        quantity: u64,
+        is_a: bool,
+        min_lp_out: u64,
+    ) -> Instruction {
+        Instruction {
+            program_id: amm::ID,
+            accounts: self.add_liquidity_accounts(user),
+            data: amm::instruction::AddLiquiditySingleSided {
+                quantity,
+                is_a,
+                min_lp_out,
+                deadline: None,
+            }
+            .data(),
+        }
+    }
+
+    pub fn swap_instruction(
+        &self,
+        user: &Pubkey,
+        quantity: u64,
+        minimum_slippage_quantity: u64,
+        is_a_to_b: bool,
+    ) -> Instruction {
+        Instruction {
+            program_id: amm::ID,
+            accounts: self.swap_accounts(user),
+            data: amm::instruction::Swap {
+                quantity,
+                minimum_slippage_quantity,
+                is_a_to_b,
+                deadline: None,
+            }
+            .data(),
+        }
+    }
+
+    pub fn flash_borrow_instruction(
+        &self,
+        borrower: &Pubkey,
+        amount: u64,
+        is_a: bool,
+    ) -> Instruction {
+        Instruction {
+            program_id: amm::ID,
+            accounts: self.flash_loan_accounts(borrower),
+            data: amm::instruction::FlashBorrow { amount, is_a }.data(),
+        }
+    }
+
+    pub fn flash_repay_instruction(&self, borrower: &Pubkey) -> Instruction {
+        Instruction {
+            program_id: amm::ID,
+            accounts: self.flash_loan_accounts(borrower),
+            data: amm::instruction::FlashRepay {}.data(),
+        }
+    }
+}
+
+pub struct PoolFixture {
+    pub runtime: TestRuntime,
+    pub admin: Keypair,
+    pub user: Keypair,
+    pub registry: Pubkey,
+    pub curve: CurveConfig,
+    pub pool: Pool,
+}
+
+impl PoolFixture {
+    // A funded user and admin with a registry in place, but no pool yet
+    pub async fn new() -> Self {
+        Self::without_pool(FixtureOptions::default()).await
+    }
+
+    pub async fn without_pool(options: FixtureOptions) -> Self {
+        let mut runtime = TestRuntime::new().await;
+        let admin = Keypair::new();
+        let user = Keypair::new();
+        runtime.airdrop(admin.pubkey(), 100_000_000_000);
+        runtime.airdrop(user.pubkey(), 100_000_000_000);
+
+        let (mint_a, mint_b) = sorted_mints();
+        for (mint, kind) in [(&mint_a, options.mint_a), (&mint_b, options.mint_b)] {
+            runtime
+                .create_mint(mint, DECIMALS, kind.token_program, kind.transfer_fee_bps)
+                .await;
+            runtime
+                .create_token_account(user.pubkey(), mint.pubkey(), options.user_balance)
+                .await;
+        }
+
+        let registry = pda(&[b"registry"]);
+        let instruction = Instruction {
+            program_id: amm::ID,
+            accounts: amm::accounts::InitRegistry {
+                payer: admin.pubkey(),
+                pool_registry: registry,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: amm::instruction::InitializeRegistry {}.data(),
+        };
+        runtime.process(&[instruction], &[&admin]).await.unwrap();
+
+        PoolFixture {
+            runtime,
+            admin,
+            user,
+            registry,
+            curve: options.curve,
+            pool: Pool::new(
+                mint_a.pubkey(),
+                options.mint_a.token_program,
+                mint_b.pubkey(),
+                options.mint_b.token_program,
+            ),
+        }
+    }
+
+    pub async fn with_options(options: FixtureOptions) -> Self {
+        let mut fixture = Self::without_pool(options).await;
+        fixture.initialize_pool().await.unwrap();
+        fixture
+    }
+
+    pub async fn with_pool() -> Self {
+        Self::with_options(FixtureOptions::default()).await
+    }
+
+    pub async fn with_liquidity(amount_a: u64, amount_b: u64) -> Self {
+        let mut fixture = Self::with_pool().await;
+        fixture.add_liquidity(amount_a, amount_b, 0).await.unwrap();
+        fixture
+    }
+
+    pub fn user_token_a(&self) -> Pubkey {
+        self.pool.token_a(&self.user.pubkey())
+    }
+
+    pub fn user_token_b(&self) -> Pubkey {
+        self.pool.token_b(&self.user.pubkey())
+    }
+
+    pub fn user_lp(&self) -> Pubkey {
+        self.pool.lp(&self.user.pubkey())
+    }
+
+    pub async fn pool_state(&mut self) -> AMMPool {
+        self.runtime.anchor_account(&self.pool.amm_pool).await
+    }
+
+    pub async fn balance(&mut self, token_account: Pubkey) -> u64 {
+        self.runtime.token_balance(&token_account).await
+    }
+
+    // A new mint with a funded account for the user, for pools beyond the first one
+    pub async fn create_mint(&mut self, kind: MintKind) -> Pubkey {
+        let mint = Keypair::new();
+        self.runtime
+            .create_mint(&mint, DECIMALS, kind.token_program, kind.transfer_fee_bps)
+            .await;
+        self.runtime
+            .create_token_account(self.user.pubkey(), mint.pubkey(), USER_BALANCE)
+            .await;
+        mint.pubkey()
+    }
+
+    pub async fn initialize_pool(&mut self) -> TestResult {
+        let pool = self.pool;
+        self.initialize(pool, self.curve).await
+    }
+
+    pub async fn initialize_pool_with_mints(
+        &mut self,
+        token_a_mint: Pubkey,
+        token_b_mint: Pubkey,
+    ) -> TestResult {
+        let pool = Pool::new(
+            token_a_mint,
+            self.pool.token_a_program,
+            token_b_mint,
+            self.pool.token_b_program,
+        );
+        self.initialize(pool, self.curve).await
+    }
+
+    pub async fn initialize(&mut self, pool: Pool, curve: CurveConfig) -> TestResult {
+        let registry: PoolRegistry = self.runtime.anchor_account(&self.registry).await;
+        let instruction = Instruction {
+            program_id: amm::ID,
+            accounts: amm::accounts::InitPool {
+                initializer: self.admin.pubkey(),
+                authority: pool.authority,
+                token_a_mint: pool.mint_a,
+                token_b_mint: pool.mint_b,
+                vault_a: pool.vault_a,
+                vault_b: pool.vault_b,
+                lp_token_mint: pool.lp_mint,
+                amm_pool: pool.amm_pool,
+                pool_registry: self.registry,
+                registry_entry: registry_entry(registry.pool_count),
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+                token_a_program: pool.token_a_program,
+                token_b_program: pool.token_b_program,
+            }
+            .to_account_metas(None),
+            data: amm::instruction::InitializePool {
+                fee_numerator: FEE_NUMERATOR,
+                fee_denominator: FEE_DENOMINATOR,
+                protocol_fee_numerator: 0,
+                fee_authority: self.admin.pubkey(),
+                curve,
+            }
+            .data(),
+        };
+        self.runtime.process(&[instruction], &[&self.admin]).await
+    }
+
+    pub async fn add_liquidity(
+        &mut self,
+        max_quantity_a: u64,
+        max_quantity_b: u64,
+        min_lp_out: u64,
+    ) -> TestResult {
+        let pool = self.pool;
+        self.add_liquidity_to(pool, max_quantity_a, max_quantity_b, min_lp_out)
+            .await
+    }
+
+    pub async fn add_liquidity_to(
+        &mut self,
+        pool: Pool,
+        max_quantity_a: u64,
+        max_quantity_b: u64,
+        min_lp_out: u64,
+    ) -> TestResult {
+        // The client creates the LP token account ahead of the first deposit
+        let user = self.user.pubkey();
+        if !self.runtime.account_exists(&pool.lp(&user)).await {
+            self.runtime
+                .create_token_account(user, pool.lp_mint, 0)
+                .await;
+        }
+        let instruction = Instruction {
+            program_id: amm::ID,
+            accounts: pool.add_liquidity_accounts(&user),
+            data: amm::instruction::AddLiquidity {
+                max_quantity_a,
+                max_quantity_b,
+                min_lp_out,
+                deadline: None,
+            }
+            .data(),
+        };
+        self.runtime.process(&[instruction], &[&self.user]).await
+    }
+
+    pub async fn add_liquidity_single_sided(
+        &mut self,
+        quantity: u64,
+        is_a: bool,
+        min_lp_out: u64,
+    ) -> TestResult {
+        let instruction = self.pool.add_liquidity_single_sided_instruction(
+            &self.user.pubkey(),
+            quantity,
+            is_a,
+            min_lp_out,
+        );
+        self.runtime.process(&[instruction], &[&self.user]).await
+    }
+
+    pub async fn swap(
+        &mut self,
+        quantity: u64,
+        minimum_slippage_quantity: u64,
+        is_a_to_b: bool,
+    ) -> TestResult {
+        let instruction = self.pool.swap_instruction(
+            &self.user.pubkey(),
+            quantity,
+            minimum_slippage_quantity,
+            is_a_to_b,
+        );
+        self.runtime.process(&[instruction], &[&self.user]).await
+    }
+
+    pub async fn swap_exact_out(
+        &mut self,
+        amount_out: u64,
+        max_amount_in: u64,
+        is_a_to_b: bool,
+    ) -> TestResult {
+        let instruction = Instruction {
+            program_id: amm::ID,
+            accounts: self.pool.swap_accounts(&self.user.pubkey()),
+            data: amm::instruction::SwapExactOut {
+                amount_out,
+                max_amount_in,
+                is_a_to_b,
+                deadline: None,
+            }
+            .data(),
+        };
+        self.runtime.process(&[instruction], &[&self.user]).await
+    }
+
+    // Swaps amount_in of the user's token_in through every pool in hops, in order
+    pub async fn swap_route(
+        &mut self,
+        token_in: Pubkey,
+        token_out: Pubkey,
+        hops: &[Pool],
+        amount_in: u64,
+        min_amount_out: u64,
+    ) -> TestResult {
+        let mut accounts = amm::accounts::SwapRoute {
+            user: self.user.pubkey(),
+            user_token_in: token_in,
+            user_token_out: token_out,
+        }
+        .to_account_metas(None);
+        accounts.extend(hops.iter().flat_map(Pool::route_hop_accounts));
+        let instruction = Instruction {
+            program_id: amm::ID,
+            accounts,
+            data: amm::instruction::SwapRoute {
+                amount_in,
+                min_amount_out,
+                deadline: None,
+            }
+            .data(),
+        };
+        self.runtime.process(&[instruction], &[&self.user]).await
+    }
+
+    pub async fn withdraw_liquidity(
+        &mut self,
+        lp_token_quantity: u64,
+        min_amount_a: u64,
+        min_amount_b: u64,
+    ) -> TestResult {
+        let instruction = Instruction {
+            program_id: amm::ID,
+            accounts: self.pool.withdraw_liquidity_accounts(&self.user.pubkey()),
+            data: amm::instruction::WithdrawLiquidity {
+                lp_token_quantity,
+                min_amount_a,
+                min_amount_b,
+            }
+            .data(),
+        };
+        self.runtime.process(&[instruction], &[&self.user]).await
+    }
+
+    pub async fn withdraw_liquidity_single(
+        &mut self,
+        lp_token_quantity: u64,
+        want_a: bool,
+        min_out: u64,
+    ) -> TestResult {
+        let instruction = Instruction {
+            program_id: amm::ID,
+            accounts: self.pool.withdraw_liquidity_accounts(&self.user.pubkey()),
+            data: amm::instruction::WithdrawLiquiditySingle {
+                lp_token_quantity,
+                want_a,
+                min_out,
+            }
+            .data(),
+        };
+        self.runtime.process(&[instruction], &[&self.user]).await
+    }
+
+    // Sends the instructions in one transaction signed by the user, for flash loans and
+    // whatever they are combined with
+    pub async fn process_as_user(&mut self, instructions: &[Instruction]) -> TestResult {
+        self.runtime.process(instructions, &[&self.user]).await
+    }
+
+    pub async fn set_dynamic_fee(&mut self, max_fee_numerator: u64) -> TestResult {
+        let instruction = Instruction {
+            program_id: amm::ID,
+            accounts: amm::accounts::PoolAdmin {
+                admin: self.admin.pubkey(),
+                token_a_mint: self.pool.mint_a,
+                token_b_mint: self.pool.mint_b,
+                amm_pool: self.pool.amm_pool,
+            }
+            .to_account_metas(None),
+            data: amm::instruction::SetDynamicFee { max_fee_numerator }.data(),
+        };
+        self.runtime.process(&[instruction], &[&self.admin]).await
+    }
+
+    pub async fn observe(&mut self) -> PriceObservation {
+        let instruction = Instruction {
+            program_id: amm::ID,
+            accounts: amm::accounts::Observe {
+                token_a_mint: self.pool.mint_a,
+                token_b_mint: self.pool.mint_b,
+                amm_pool: self.pool.amm_pool,
+                vault_a: self.pool.vault_a,
+                vault_b: self.pool.vault_b,
+            }
+            .to_account_metas(None),
+            data: amm::instruction::Observe {}.data(),
+        };
+        self.runtime.process(&[instruction], &[]).await.unwrap();
+        PriceObservation::deserialize(&mut self.runtime.return_data().unwrap().as_slice()).unwrap()
+    }
+
+    pub fn swap_result(&self) -> SwapResult {
+        SwapResult::deserialize(&mut self.runtime.return_data().unwrap().as_slice()).unwrap()
+    }
+}
+
+fn sorted_mints() -> (Keypair, Keypair) {
+    let (first, second) = (Keypair::new(), Keypair::new());
+    if first.pubkey() < second.pubkey() {
+        (first, second)
+    } else {
+        (second, first)
+    }
+}
@@ -0,0 +1,74 @@
+// flash_borrow and flash_repay, which only work as a pair within one transaction,
+// on the solana-program-test runtime in runtime/mod.rs.
+mod fixture;
+mod runtime;
+
+use amm::error::AMMError;
+use solana_sdk::signature::Signer;
+
+use fixture::{assert_amm_error, PoolFixture, FEE_DENOMINATOR, FEE_NUMERATOR};
+
+#[tokio::test]
+async fn flash_loan_repaid_in_the_same_transaction_pays_the_pool_fee() {
+    let mut fixture = PoolFixture::with_liquidity(10_000_000, 10_000_000).await;
+    let borrower = fixture.user.pubkey();
+    let user_a_before = fixture.balance(fixture.user_token_a()).await;
+
+    let borrow = fixture
+        .pool
+        .flash_borrow_instruction(&borrower, 1_000_000, true);
+    let repay = fixture.pool.flash_repay_instruction(&borrower);
+    fixture.process_as_user(&[borrow, repay]).await.unwrap();
+
+    let fee = 1_000_000 * FEE_NUMERATOR / FEE_DENOMINATOR;
+    assert_eq!(
+        fixture.balance(fixture.pool.vault_a).await,
+        10_000_000 + fee
+    );
+    assert_eq!(fixture.balance(fixture.pool.vault_b).await, 10_000_000);
+    assert_eq!(
+        user_a_before - fixture.balance(fixture.user_token_a()).await,
+        fee
+    );
+    let pool = fixture.pool_state().await;
+    assert_eq!(pool.flash_loan_amount, 0);
+    assert_eq!(pool.protocol_fees_a, 0);
+}
+
+#[tokio::test]
+async fn flash_borrow_without_repay_fails() {
+    let mut fixture = PoolFixture::with_liquidity(10_000_000, 10_000_000).await;
+    let borrower = fixture.user.pubkey();
+
+    let borrow = fixture
+        .pool
+        .flash_borrow_instruction(&borrower, 1_000_000, true);
+    let result = fixture.process_as_user(&[borrow]).await;
+    assert_amm_error(result, AMMError::FlashLoanNotRepaid);
+    assert_eq!(fixture.balance(fixture.pool.vault_a).await, 10_000_000);
+}
+
+#[tokio::test]
+async fn flash_repay_without_borrow_fails() {
+    let mut fixture = PoolFixture::with_liquidity(10_000_000, 10_000_000).await;
+    let borrower = fixture.user.pubkey();
+
+    let repay = fixture.pool.flash_repay_instruction(&borrower);
+    let result = fixture.process_as_user(&[repay]).await;
+    assert_amm_error(result, AMMError::NoFlashLoan);
+}
+
+#[tokio::test]
+async fn pool_refuses_swaps_while_a_loan_is_outstanding() {
+    let mut fixture = PoolFixture::with_liquidity(10_000_000, 10_000_000).await;
+    let borrower = fixture.user.pubkey();
+
+    let borrow = fixture
+        .pool
+        .flash_borrow_instruction(&borrower, 1_000_000, true);
+    let swap = fixture.pool.swap_instruction(&borrower, 500_000, 0, true);
+    let repay = fixture.pool.flash_repay_instruction(&borrower);
+    let result = fixture.process_as_user(&[borrow, swap, repay]).await;
+    assert_amm_error(result, AMMError::FlashLoanInProgress);
+    assert_eq!(fixture.balance(fixture.pool.vault_a).await, 10_000_000);
+}
@@ -0,0 +1,342 @@
+// Thin wrapper around solana-program-test. Under `cargo test-sbf` or `anchor test` the
+// AMM is loaded from the amm.so in SBF_OUT_DIR, under a plain `cargo test` it runs from
+// its native entrypoint instead. SPL Token, Token-2022 and the associated token program
+// are the shared objects bundled with solana-program-test either way.
+#![allow(dead_code)]
+
+use std::collections::HashSet;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{entrypoint::ProgramResult, instruction::Instruction};
+use anchor_lang::{system_program, AccountDeserialize};
+use anchor_spl::associated_token::{
+    get_associated_token_address_with_program_id,
+    spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+};
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{
+        transfer_fee::instruction::initialize_transfer_fee_config, BaseStateWithExtensions,
+        ExtensionType, StateWithExtensions,
+    },
+};
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::AccountSharedData,
+    instruction::InstructionError,
+    signature::{Keypair, Signature, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use solana_system_interface::instruction as system_instruction;
+
+const MAXIMUM_TRANSFER_FEE: u64 = u64::MAX;
+
+// Anchor's entry takes &'info [AccountInfo<'info>], a slice that lives as long as the
+// account data it borrows, while a native processor is only lent the slice for the call.
+// AccountInfo is invariant in 'info, so the slice can't be reborrowed for that lifetime,
+// and a copy of it has to be leaked to outlive the call without unsafe code. That is a
+// few hundred bytes per instruction, reclaimed when the test process exits.
+fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    amm::entry(program_id, accounts, data)
+}
+
+#[derive(Debug)]
+pub struct TransactionFailure {
+    pub error: TransactionError,
+    pub logs: Vec<String>,
+}
+
+impl TransactionFailure {
+    // Code of the custom error the failing instruction returned, if that is how it failed
+    pub fn custom_error(&self) -> Option<u32> {
+        match self.error {
+            TransactionError::InstructionError(_, InstructionError::Custom(code)) => Some(code),
+            _ => None,
+        }
+    }
+}
+
+pub struct TestRuntime {
+    context: ProgramTestContext,
+    sent: HashSet<Signature>,
+    return_data: Option<Vec<u8>>,
+}
+
+impl TestRuntime {
+    pub async fn new() -> Self {
+        let program_test = ProgramTest::new("amm", amm::ID, processor!(process_instruction));
+        TestRuntime {
+            context: program_test.start_with_context().await,
+            sent: HashSet::new(),
+            return_data: None,
+        }
+    }
+
+    pub fn payer(&self) -> Pubkey {
+        self.context.payer.pubkey()
+    }
+
+    pub fn airdrop(&mut self, address: Pubkey, lamports: u64) {
+        let account = AccountSharedData::new(lamports, 0, &system_program::ID);
+        self.context.set_account(&address, &account);
+    }
+
+    pub async fn unix_timestamp(&mut self) -> i64 {
+        let clock: Clock = self.context.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp
+    }
+
+    pub async fn advance_clock(&mut self, seconds: i64) {
+        let mut clock: Clock = self.context.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp += seconds;
+        self.context.set_sysvar(&clock);
+    }
+
+    // Mint owned by token_program with the payer as its authority. A transfer fee of
+    // transfer_fee_bps, with no cap, makes it a Token-2022 transfer fee mint.
+    pub async fn create_mint(
+        &mut self,
+        mint: &Keypair,
+        decimals: u8,
+        token_program: Pubkey,
+        transfer_fee_bps: Option<u16>,
+    ) {
+        let payer = self.payer();
+        let extensions: &[ExtensionType] = match transfer_fee_bps {
+            Some(_) => &[ExtensionType::TransferFeeConfig],
+            None => &[],
+        };
+        let space =
+            ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(extensions)
+                .unwrap();
+        let lamports = Rent::default().minimum_balance(space);
+
+        let mut instructions = vec![system_instruction::create_account(
+            &payer,
+            &mint.pubkey(),
+            lamports,
+            space as u64,
+            &token_program,
+        )];
+        if let Some(transfer_fee_bps) = transfer_fee_bps {
+            instructions.push(
+                initialize_transfer_fee_config(
+                    &token_program,
+                    &mint.pubkey(),
+                    None,
+                    None,
+                    transfer_fee_bps,
+                    MAXIMUM_TRANSFER_FEE,
+                )
+                .unwrap(),
+            );
+        }
+        instructions.push(
+            spl_token_2022::instruction::initialize_mint2(
+                &token_program,
+                &mint.pubkey(),
+                &payer,
+                None,
+                decimals,
+            )
+            .unwrap(),
+        );
+        self.process(&instructions, &[mint]).await.unwrap();
+    }
+
+    // Creates owner's associated token account for mint if needed and mints amount to it
+    pub async fn create_token_account(
+        &mut self,
+        owner: Pubkey,
+        mint: Pubkey,
+        amount: u64,
+    ) -> Pubkey {
+        let payer = self.payer();
+        let mint_account = self
+            .context
+            .banks_client
+            .get_account(mint)
+            .await
+            .unwrap()
+            .unwrap();
+        let token_program = mint_account.owner;
+        let decimals =
+            StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_account.data)
+                .unwrap()
+                .base
+                .decimals;
+        let address = get_associated_token_address_with_program_id(&owner, &mint, &token_program);
+
+        let mut instructions = vec![create_associated_token_account_idempotent(
+            &payer,
+            &owner,
+            &mint,
+            &token_program,
+        )];
+        if amount > 0 {
+            instructions.push(
+                spl_token_2022::instruction::mint_to_checked(
+                    &token_program,
+                    &mint,
+                    &address,
+                    &payer,
+                    &[],
+                    amount,
+                    decimals,
+                )
+                .unwrap(),
+            );
+        }
+        self.process(&instructions, &[]).await.unwrap();
+        address
+    }
+
+    pub async fn account_exists(&mut self, address: &Pubkey) -> bool {
+        self.context
+            .banks_client
+            .get_account(*address)
+            .await
+            .unwrap()
+            .is_some()
+    }
+
+    // (owner, data) of an account that has to exist
+    pub async fn account(&mut self, address: &Pubkey) -> (Pubkey, Vec<u8>) {
+        let account = self
+            .context
+            .banks_client
+            .get_account(*address)
+            .await
+            .unwrap()
+            .unwrap();
+        (account.owner, account.data)
+    }
+
+    pub async fn epoch(&mut self) -> u64 {
+        let clock: Clock = self.context.banks_client.get_sysvar().await.unwrap();
+        clock.epoch
+    }
+
+    pub async fn token_balance(&mut self, address: &Pubkey) -> u64 {
+        let account = self
+            .context
+            .banks_client
+            .get_account(*address)
+            .await
+            .unwrap();
+        account
+            .and_then(|account| {
+                StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)
+                    .ok()
+                    .map(|state| state.base.amount)
+            })
+            .unwrap_or(0)
+    }
+
+    pub async fn token_supply(&mut self, mint: &Pubkey) -> u64 {
+        let account = self
+            .context
+            .banks_client
+            .get_account(*mint)
+            .await
+            .unwrap()
+            .unwrap();
+        StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&account.data)
+            .unwrap()
+            .base
+            .supply
+    }
+
+    // Transfer fee mint charges on a transfer of amount in the current epoch, zero for
+    // mints without one
+    pub async fn transfer_fee(&mut self, mint: &Pubkey, amount: u64) -> u64 {
+        let account = self
+            .context
+            .banks_client
+            .get_account(*mint)
+            .await
+            .unwrap()
+            .unwrap();
+        let clock: Clock = self.context.banks_client.get_sysvar().await.unwrap();
+        StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&account.data)
+            .ok()
+            .and_then(|mint| {
+                mint.get_extension::<spl_token_2022::extension::transfer_fee::TransferFeeConfig>()
+                    .ok()
+                    .and_then(|config| config.calculate_epoch_fee(clock.epoch, amount))
+            })
+            .unwrap_or(0)
+    }
+
+    pub async fn anchor_account<T: AccountDeserialize>(&mut self, address: &Pubkey) -> T {
+        let account = self
+            .context
+            .banks_client
+            .get_account(*address)
+            .await
+            .unwrap()
+            .unwrap();
+        T::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    // Return data set by the last transaction that succeeded
+    pub fn return_data(&self) -> Option<Vec<u8>> {
+        self.return_data.clone()
+    }
+
+    // Sends the instructions as one transaction paid for by the payer. Nothing is
+    // written back unless every instruction succeeds.
+    pub async fn process(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> std::result::Result<(), TransactionFailure> {
+        let mut transaction = self.sign(instructions, signers);
+        // The bank rejects a transaction it has already seen, which is what sending the
+        // same instructions twice within one blockhash produces
+        if self.sent.contains(&transaction.signatures[0]) {
+            self.context.get_new_latest_blockhash().await.unwrap();
+            transaction = self.sign(instructions, signers);
+        }
+        self.sent.insert(transaction.signatures[0]);
+
+        let outcome = self
+            .context
+            .banks_client
+            .process_transaction_with_metadata(transaction)
+            .await
+            .unwrap();
+        let metadata = outcome.metadata;
+        match outcome.result {
+            Ok(()) => {
+                self.return_data = metadata
+                    .and_then(|metadata| metadata.return_data)
+                    .filter(|return_data| !return_data.data.is_empty())
+                    .map(|return_data| return_data.data);
+                Ok(())
+            }
+            Err(error) => Err(TransactionFailure {
+                error,
+                logs: metadata
+                    .map(|metadata| metadata.log_messages)
+                    .unwrap_or_default(),
+            }),
+        }
+    }
+
+    fn sign(&self, instructions: &[Instruction], signers: &[&Keypair]) -> Transaction {
+        let mut all_signers = vec![&self.context.payer];
+        all_signers.extend_from_slice(signers);
+        Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.context.payer.pubkey()),
+            &all_signers,
+            self.context.last_blockhash,
+        )
+    }
+}
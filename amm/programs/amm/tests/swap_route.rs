@@ -0,0 +1,128 @@
+// swap_route through two pools that share a mint, on the solana-program-test runtime in
+// runtime/mod.rs.
+mod fixture;
+mod runtime;
+
+use amm::error::AMMError;
+use amm::quote::quote_swap_exact_in;
+use amm::state::{AMMPool, PoolRegistry, PoolRegistryEntry};
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::token;
+use solana_sdk::signature::Signer;
+
+use fixture::{assert_amm_error, registry_entry, MintKind, Pool, PoolFixture};
+
+struct Route {
+    fixture: PoolFixture,
+    // Pairs the fixture pool's mint B with a new mint C
+    second_pool: Pool,
+    user_token_c: Pubkey,
+    // Output of routing 1_000_000 A to C, as quoted on both pools
+    quoted_amount_out: u64,
+}
+
+// The fixture's A/B pool and a B/C pool, both funded 10M/10M
+async fn route() -> Route {
+    let mut fixture = PoolFixture::with_liquidity(10_000_000, 10_000_000).await;
+    let mint_b = fixture.pool.mint_b;
+    let mint_c = fixture.create_mint(MintKind::SPL_TOKEN).await;
+    let b_is_a = mint_b < mint_c;
+    let second_pool = if b_is_a {
+        Pool::new(mint_b, token::ID, mint_c, token::ID)
+    } else {
+        Pool::new(mint_c, token::ID, mint_b, token::ID)
+    };
+    let curve = fixture.curve;
+    fixture.initialize(second_pool, curve).await.unwrap();
+    fixture
+        .add_liquidity_to(second_pool, 10_000_000, 10_000_000, 0)
+        .await
+        .unwrap();
+
+    let user = fixture.user.pubkey();
+    let user_token_c = if b_is_a {
+        second_pool.token_b(&user)
+    } else {
+        second_pool.token_a(&user)
+    };
+    let first_state = fixture.pool_state().await;
+    let second_state: AMMPool = fixture.runtime.anchor_account(&second_pool.amm_pool).await;
+    let now = fixture.runtime.unix_timestamp().await;
+    let amount_b = quote_swap_exact_in(&first_state, 10_000_000, 10_000_000, 1_000_000, true, now)
+        .unwrap()
+        .amount_out;
+    let quoted_amount_out =
+        quote_swap_exact_in(&second_state, 10_000_000, 10_000_000, amount_b, b_is_a, now)
+            .unwrap()
+            .amount_out;
+
+    Route {
+        fixture,
+        second_pool,
+        user_token_c,
+        quoted_amount_out,
+    }
+}
+
+#[tokio::test]
+async fn swap_route_trades_through_both_pools() {
+    let Route {
+        mut fixture,
+        second_pool,
+        user_token_c,
+        quoted_amount_out,
+    } = route().await;
+    let first_pool = fixture.pool;
+    let user_c_before = fixture.balance(user_token_c).await;
+
+    fixture
+        .swap_route(
+            fixture.user_token_a(),
+            user_token_c,
+            &[first_pool, second_pool],
+            1_000_000,
+            quoted_amount_out,
+        )
+        .await
+        .unwrap();
+
+    let received = fixture.balance(user_token_c).await - user_c_before;
+    assert_eq!(received, quoted_amount_out);
+    let amount_out = u64::from_le_bytes(fixture.runtime.return_data().unwrap().try_into().unwrap());
+    assert_eq!(amount_out, received);
+    assert_eq!(fixture.balance(first_pool.vault_a).await, 11_000_000);
+
+    // Both hops moved their pool's price, so both record it
+    let first_state = fixture.pool_state().await;
+    let second_state: AMMPool = fixture.runtime.anchor_account(&second_pool.amm_pool).await;
+    assert!(first_state.volatility_bps > 0);
+    assert!(second_state.volatility_bps > 0);
+
+    let registry: PoolRegistry = fixture.runtime.anchor_account(&fixture.registry).await;
+    assert_eq!(registry.pool_count, 2);
+    let entry: PoolRegistryEntry = fixture.runtime.anchor_account(&registry_entry(1)).await;
+    assert_eq!(entry.pool, second_pool.amm_pool);
+}
+
+#[tokio::test]
+async fn swap_route_fails_below_min_amount_out() {
+    let Route {
+        mut fixture,
+        second_pool,
+        user_token_c,
+        quoted_amount_out,
+    } = route().await;
+    let first_pool = fixture.pool;
+
+    let result = fixture
+        .swap_route(
+            fixture.user_token_a(),
+            user_token_c,
+            &[first_pool, second_pool],
+            1_000_000,
+            quoted_amount_out + 1,
+        )
+        .await;
+    assert_amm_error(result, AMMError::SlippageExceeded);
+    assert_eq!(fixture.balance(first_pool.vault_a).await, 10_000_000);
+}
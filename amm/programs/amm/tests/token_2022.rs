@@ -0,0 +1,195 @@
+// Pools with a Token-2022 transfer fee mint on one side: the pool trades what actually
+// reaches its vaults and slippage is checked on what actually reaches the user.
+mod fixture;
+mod runtime;
+
+use amm::error::AMMError;
+use amm::quote::{amount_with_transfer_fee, quote_swap_exact_in, quote_withdraw};
+
+use fixture::{assert_amm_error, FixtureOptions, MintKind, PoolFixture};
+
+// 1% on every transfer, with no cap
+const TRANSFER_FEE_BPS: u16 = 100;
+
+async fn fixture_with_fee_on(is_a: bool) -> PoolFixture {
+    let fee_mint = MintKind::transfer_fee(TRANSFER_FEE_BPS);
+    let (mint_a, mint_b) = if is_a {
+        (fee_mint, MintKind::SPL_TOKEN)
+    } else {
+        (MintKind::SPL_TOKEN, fee_mint)
+    };
+    let mut fixture = PoolFixture::with_options(FixtureOptions {
+        mint_a,
+        mint_b,
+        ..FixtureOptions::default()
+    })
+    .await;
+    fixture
+        .add_liquidity(100_000_000, 100_000_000, 0)
+        .await
+        .unwrap();
+    fixture
+}
+
+#[tokio::test]
+async fn swap_prices_what_reaches_the_vault_when_the_input_mint_charges_a_fee() {
+    let mut fixture = fixture_with_fee_on(true).await;
+    let reserve_a = fixture.balance(fixture.pool.vault_a).await;
+    let reserve_b = fixture.balance(fixture.pool.vault_b).await;
+    // The deposit itself paid the transfer fee on its way into vault A
+    assert_eq!(reserve_a, 99_000_000);
+
+    let transfer_fee = fixture
+        .runtime
+        .transfer_fee(&fixture.pool.mint_a, 1_000_000)
+        .await;
+    let pool = fixture.pool_state().await;
+    let now = fixture.runtime.unix_timestamp().await;
+    let quote = quote_swap_exact_in(
+        &pool,
+        reserve_a,
+        reserve_b,
+        1_000_000 - transfer_fee,
+        true,
+        now,
+    )
+    .unwrap();
+    let user_b_before = fixture.balance(fixture.user_token_b()).await;
+
+    fixture
+        .swap(1_000_000, quote.amount_out, true)
+        .await
+        .unwrap();
+
+    assert_eq!(transfer_fee, 10_000);
+    assert_eq!(
+        fixture.balance(fixture.pool.vault_a).await,
+        reserve_a + 1_000_000 - transfer_fee
+    );
+    assert_eq!(
+        fixture.balance(fixture.user_token_b()).await - user_b_before,
+        quote.amount_out
+    );
+}
+
+#[tokio::test]
+async fn swap_checks_slippage_on_what_the_user_receives() {
+    let mut fixture = fixture_with_fee_on(false).await;
+    let reserve_a = fixture.balance(fixture.pool.vault_a).await;
+    let reserve_b = fixture.balance(fixture.pool.vault_b).await;
+    let pool = fixture.pool_state().await;
+    let now = fixture.runtime.unix_timestamp().await;
+    let quote = quote_swap_exact_in(&pool, reserve_a, reserve_b, 1_000_000, true, now).unwrap();
+    let transfer_fee = fixture
+        .runtime
+        .transfer_fee(&fixture.pool.mint_b, quote.amount_out)
+        .await;
+    let received = quote.amount_out - transfer_fee;
+
+    // What leaves the vault is not what arrives, so asking for it fails
+    let result = fixture.swap(1_000_000, quote.amount_out, true).await;
+    assert_amm_error(result, AMMError::SlippageExceeded);
+
+    let user_b_before = fixture.balance(fixture.user_token_b()).await;
+    fixture.swap(1_000_000, received, true).await.unwrap();
+
+    assert_eq!(
+        fixture.balance(fixture.user_token_b()).await - user_b_before,
+        received
+    );
+    assert_eq!(
+        fixture.balance(fixture.pool.vault_b).await,
+        reserve_b - quote.amount_out
+    );
+    assert_eq!(fixture.swap_result().amount_out, received);
+}
+
+#[tokio::test]
+async fn swap_exact_out_delivers_the_amount_net_of_the_output_fee() {
+    let mut fixture = fixture_with_fee_on(false).await;
+    let user_a_before = fixture.balance(fixture.user_token_a()).await;
+    let user_b_before = fixture.balance(fixture.user_token_b()).await;
+    let vault_b_before = fixture.balance(fixture.pool.vault_b).await;
+
+    fixture
+        .swap_exact_out(1_000_000, 2_000_000, true)
+        .await
+        .unwrap();
+
+    let result = fixture.swap_result();
+    let received = fixture.balance(fixture.user_token_b()).await - user_b_before;
+    // A client grosses the payout up exactly like the program does
+    let (mint_owner, mint_data) = fixture.runtime.account(&fixture.pool.mint_b).await;
+    let epoch = fixture.runtime.epoch().await;
+    let amount_sent = amount_with_transfer_fee(&mint_owner, &mint_data, epoch, 1_000_000).unwrap();
+    assert_eq!(
+        vault_b_before - fixture.balance(fixture.pool.vault_b).await,
+        amount_sent
+    );
+    assert!(received >= 1_000_000, "{received}");
+    // The fee rounds up, so grossing up by it can leave at most one unit extra
+    assert!(received <= 1_000_001, "{received}");
+    assert_eq!(result.amount_out, received);
+    assert_eq!(
+        user_a_before - fixture.balance(fixture.user_token_a()).await,
+        result.amount_in
+    );
+}
+
+#[tokio::test]
+async fn withdraw_liquidity_checks_the_minimums_on_what_the_user_receives() {
+    let mut fixture = fixture_with_fee_on(false).await;
+    let reserve_a = fixture.balance(fixture.pool.vault_a).await;
+    let reserve_b = fixture.balance(fixture.pool.vault_b).await;
+    let to_burn = fixture.balance(fixture.user_lp()).await / 2;
+    let pool = fixture.pool_state().await;
+    let quote = quote_withdraw(&pool, reserve_a, reserve_b, to_burn).unwrap();
+    let transfer_fee = fixture
+        .runtime
+        .transfer_fee(&fixture.pool.mint_b, quote.amount_b)
+        .await;
+    let received_b = quote.amount_b - transfer_fee;
+
+    let result = fixture
+        .withdraw_liquidity(to_burn, quote.amount_a, quote.amount_b)
+        .await;
+    assert_amm_error(result, AMMError::SlippageExceeded);
+
+    let user_b_before = fixture.balance(fixture.user_token_b()).await;
+    fixture
+        .withdraw_liquidity(to_burn, quote.amount_a, received_b)
+        .await
+        .unwrap();
+    assert_eq!(
+        fixture.balance(fixture.user_token_b()).await - user_b_before,
+        received_b
+    );
+}
+
+#[tokio::test]
+async fn withdraw_liquidity_single_checks_min_out_on_what_the_user_receives() {
+    let mut fixture = fixture_with_fee_on(false).await;
+    let lp_balance = fixture.balance(fixture.user_lp()).await;
+    let vault_b_before = fixture.balance(fixture.pool.vault_b).await;
+    let user_b_before = fixture.balance(fixture.user_token_b()).await;
+
+    // Find out what leaves the vault, then ask for exactly that in a fresh pool
+    fixture
+        .withdraw_liquidity_single(lp_balance / 10, false, 0)
+        .await
+        .unwrap();
+    let sent = vault_b_before - fixture.balance(fixture.pool.vault_b).await;
+    let received = fixture.balance(fixture.user_token_b()).await - user_b_before;
+    assert!(received < sent);
+
+    let mut fixture = fixture_with_fee_on(false).await;
+    let lp_balance = fixture.balance(fixture.user_lp()).await;
+    let result = fixture
+        .withdraw_liquidity_single(lp_balance / 10, false, sent)
+        .await;
+    assert_amm_error(result, AMMError::SlippageExceeded);
+    fixture
+        .withdraw_liquidity_single(lp_balance / 10, false, received)
+        .await
+        .unwrap();
+}
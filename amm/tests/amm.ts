@@ -12,20 +12,45 @@ import {
   createMintToInstruction,
   ASSOCIATED_TOKEN_PROGRAM_ID,
   getAssociatedTokenAddress,
+  TOKEN_2022_PROGRAM_ID,
+  ExtensionType,
+  getMintLen,
+  createInitializeTransferFeeConfigInstruction,
 } from "@solana/spl-token";
 import { assert } from "chai";
 
+// Pools only accept their mints in canonical order, lowest address first
+const sortedMintKeypairs = (): [anchor.web3.Keypair, anchor.web3.Keypair] => {
+  const [first, second] = [
+    anchor.web3.Keypair.generate(),
+    anchor.web3.Keypair.generate(),
+  ];
+  return first.publicKey.toBuffer().compare(second.publicKey.toBuffer()) < 0
+    ? [first, second]
+    : [second, first];
+};
+
 describe("Automated Market Maker", () => {
   anchor.setProvider(anchor.AnchorProvider.env());
   const provider = anchor.getProvider();
   const program = anchor.workspace.amm as Program<Amm>;
 
   const initializer = provider.wallet.publicKey;
-  const tokenAMint = anchor.web3.Keypair.generate();
-  const tokenBMint = anchor.web3.Keypair.generate();
+  const [tokenAMint, tokenBMint] = sortedMintKeypairs();
   const tokensAuthority = anchor.web3.Keypair.generate();
   const liquidityProvider = anchor.web3.Keypair.generate();
 
+  // 30 bps to liquidity providers plus a 5 bps protocol cut
+  const FEE_NUMERATOR = new anchor.BN(30);
+  const FEE_DENOMINATOR = new anchor.BN(10_000);
+  const PROTOCOL_FEE_NUMERATOR = new anchor.BN(5);
+  const CONSTANT_PRODUCT = {
+    curveType: { constantProduct: {} },
+    amp: new anchor.BN(0),
+    weightA: new anchor.BN(0),
+  };
+  const MINIMUM_LIQUIDITY = 1_000;
+
   let ammPoolPda: anchor.web3.PublicKey;
   let ammBump: number;
   let vaultA: anchor.web3.PublicKey;
@@ -89,6 +114,11 @@ describe("Automated Market Maker", () => {
 
     await provider.sendAndConfirm(tx, [tokenAMint, tokenBMint]);
 
+    await program.methods
+      .initializeRegistry()
+      .accounts({ payer: initializer })
+      .rpc();
+
     [ammPoolPda, ammBump] = anchor.web3.PublicKey.findProgramAddressSync(
       [
         Buffer.from("pool"),
@@ -138,13 +168,50 @@ describe("Automated Market Maker", () => {
   });
 
   describe("Initialize Pool", () => {
+    it("Should fail when the fee is not below the denominator", async () => {
+      try {
+        await program.methods
+          .initializePool(
+            FEE_DENOMINATOR,
+            FEE_DENOMINATOR,
+            PROTOCOL_FEE_NUMERATOR,
+            initializer,
+            CONSTANT_PRODUCT
+          )
+          .accounts({
+            initializer: initializer,
+            tokenAMint: tokenAMint.publicKey,
+            tokenBMint: tokenBMint.publicKey,
+            tokenAProgram: TOKEN_PROGRAM_ID,
+            tokenBProgram: TOKEN_PROGRAM_ID,
+          })
+          .rpc();
+
+        assert.fail("Should have failed with invalid fee error");
+      } catch (error) {
+        assert.include(
+          error.toString(),
+          "InvalidFee",
+          "Should fail with InvalidFee error"
+        );
+      }
+    });
+
     it("Should initialize the pool successfully", async () => {
       const tx = await program.methods
-        .initializePool()
+        .initializePool(
+          FEE_NUMERATOR,
+          FEE_DENOMINATOR,
+          PROTOCOL_FEE_NUMERATOR,
+          initializer,
+          CONSTANT_PRODUCT
+        )
         .accounts({
           initializer: initializer,
           tokenAMint: tokenAMint.publicKey,
           tokenBMint: tokenBMint.publicKey,
+          tokenAProgram: TOKEN_PROGRAM_ID,
+          tokenBProgram: TOKEN_PROGRAM_ID,
         })
         .rpc();
 
@@ -179,6 +246,27 @@ describe("Automated Market Maker", () => {
         "0",
         "Initial LP issued should be 0"
       );
+      assert.strictEqual(
+        pool.feeNumerator.toString(),
+        FEE_NUMERATOR.toString(),
+        "Pool fee numerator should match"
+      );
+      assert.strictEqual(
+        pool.feeDenominator.toString(),
+        FEE_DENOMINATOR.toString(),
+        "Pool fee denominator should match"
+      );
+      assert.strictEqual(
+        pool.feeAuthority.toBase58(),
+        initializer.toBase58(),
+        "Pool fee authority should match"
+      );
+      assert.strictEqual(
+        pool.admin.toBase58(),
+        initializer.toBase58(),
+        "Pool admin should be the initializer"
+      );
+      assert.isFalse(pool.paused, "Pool should start unpaused");
       assert.strictEqual(
         pool.bump.toString(),
         ammBump.toString(),
@@ -240,16 +328,49 @@ describe("Automated Market Maker", () => {
         "0",
         "LP mint supply should start at 0"
       );
+
+      const [registryPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("registry")],
+        program.programId
+      );
+      const registry = await program.account.poolRegistry.fetch(registryPda);
+      const registryEntryPdas = Array.from(
+        { length: registry.poolCount.toNumber() },
+        (_, index) =>
+          anchor.web3.PublicKey.findProgramAddressSync(
+            [
+              Buffer.from("registry"),
+              new anchor.BN(index).toArrayLike(Buffer, "le", 8),
+            ],
+            program.programId
+          )[0]
+      );
+      const registryEntries =
+        await program.account.poolRegistryEntry.fetchMultiple(
+          registryEntryPdas
+        );
+      assert.isTrue(
+        registryEntries.some((entry) => entry.pool.equals(ammPoolPda)),
+        "Pool should be listed in the registry"
+      );
     });
 
     it("Should fail when trying to initialize with the same token", async () => {
       try {
         await program.methods
-          .initializePool()
+          .initializePool(
+            FEE_NUMERATOR,
+            FEE_DENOMINATOR,
+            PROTOCOL_FEE_NUMERATOR,
+            initializer,
+            CONSTANT_PRODUCT
+          )
           .accounts({
             initializer: initializer,
             tokenAMint: tokenAMint.publicKey,
             tokenBMint: tokenAMint.publicKey,
+            tokenAProgram: TOKEN_PROGRAM_ID,
+            tokenBProgram: TOKEN_PROGRAM_ID,
           })
           .rpc();
 
@@ -263,14 +384,51 @@ describe("Automated Market Maker", () => {
       }
     });
 
+    it("Should fail when the mints are not in canonical order", async () => {
+      try {
+        await program.methods
+          .initializePool(
+            FEE_NUMERATOR,
+            FEE_DENOMINATOR,
+            PROTOCOL_FEE_NUMERATOR,
+            initializer,
+            CONSTANT_PRODUCT
+          )
+          .accounts({
+            initializer: initializer,
+            tokenAMint: tokenBMint.publicKey,
+            tokenBMint: tokenAMint.publicKey,
+            tokenAProgram: TOKEN_PROGRAM_ID,
+            tokenBProgram: TOKEN_PROGRAM_ID,
+          })
+          .rpc();
+
+        assert.fail("Should have failed with unsorted mints error");
+      } catch (error) {
+        assert.include(
+          error.toString(),
+          "UnsortedMints",
+          "Should fail with UnsortedMints error"
+        );
+      }
+    });
+
     it("Should fail when trying to initialize pool twice", async () => {
       try {
         await program.methods
-          .initializePool()
+          .initializePool(
+            FEE_NUMERATOR,
+            FEE_DENOMINATOR,
+            PROTOCOL_FEE_NUMERATOR,
+            initializer,
+            CONSTANT_PRODUCT
+          )
           .accounts({
             initializer: initializer,
             tokenAMint: tokenAMint.publicKey,
             tokenBMint: tokenBMint.publicKey,
+            tokenAProgram: TOKEN_PROGRAM_ID,
+            tokenBProgram: TOKEN_PROGRAM_ID,
           })
           .rpc();
 
@@ -339,11 +497,13 @@ describe("Automated Market Maker", () => {
       const initialVaultB = await getAccount(provider.connection, vaultB);
 
       const tx = await program.methods
-        .addLiquidity(quantityA, quantityB)
+        .addLiquidity(quantityA, quantityB, new anchor.BN(0), null)
         .accounts({
           liquidityProvider: liquidityProvider.publicKey,
           tokenAMint: tokenAMint.publicKey,
           tokenBMint: tokenBMint.publicKey,
+          tokenAProgram: TOKEN_PROGRAM_ID,
+          tokenBProgram: TOKEN_PROGRAM_ID,
         })
         .signers([liquidityProvider])
         .rpc();
@@ -383,11 +543,13 @@ describe("Automated Market Maker", () => {
       const initialPool = await program.account.ammPool.fetch(ammPoolPda);
 
       const tx = await program.methods
-        .addLiquidity(quantityA, quantityB)
+        .addLiquidity(quantityA, quantityB, new anchor.BN(0), null)
         .accounts({
           liquidityProvider: liquidityProvider.publicKey,
           tokenAMint: tokenAMint.publicKey,
           tokenBMint: tokenBMint.publicKey,
+          tokenAProgram: TOKEN_PROGRAM_ID,
+          tokenBProgram: TOKEN_PROGRAM_ID,
         })
         .signers([liquidityProvider])
         .rpc();
@@ -421,11 +583,18 @@ describe("Automated Market Maker", () => {
     it("should fail with zero amounts", async () => {
       try {
         await program.methods
-          .addLiquidity(new anchor.BN(0), new anchor.BN(100 * 1_000_000))
+          .addLiquidity(
+            new anchor.BN(0),
+            new anchor.BN(100 * 1_000_000),
+            new anchor.BN(0),
+            null
+          )
           .accounts({
             liquidityProvider: liquidityProvider.publicKey,
             tokenAMint: tokenAMint.publicKey,
             tokenBMint: tokenBMint.publicKey,
+            tokenAProgram: TOKEN_PROGRAM_ID,
+            tokenBProgram: TOKEN_PROGRAM_ID,
           })
           .signers([liquidityProvider])
           .rpc();
@@ -441,32 +610,110 @@ describe("Automated Market Maker", () => {
       }
     });
 
-    it("should fail with invalid liquidity ratio", async () => {
+    it("should only deposit the proportional part of the maximums", async () => {
+      const initialVaultA = await getAccount(provider.connection, vaultA);
+      const initialVaultB = await getAccount(provider.connection, vaultB);
+
+      await program.methods
+        .addLiquidity(
+          new anchor.BN(100 * 1_000_000),
+          new anchor.BN(100 * 1_000_000),
+          new anchor.BN(0),
+          null
+        )
+        .accounts({
+          liquidityProvider: liquidityProvider.publicKey,
+          tokenAMint: tokenAMint.publicKey,
+          tokenBMint: tokenBMint.publicKey,
+          tokenAProgram: TOKEN_PROGRAM_ID,
+          tokenBProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([liquidityProvider])
+        .rpc();
+
+      const finalVaultA = await getAccount(provider.connection, vaultA);
+      const finalVaultB = await getAccount(provider.connection, vaultB);
+
+      // pool is at a 1:2 ratio, so token B is the limiting side
+      assert.equal(
+        (finalVaultA.amount - initialVaultA.amount).toString(),
+        (50 * 1_000_000).toString(),
+        "Only the proportional amount of token A should be deposited"
+      );
+      assert.equal(
+        (finalVaultB.amount - initialVaultB.amount).toString(),
+        (100 * 1_000_000).toString(),
+        "The full maximum of token B should be deposited"
+      );
+    });
+
+    it("should fail when fewer LP tokens than min_lp_out would be minted", async () => {
       try {
         await program.methods
           .addLiquidity(
+            new anchor.BN(50 * 1_000_000),
             new anchor.BN(100 * 1_000_000),
-            new anchor.BN(100 * 1_000_000)
+            new anchor.BN("1000000000000000"),
+            null
           )
           .accounts({
             liquidityProvider: liquidityProvider.publicKey,
             tokenAMint: tokenAMint.publicKey,
             tokenBMint: tokenBMint.publicKey,
+            tokenAProgram: TOKEN_PROGRAM_ID,
+            tokenBProgram: TOKEN_PROGRAM_ID,
           })
           .signers([liquidityProvider])
           .rpc();
 
-        assert.fail("Should have failed with invalid liquidity ratio");
+        assert.fail("Should have failed with slippage exceeded");
       } catch (error) {
         assert.include(
           error.toString(),
-          "InvalidLiquidity",
-          "Should fail with InvalidLiquidity error"
+          "SlippageExceeded",
+          "Should fail with SlippageExceeded error"
         );
-        console.log("Invalid ratio test passed - correctly rejected");
       }
     });
 
+    it("should add single sided liquidity", async () => {
+      const initialVaultA = await getAccount(provider.connection, vaultA);
+      const initialVaultB = await getAccount(provider.connection, vaultB);
+      const initialPool = await program.account.ammPool.fetch(ammPoolPda);
+      const quantity = new anchor.BN(10 * 1_000_000);
+
+      await program.methods
+        .addLiquiditySingleSided(quantity, true, new anchor.BN(1), null)
+        .accounts({
+          liquidityProvider: liquidityProvider.publicKey,
+          tokenAMint: tokenAMint.publicKey,
+          tokenBMint: tokenBMint.publicKey,
+          tokenAProgram: TOKEN_PROGRAM_ID,
+          tokenBProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([liquidityProvider])
+        .rpc();
+
+      const finalVaultA = await getAccount(provider.connection, vaultA);
+      const finalVaultB = await getAccount(provider.connection, vaultB);
+      const finalPool = await program.account.ammPool.fetch(ammPoolPda);
+
+      assert.equal(
+        (finalVaultA.amount - initialVaultA.amount).toString(),
+        quantity.toString(),
+        "Vault A should receive the whole single sided deposit"
+      );
+      assert.equal(
+        finalVaultB.amount.toString(),
+        initialVaultB.amount.toString(),
+        "Vault B should be untouched"
+      );
+      assert.isTrue(
+        finalPool.totalLpIssued.gt(initialPool.totalLpIssued),
+        "LP tokens should be minted for the deposit"
+      );
+    });
+
     it("should fail with wrong token mint", async () => {
       const wrongMint = anchor.web3.Keypair.generate();
 
@@ -474,12 +721,16 @@ describe("Automated Market Maker", () => {
         await program.methods
           .addLiquidity(
             new anchor.BN(100 * 1_000_000),
-            new anchor.BN(200 * 1_000_000)
+            new anchor.BN(200 * 1_000_000),
+            new anchor.BN(0),
+            null
           )
           .accounts({
             liquidityProvider: liquidityProvider.publicKey,
             tokenAMint: wrongMint.publicKey,
             tokenBMint: tokenBMint.publicKey,
+            tokenAProgram: TOKEN_PROGRAM_ID,
+            tokenBProgram: TOKEN_PROGRAM_ID,
           })
           .signers([liquidityProvider])
           .rpc();
@@ -530,12 +781,16 @@ describe("Automated Market Maker", () => {
         await program.methods
           .addLiquidity(
             new anchor.BN(100 * 1_000_000),
-            new anchor.BN(200 * 1_000_000)
+            new anchor.BN(200 * 1_000_000),
+            new anchor.BN(0),
+            null
           )
           .accounts({
             liquidityProvider: newProvider.publicKey,
             tokenAMint: tokenAMint.publicKey,
             tokenBMint: tokenBMint.publicKey,
+            tokenAProgram: TOKEN_PROGRAM_ID,
+            tokenBProgram: TOKEN_PROGRAM_ID,
           })
           .signers([newProvider])
           .rpc();
@@ -552,8 +807,7 @@ describe("Automated Market Maker", () => {
     });
 
     it("should calculate LP tokens correctly ", async () => {
-      const newTokenAMint = anchor.web3.Keypair.generate();
-      const newTokenBMint = anchor.web3.Keypair.generate();
+      const [newTokenAMint, newTokenBMint] = sortedMintKeypairs();
 
       const mintRent =
         await provider.connection.getMinimumBalanceForRentExemption(MINT_SIZE);
@@ -593,11 +847,19 @@ describe("Automated Market Maker", () => {
       ]);
 
       await program.methods
-        .initializePool()
+        .initializePool(
+          FEE_NUMERATOR,
+          FEE_DENOMINATOR,
+          PROTOCOL_FEE_NUMERATOR,
+          initializer,
+          CONSTANT_PRODUCT
+        )
         .accounts({
           initializer: initializer,
           tokenAMint: newTokenAMint.publicKey,
           tokenBMint: newTokenBMint.publicKey,
+          tokenAProgram: TOKEN_PROGRAM_ID,
+          tokenBProgram: TOKEN_PROGRAM_ID,
         })
         .rpc();
 
@@ -652,28 +914,214 @@ describe("Automated Market Maker", () => {
       await provider.sendAndConfirm(mintIx, [tokensAuthority]);
 
       await program.methods
-        .addLiquidity(quantityA, quantityB)
+        .addLiquidity(quantityA, quantityB, new anchor.BN(0), null)
         .accounts({
           liquidityProvider: liquidityProvider.publicKey,
           tokenAMint: newTokenAMint.publicKey,
           tokenBMint: newTokenBMint.publicKey,
+          tokenAProgram: TOKEN_PROGRAM_ID,
+          tokenBProgram: TOKEN_PROGRAM_ID,
         })
         .signers([liquidityProvider])
         .rpc();
 
       const lpMintAccount = await getMint(provider.connection, newLpMint);
 
-      const expectedLpTokens = 100_000_000_000;
+      // sqrt(100e6 * 100e6 * 10^6) minus the permanently locked minimum liquidity
+      const expectedLpTokens = 100_000_000_000 - MINIMUM_LIQUIDITY;
       assert.equal(
         Number(lpMintAccount.supply),
         expectedLpTokens,
         "LP tokens should match calculation"
       );
 
+      const [newPoolPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("pool"),
+          newTokenAMint.publicKey.toBytes(),
+          newTokenBMint.publicKey.toBytes(),
+        ],
+        program.programId
+      );
+      const newPool = await program.account.ammPool.fetch(newPoolPda);
+      assert.equal(
+        newPool.totalLpIssued.toNumber(),
+        100_000_000_000,
+        "Locked minimum liquidity should count towards the LP supply"
+      );
+
       console.log("LP token calculation test passed");
       console.log("Expected LP tokens:", expectedLpTokens);
       console.log("Actual LP tokens:", lpMintAccount.supply.toString());
     });
+
+    it("should issue LP tokens for what a transfer-fee mint delivers", async () => {
+      const [feeTokenAMint, plainTokenBMint] = sortedMintKeypairs();
+
+      // 1% Token-2022 transfer fee on token A, capped far above these amounts
+      const transferFeeBasisPoints = 100;
+      const feeMintLen = getMintLen([ExtensionType.TransferFeeConfig]);
+      const feeMintRent =
+        await provider.connection.getMinimumBalanceForRentExemption(feeMintLen);
+      const mintRent =
+        await provider.connection.getMinimumBalanceForRentExemption(MINT_SIZE);
+
+      const createMintsIx = new anchor.web3.Transaction().add(
+        anchor.web3.SystemProgram.createAccount({
+          fromPubkey: initializer,
+          newAccountPubkey: feeTokenAMint.publicKey,
+          lamports: feeMintRent,
+          space: feeMintLen,
+          programId: TOKEN_2022_PROGRAM_ID,
+        }),
+        createInitializeTransferFeeConfigInstruction(
+          feeTokenAMint.publicKey,
+          tokensAuthority.publicKey,
+          tokensAuthority.publicKey,
+          transferFeeBasisPoints,
+          BigInt(1_000_000_000),
+          TOKEN_2022_PROGRAM_ID
+        ),
+        createInitializeMintInstruction(
+          feeTokenAMint.publicKey,
+          6,
+          tokensAuthority.publicKey,
+          null,
+          TOKEN_2022_PROGRAM_ID
+        ),
+        anchor.web3.SystemProgram.createAccount({
+          fromPubkey: initializer,
+          newAccountPubkey: plainTokenBMint.publicKey,
+          lamports: mintRent,
+          space: MINT_SIZE,
+          programId: TOKEN_PROGRAM_ID,
+        }),
+        createInitializeMintInstruction(
+          plainTokenBMint.publicKey,
+          6,
+          tokensAuthority.publicKey,
+          null
+        )
+      );
+
+      await provider.sendAndConfirm(createMintsIx, [
+        feeTokenAMint,
+        plainTokenBMint,
+      ]);
+
+      await program.methods
+        .initializePool(
+          FEE_NUMERATOR,
+          FEE_DENOMINATOR,
+          PROTOCOL_FEE_NUMERATOR,
+          initializer,
+          CONSTANT_PRODUCT
+        )
+        .accounts({
+          initializer: initializer,
+          tokenAMint: feeTokenAMint.publicKey,
+          tokenBMint: plainTokenBMint.publicKey,
+          tokenAProgram: TOKEN_2022_PROGRAM_ID,
+          tokenBProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+      const feeTokenAccountA = await createAssociatedTokenAccount(
+        provider.connection,
+        liquidityProvider,
+        feeTokenAMint.publicKey,
+        liquidityProvider.publicKey,
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+      const plainTokenAccountB = await createAssociatedTokenAccount(
+        provider.connection,
+        liquidityProvider,
+        plainTokenBMint.publicKey,
+        liquidityProvider.publicKey
+      );
+
+      const [feePoolLpMint] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("lp_mint"),
+          feeTokenAMint.publicKey.toBytes(),
+          plainTokenBMint.publicKey.toBytes(),
+        ],
+        program.programId
+      );
+      await createAssociatedTokenAccount(
+        provider.connection,
+        liquidityProvider,
+        feePoolLpMint,
+        liquidityProvider.publicKey
+      );
+
+      const quantity = 100 * 1_000_000;
+      const mintIx = new anchor.web3.Transaction().add(
+        createMintToInstruction(
+          feeTokenAMint.publicKey,
+          feeTokenAccountA,
+          tokensAuthority.publicKey,
+          quantity,
+          [],
+          TOKEN_2022_PROGRAM_ID
+        ),
+        createMintToInstruction(
+          plainTokenBMint.publicKey,
+          plainTokenAccountB,
+          tokensAuthority.publicKey,
+          quantity
+        )
+      );
+      await provider.sendAndConfirm(mintIx, [tokensAuthority]);
+
+      await program.methods
+        .addLiquidity(
+          new anchor.BN(quantity),
+          new anchor.BN(quantity),
+          new anchor.BN(0),
+          null
+        )
+        .accounts({
+          liquidityProvider: liquidityProvider.publicKey,
+          tokenAMint: feeTokenAMint.publicKey,
+          tokenBMint: plainTokenBMint.publicKey,
+          tokenAProgram: TOKEN_2022_PROGRAM_ID,
+          tokenBProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([liquidityProvider])
+        .rpc();
+
+      const [feePoolVaultA] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("vault_token"),
+          feeTokenAMint.publicKey.toBytes(),
+          plainTokenBMint.publicKey.toBytes(),
+          Buffer.from("A"),
+        ],
+        program.programId
+      );
+      const feePoolVaultAAccount = await getAccount(
+        provider.connection,
+        feePoolVaultA,
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+      const receivedA = quantity - (quantity * transferFeeBasisPoints) / 10_000;
+      assert.equal(
+        Number(feePoolVaultAAccount.amount),
+        receivedA,
+        "Vault A should hold the deposit net of the transfer fee"
+      );
+
+      // sqrt(99e6 * 100e6 * 10^6), priced on what vault A actually received
+      const lpMintAccount = await getMint(provider.connection, feePoolLpMint);
+      assert.equal(
+        Number(lpMintAccount.supply),
+        99_498_743_710 - MINIMUM_LIQUIDITY,
+        "LP tokens should be issued for the amount received"
+      );
+    });
   });
 
   describe("Withdraw Liquidity", () => {
@@ -724,11 +1172,17 @@ describe("Automated Market Maker", () => {
         );
 
         await program.methods
-          .withdrawLiquidity(new anchor.BN(withdrawAmount))
+          .withdrawLiquidity(
+            new anchor.BN(withdrawAmount),
+            new anchor.BN(0),
+            new anchor.BN(0)
+          )
           .accounts({
             liquidityProvider: liquidityProvider.publicKey,
             tokenAMint: tokenAMint.publicKey,
             tokenBMint: tokenBMint.publicKey,
+            tokenAProgram: TOKEN_PROGRAM_ID,
+            tokenBProgram: TOKEN_PROGRAM_ID,
           })
           .signers([liquidityProvider])
           .rpc();
@@ -768,14 +1222,62 @@ describe("Automated Market Maker", () => {
       }
     });
 
+    it("should withdraw liquidity into token A only", async () => {
+      const initialTokenA = await getAccount(
+        provider.connection,
+        tokenAccountAAddress
+      );
+      const initialTokenB = await getAccount(
+        provider.connection,
+        tokenAccountBAddress
+      );
+
+      await program.methods
+        .withdrawLiquiditySingle(new anchor.BN(100_000), true, new anchor.BN(1))
+        .accounts({
+          liquidityProvider: liquidityProvider.publicKey,
+          tokenAMint: tokenAMint.publicKey,
+          tokenBMint: tokenBMint.publicKey,
+          tokenAProgram: TOKEN_PROGRAM_ID,
+          tokenBProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([liquidityProvider])
+        .rpc();
+
+      const finalTokenA = await getAccount(
+        provider.connection,
+        tokenAccountAAddress
+      );
+      const finalTokenB = await getAccount(
+        provider.connection,
+        tokenAccountBAddress
+      );
+
+      assert.isTrue(
+        finalTokenA.amount > initialTokenA.amount,
+        "Should receive token A"
+      );
+      assert.equal(
+        finalTokenB.amount.toString(),
+        initialTokenB.amount.toString(),
+        "Token B balance should be unchanged"
+      );
+    });
+
     it("should fail with insufficient LP tokens", async () => {
       try {
         await program.methods
-          .withdrawLiquidity(new anchor.BN(1_000_000_000_000))
+          .withdrawLiquidity(
+            new anchor.BN(1_000_000_000_000),
+            new anchor.BN(0),
+            new anchor.BN(0)
+          )
           .accounts({
             liquidityProvider: liquidityProvider.publicKey,
             tokenAMint: tokenAMint.publicKey,
             tokenBMint: tokenBMint.publicKey,
+            tokenAProgram: TOKEN_PROGRAM_ID,
+            tokenBProgram: TOKEN_PROGRAM_ID,
           })
           .signers([liquidityProvider])
           .rpc();
@@ -791,15 +1293,42 @@ describe("Automated Market Maker", () => {
       }
     });
 
-    it("should fail with empty pool", async () => {
-      const newTokenAMint = anchor.web3.Keypair.generate();
-      const newTokenBMint = anchor.web3.Keypair.generate();
+    it("should fail when payout is below the minimum amounts", async () => {
+      try {
+        await program.methods
+          .withdrawLiquidity(
+            new anchor.BN(100_000),
+            new anchor.BN("1000000000000000"),
+            new anchor.BN(0)
+          )
+          .accounts({
+            liquidityProvider: liquidityProvider.publicKey,
+            tokenAMint: tokenAMint.publicKey,
+            tokenBMint: tokenBMint.publicKey,
+            tokenAProgram: TOKEN_PROGRAM_ID,
+            tokenBProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([liquidityProvider])
+          .rpc();
 
-      const mintRent =
-        await provider.connection.getMinimumBalanceForRentExemption(MINT_SIZE);
-      const createMintsIx = new anchor.web3.Transaction().add(
-        anchor.web3.SystemProgram.createAccount({
-          fromPubkey: initializer,
+        assert.fail("Should have failed with slippage exceeded");
+      } catch (error) {
+        assert.include(
+          error.toString(),
+          "SlippageExceeded",
+          "Should fail with SlippageExceeded error"
+        );
+      }
+    });
+
+    it("should fail with empty pool", async () => {
+      const [newTokenAMint, newTokenBMint] = sortedMintKeypairs();
+
+      const mintRent =
+        await provider.connection.getMinimumBalanceForRentExemption(MINT_SIZE);
+      const createMintsIx = new anchor.web3.Transaction().add(
+        anchor.web3.SystemProgram.createAccount({
+          fromPubkey: initializer,
           newAccountPubkey: newTokenAMint.publicKey,
           lamports: mintRent,
           space: MINT_SIZE,
@@ -832,11 +1361,19 @@ describe("Automated Market Maker", () => {
       ]);
 
       await program.methods
-        .initializePool()
+        .initializePool(
+          FEE_NUMERATOR,
+          FEE_DENOMINATOR,
+          PROTOCOL_FEE_NUMERATOR,
+          initializer,
+          CONSTANT_PRODUCT
+        )
         .accounts({
           initializer: initializer,
           tokenAMint: newTokenAMint.publicKey,
           tokenBMint: newTokenBMint.publicKey,
+          tokenAProgram: TOKEN_PROGRAM_ID,
+          tokenBProgram: TOKEN_PROGRAM_ID,
         })
         .rpc();
 
@@ -871,11 +1408,17 @@ describe("Automated Market Maker", () => {
 
       try {
         await program.methods
-          .withdrawLiquidity(new anchor.BN(1))
+          .withdrawLiquidity(
+            new anchor.BN(1),
+            new anchor.BN(0),
+            new anchor.BN(0)
+          )
           .accounts({
             liquidityProvider: liquidityProvider.publicKey,
             tokenAMint: newTokenAMint.publicKey,
             tokenBMint: newTokenBMint.publicKey,
+            tokenAProgram: TOKEN_PROGRAM_ID,
+            tokenBProgram: TOKEN_PROGRAM_ID,
           })
           .signers([liquidityProvider])
           .rpc();
@@ -924,16 +1467,29 @@ describe("Automated Market Maker", () => {
         const swapAmount = new anchor.BN(1_000_000); // 1 token
         const minSlippage = new anchor.BN(100_000); // Minimum 0.1 tokens out
 
+        const initialPool = await program.account.ammPool.fetch(ammPoolPda);
+        const reserveA =
+          Number(initialVaultA.amount) - initialPool.protocolFeesA.toNumber();
+        const reserveB =
+          Number(initialVaultB.amount) - initialPool.protocolFeesB.toNumber();
+
+        const fee = Math.ceil(
+          (swapAmount.toNumber() *
+            FEE_NUMERATOR.add(PROTOCOL_FEE_NUMERATOR).toNumber()) /
+            FEE_DENOMINATOR.toNumber()
+        );
+        const swapAmountAfterFee = swapAmount.toNumber() - fee;
         const expectedOutput =
-          (Number(initialVaultB.amount) * swapAmount.toNumber()) /
-          (Number(initialVaultA.amount) + swapAmount.toNumber());
+          (reserveB * swapAmountAfterFee) / (reserveA + swapAmountAfterFee);
 
         await program.methods
-          .swap(swapAmount, minSlippage, true) // true = A to B
+          .swap(swapAmount, minSlippage, true, null) // true = A to B
           .accounts({
             user: liquidityProvider.publicKey,
             tokenAMint: tokenAMint.publicKey,
             tokenBMint: tokenBMint.publicKey,
+            tokenAProgram: TOKEN_PROGRAM_ID,
+            tokenBProgram: TOKEN_PROGRAM_ID,
           })
           .signers([liquidityProvider])
           .rpc();
@@ -958,9 +1514,10 @@ describe("Automated Market Maker", () => {
           "User should spend exactly the swap amount of token A"
         );
 
-        assert.isTrue(
-          tokenBReceived > BigInt(0),
-          "User should receive some token B"
+        assert.equal(
+          tokenBReceived.toString(),
+          Math.floor(expectedOutput).toString(),
+          "User should receive the curve output for the amount after fee"
         );
 
         assert.equal(
@@ -977,15 +1534,9 @@ describe("Automated Market Maker", () => {
 
         const initialProduct = initialVaultA.amount * initialVaultB.amount;
         const finalProduct = finalVaultA.amount * finalVaultB.amount;
-        const productDifference =
-          finalProduct > initialProduct
-            ? finalProduct - initialProduct
-            : initialProduct - finalProduct;
-        const tolerance = initialProduct / BigInt(1000000); // 0.0001% tolerance
-
         assert.isTrue(
-          productDifference <= tolerance,
-          `Constant product should be preserved within tolerance. Initial: ${initialProduct}, Final: ${finalProduct}`
+          finalProduct >= initialProduct,
+          `Constant product should grow by the retained fee. Initial: ${initialProduct}, Final: ${finalProduct}`
         );
 
         console.log("Swap A->B successful");
@@ -1023,16 +1574,29 @@ describe("Automated Market Maker", () => {
         const swapAmount = new anchor.BN(2_000_000); // 2 tokens
         const minSlippage = new anchor.BN(100_000); // Minimum 0.1 tokens out
 
+        const initialPool = await program.account.ammPool.fetch(ammPoolPda);
+        const reserveB =
+          Number(initialVaultB.amount) - initialPool.protocolFeesB.toNumber();
+        const reserveA =
+          Number(initialVaultA.amount) - initialPool.protocolFeesA.toNumber();
+
+        const fee = Math.ceil(
+          (swapAmount.toNumber() *
+            FEE_NUMERATOR.add(PROTOCOL_FEE_NUMERATOR).toNumber()) /
+            FEE_DENOMINATOR.toNumber()
+        );
+        const swapAmountAfterFee = swapAmount.toNumber() - fee;
         const expectedOutput =
-          (Number(initialVaultA.amount) * swapAmount.toNumber()) /
-          (Number(initialVaultB.amount) + swapAmount.toNumber());
+          (reserveA * swapAmountAfterFee) / (reserveB + swapAmountAfterFee);
 
         await program.methods
-          .swap(swapAmount, minSlippage, false) // false = B to A
+          .swap(swapAmount, minSlippage, false, null) // false = B to A
           .accounts({
             user: liquidityProvider.publicKey,
             tokenAMint: tokenAMint.publicKey,
             tokenBMint: tokenBMint.publicKey,
+            tokenAProgram: TOKEN_PROGRAM_ID,
+            tokenBProgram: TOKEN_PROGRAM_ID,
           })
           .signers([liquidityProvider])
           .rpc();
@@ -1057,9 +1621,10 @@ describe("Automated Market Maker", () => {
           "User should spend exactly the swap amount of token B"
         );
 
-        assert.isTrue(
-          tokenAReceived > BigInt(0),
-          "User should receive some token A"
+        assert.equal(
+          tokenAReceived.toString(),
+          Math.floor(expectedOutput).toString(),
+          "User should receive the curve output for the amount after fee"
         );
 
         assert.equal(
@@ -1076,15 +1641,9 @@ describe("Automated Market Maker", () => {
 
         const initialProduct = initialVaultA.amount * initialVaultB.amount;
         const finalProduct = finalVaultA.amount * finalVaultB.amount;
-        const productDifference =
-          finalProduct > initialProduct
-            ? finalProduct - initialProduct
-            : initialProduct - finalProduct;
-        const tolerance = initialProduct / BigInt(1000000); // 0.0001%
-
         assert.isTrue(
-          productDifference <= tolerance,
-          `Constant product should be preserved within tolerance. Initial: ${initialProduct}, Final: ${finalProduct}`
+          finalProduct >= initialProduct,
+          `Constant product should grow by the retained fee. Initial: ${initialProduct}, Final: ${finalProduct}`
         );
 
         console.log("Swap B->A successful");
@@ -1106,14 +1665,141 @@ describe("Automated Market Maker", () => {
       }
     });
 
+    it("should swap for an exact amount of token B", async () => {
+      const initialTokenA = await getAccount(
+        provider.connection,
+        tokenAccountAAddress
+      );
+      const initialTokenB = await getAccount(
+        provider.connection,
+        tokenAccountBAddress
+      );
+
+      const amountOut = new anchor.BN(500_000);
+      const maxAmountIn = new anchor.BN(10_000_000);
+
+      await program.methods
+        .swapExactOut(amountOut, maxAmountIn, true, null)
+        .accounts({
+          user: liquidityProvider.publicKey,
+          tokenAMint: tokenAMint.publicKey,
+          tokenBMint: tokenBMint.publicKey,
+          tokenAProgram: TOKEN_PROGRAM_ID,
+          tokenBProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([liquidityProvider])
+        .rpc();
+
+      const finalTokenA = await getAccount(
+        provider.connection,
+        tokenAccountAAddress
+      );
+      const finalTokenB = await getAccount(
+        provider.connection,
+        tokenAccountBAddress
+      );
+
+      assert.equal(
+        (finalTokenB.amount - initialTokenB.amount).toString(),
+        amountOut.toString(),
+        "User should receive exactly the requested amount of token B"
+      );
+      assert.isTrue(
+        initialTokenA.amount - finalTokenA.amount <=
+          BigInt(maxAmountIn.toString()),
+        "User should not spend more than the maximum input"
+      );
+    });
+
+    it("should fail exact output swap when input cap is too low", async () => {
+      try {
+        await program.methods
+          .swapExactOut(
+            new anchor.BN(500_000),
+            new anchor.BN(1),
+            true,
+            null
+          )
+          .accounts({
+            user: liquidityProvider.publicKey,
+            tokenAMint: tokenAMint.publicKey,
+            tokenBMint: tokenBMint.publicKey,
+            tokenAProgram: TOKEN_PROGRAM_ID,
+            tokenBProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([liquidityProvider])
+          .rpc();
+
+        assert.fail("Should have failed with slippage exceeded");
+      } catch (error) {
+        assert.include(
+          error.toString(),
+          "SlippageExceeded",
+          "Should fail with SlippageExceeded error"
+        );
+      }
+    });
+
+    it("should emit a Swapped event with the post-trade reserves", async () => {
+      let listener: number;
+      const swapped = new Promise<any>((resolve) => {
+        listener = program.addEventListener("swapped", (event) =>
+          resolve(event)
+        );
+      });
+
+      await program.methods
+        .swap(new anchor.BN(1_000_000), new anchor.BN(1), false, null)
+        .accounts({
+          user: liquidityProvider.publicKey,
+          tokenAMint: tokenAMint.publicKey,
+          tokenBMint: tokenBMint.publicKey,
+          tokenAProgram: TOKEN_PROGRAM_ID,
+          tokenBProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([liquidityProvider])
+        .rpc();
+
+      const event = await swapped;
+      await program.removeEventListener(listener);
+
+      const pool = await program.account.ammPool.fetch(ammPoolPda);
+      const vaultAAccount = await getAccount(provider.connection, vaultA);
+      const vaultBAccount = await getAccount(provider.connection, vaultB);
+
+      assert.strictEqual(event.pool.toBase58(), ammPoolPda.toBase58());
+      assert.strictEqual(
+        event.user.toBase58(),
+        liquidityProvider.publicKey.toBase58()
+      );
+      assert.isFalse(event.isAToB);
+      assert.strictEqual(event.amountIn.toString(), "1000000");
+      assert.strictEqual(
+        event.reserveA.toString(),
+        (
+          vaultAAccount.amount - BigInt(pool.protocolFeesA.toString())
+        ).toString(),
+        "Event reserve A should match the vault net of protocol fees"
+      );
+      assert.strictEqual(
+        event.reserveB.toString(),
+        (
+          vaultBAccount.amount - BigInt(pool.protocolFeesB.toString())
+        ).toString(),
+        "Event reserve B should match the vault net of protocol fees"
+      );
+    });
+
     it("should fail with zero swap amount", async () => {
       try {
         await program.methods
-          .swap(new anchor.BN(0), new anchor.BN(1), true)
+          .swap(new anchor.BN(0), new anchor.BN(1), true, null)
           .accounts({
             user: liquidityProvider.publicKey,
             tokenAMint: tokenAMint.publicKey,
             tokenBMint: tokenBMint.publicKey,
+            tokenAProgram: TOKEN_PROGRAM_ID,
+            tokenBProgram: TOKEN_PROGRAM_ID,
           })
           .signers([liquidityProvider])
           .rpc();
@@ -1129,16 +1815,48 @@ describe("Automated Market Maker", () => {
       }
     });
 
+    it("should fail once the deadline has passed", async () => {
+      const expiredDeadline = new anchor.BN(Math.floor(Date.now() / 1000) - 60);
+      try {
+        await program.methods
+          .swap(
+            new anchor.BN(1_000_000),
+            new anchor.BN(1),
+            true,
+            expiredDeadline
+          )
+          .accounts({
+            user: liquidityProvider.publicKey,
+            tokenAMint: tokenAMint.publicKey,
+            tokenBMint: tokenBMint.publicKey,
+            tokenAProgram: TOKEN_PROGRAM_ID,
+            tokenBProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([liquidityProvider])
+          .rpc();
+
+        assert.fail("Should have failed with deadline exceeded");
+      } catch (error) {
+        assert.include(
+          error.toString(),
+          "DeadlineExceeded",
+          "Should fail with DeadlineExceeded error"
+        );
+      }
+    });
+
     it("should fail with excessive slippage", async () => {
       try {
         const swapAmount = new anchor.BN(1_000_000);
         const unrealisticMinSlippage = new anchor.BN(1_000_000_000);
         await program.methods
-          .swap(swapAmount, unrealisticMinSlippage, true)
+          .swap(swapAmount, unrealisticMinSlippage, true, null)
           .accounts({
             user: liquidityProvider.publicKey,
             tokenAMint: tokenAMint.publicKey,
             tokenBMint: tokenBMint.publicKey,
+            tokenAProgram: TOKEN_PROGRAM_ID,
+            tokenBProgram: TOKEN_PROGRAM_ID,
           })
           .signers([liquidityProvider])
           .rpc();
@@ -1161,4 +1879,1039 @@ describe("Automated Market Maker", () => {
       }
     });
   });
+
+  describe("Collect Protocol Fees", () => {
+    let feeReceiverA: anchor.web3.PublicKey;
+    let feeReceiverB: anchor.web3.PublicKey;
+
+    before(async () => {
+      feeReceiverA = await createAssociatedTokenAccount(
+        provider.connection,
+        liquidityProvider,
+        tokenAMint.publicKey,
+        initializer
+      );
+      feeReceiverB = await createAssociatedTokenAccount(
+        provider.connection,
+        liquidityProvider,
+        tokenBMint.publicKey,
+        initializer
+      );
+    });
+
+    it("should fail when signer is not the fee authority", async () => {
+      try {
+        await program.methods
+          .collectProtocolFees()
+          .accounts({
+            feeAuthority: liquidityProvider.publicKey,
+            tokenAMint: tokenAMint.publicKey,
+            tokenBMint: tokenBMint.publicKey,
+            tokenAProgram: TOKEN_PROGRAM_ID,
+            tokenBProgram: TOKEN_PROGRAM_ID,
+            feeReceiverA: feeReceiverA,
+            feeReceiverB: feeReceiverB,
+          })
+          .signers([liquidityProvider])
+          .rpc();
+
+        assert.fail("Should have failed with unauthorized error");
+      } catch (error) {
+        assert.include(
+          error.toString(),
+          "Unauthorized",
+          "Should fail with Unauthorized error"
+        );
+      }
+    });
+
+    it("should transfer accrued protocol fees to the fee authority", async () => {
+      const poolBefore = await program.account.ammPool.fetch(ammPoolPda);
+      const vaultABefore = await getAccount(provider.connection, vaultA);
+      const vaultBBefore = await getAccount(provider.connection, vaultB);
+
+      await program.methods
+        .collectProtocolFees()
+        .accounts({
+          feeAuthority: initializer,
+          tokenAMint: tokenAMint.publicKey,
+          tokenBMint: tokenBMint.publicKey,
+          tokenAProgram: TOKEN_PROGRAM_ID,
+          tokenBProgram: TOKEN_PROGRAM_ID,
+          feeReceiverA: feeReceiverA,
+          feeReceiverB: feeReceiverB,
+        })
+        .rpc();
+
+      const poolAfter = await program.account.ammPool.fetch(ammPoolPda);
+      const vaultAAfter = await getAccount(provider.connection, vaultA);
+      const vaultBAfter = await getAccount(provider.connection, vaultB);
+      const receiverA = await getAccount(provider.connection, feeReceiverA);
+      const receiverB = await getAccount(provider.connection, feeReceiverB);
+
+      assert.strictEqual(
+        receiverA.amount.toString(),
+        poolBefore.protocolFeesA.toString(),
+        "Receiver A should get the accrued protocol fees"
+      );
+      assert.strictEqual(
+        receiverB.amount.toString(),
+        poolBefore.protocolFeesB.toString(),
+        "Receiver B should get the accrued protocol fees"
+      );
+      assert.strictEqual(
+        (vaultABefore.amount - vaultAAfter.amount).toString(),
+        poolBefore.protocolFeesA.toString(),
+        "Vault A should only lose the protocol fees"
+      );
+      assert.strictEqual(
+        (vaultBBefore.amount - vaultBAfter.amount).toString(),
+        poolBefore.protocolFeesB.toString(),
+        "Vault B should only lose the protocol fees"
+      );
+      assert.strictEqual(poolAfter.protocolFeesA.toString(), "0");
+      assert.strictEqual(poolAfter.protocolFeesB.toString(), "0");
+    });
+  });
+
+  describe("Pool Admin", () => {
+    const poolAccounts = () => ({
+      tokenAMint: tokenAMint.publicKey,
+      tokenBMint: tokenBMint.publicKey,
+    });
+
+    it("should fail to pause when signer is not the admin", async () => {
+      try {
+        await program.methods
+          .pausePool()
+          .accounts({ admin: liquidityProvider.publicKey, ...poolAccounts() })
+          .signers([liquidityProvider])
+          .rpc();
+
+        assert.fail("Should have failed with unauthorized error");
+      } catch (error) {
+        assert.include(
+          error.toString(),
+          "Unauthorized",
+          "Should fail with Unauthorized error"
+        );
+      }
+    });
+
+    it("should block swaps and deposits while paused", async () => {
+      await program.methods
+        .pausePool()
+        .accounts({ admin: initializer, ...poolAccounts() })
+        .rpc();
+
+      const pool = await program.account.ammPool.fetch(ammPoolPda);
+      assert.isTrue(pool.paused, "Pool should be paused");
+
+      try {
+        await program.methods
+          .swap(new anchor.BN(1_000_000), new anchor.BN(1), true, null)
+          .accounts({
+            user: liquidityProvider.publicKey,
+            ...poolAccounts(),
+            tokenAProgram: TOKEN_PROGRAM_ID,
+            tokenBProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([liquidityProvider])
+          .rpc();
+
+        assert.fail("Should have failed with pool paused");
+      } catch (error) {
+        assert.include(
+          error.toString(),
+          "PoolPaused",
+          "Swap should fail with PoolPaused error"
+        );
+      }
+
+      try {
+        await program.methods
+          .addLiquidity(
+            new anchor.BN(1_000_000),
+            new anchor.BN(1_000_000),
+            new anchor.BN(0),
+            null
+          )
+          .accounts({
+            liquidityProvider: liquidityProvider.publicKey,
+            ...poolAccounts(),
+            tokenAProgram: TOKEN_PROGRAM_ID,
+            tokenBProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([liquidityProvider])
+          .rpc();
+
+        assert.fail("Should have failed with pool paused");
+      } catch (error) {
+        assert.include(
+          error.toString(),
+          "PoolPaused",
+          "Deposit should fail with PoolPaused error"
+        );
+      }
+    });
+
+    it("should still allow withdrawals while paused", async () => {
+      const lpTokenAccountAddress = await getAssociatedTokenAddress(
+        lpMint,
+        liquidityProvider.publicKey
+      );
+      const lpBefore = await getAccount(
+        provider.connection,
+        lpTokenAccountAddress
+      );
+      const withdrawAmount = BigInt(100_000);
+
+      await program.methods
+        .withdrawLiquidity(
+          new anchor.BN(withdrawAmount.toString()),
+          new anchor.BN(0),
+          new anchor.BN(0)
+        )
+        .accounts({
+          liquidityProvider: liquidityProvider.publicKey,
+          ...poolAccounts(),
+          tokenAProgram: TOKEN_PROGRAM_ID,
+          tokenBProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([liquidityProvider])
+        .rpc();
+
+      const lpAfter = await getAccount(
+        provider.connection,
+        lpTokenAccountAddress
+      );
+      assert.strictEqual(
+        (lpBefore.amount - lpAfter.amount).toString(),
+        withdrawAmount.toString(),
+        "LP tokens should be burned while the pool is paused"
+      );
+    });
+
+    it("should hand over the admin role and unpause", async () => {
+      await program.methods
+        .setAdmin(liquidityProvider.publicKey)
+        .accounts({ admin: initializer, ...poolAccounts() })
+        .rpc();
+
+      let pool = await program.account.ammPool.fetch(ammPoolPda);
+      assert.strictEqual(
+        pool.admin.toBase58(),
+        liquidityProvider.publicKey.toBase58(),
+        "Admin should be handed over"
+      );
+
+      await program.methods
+        .unpausePool()
+        .accounts({ admin: liquidityProvider.publicKey, ...poolAccounts() })
+        .signers([liquidityProvider])
+        .rpc();
+
+      pool = await program.account.ammPool.fetch(ammPoolPda);
+      assert.isFalse(pool.paused, "Pool should be unpaused");
+    });
+  });
+
+  describe("Price Oracle", () => {
+    const observe = () =>
+      program.methods
+        .observe()
+        .accounts({
+          tokenAMint: tokenAMint.publicKey,
+          tokenBMint: tokenBMint.publicKey,
+        })
+        .view();
+
+    it("should accumulate prices over time", async () => {
+      const first = await observe();
+      const pool = await program.account.ammPool.fetch(ammPoolPda);
+      assert.isTrue(
+        first.timestamp.gte(pool.lastUpdateTs),
+        "Observation should be taken at or after the last pool update"
+      );
+
+      // Let at least one second pass so the accumulators have to move
+      await new Promise((resolve) => setTimeout(resolve, 2_000));
+
+      await program.methods
+        .swap(new anchor.BN(1_000_000), new anchor.BN(1), true, null)
+        .accounts({
+          user: liquidityProvider.publicKey,
+          tokenAMint: tokenAMint.publicKey,
+          tokenBMint: tokenBMint.publicKey,
+          tokenAProgram: TOKEN_PROGRAM_ID,
+          tokenBProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([liquidityProvider])
+        .rpc();
+
+      const second = await observe();
+      assert.isTrue(
+        second.timestamp.gt(first.timestamp),
+        "Second observation should be later"
+      );
+      assert.isTrue(
+        second.priceACumulative.gt(first.priceACumulative),
+        "Cumulative price of A should grow"
+      );
+      assert.isTrue(
+        second.priceBCumulative.gt(first.priceBCumulative),
+        "Cumulative price of B should grow"
+      );
+    });
+  });
+
+  describe("Swap Route", () => {
+    // Token C has to sort after token B to be the B side of the B/C pool
+    let tokenCMint = anchor.web3.Keypair.generate();
+    while (
+      tokenCMint.publicKey
+        .toBuffer()
+        .compare(tokenBMint.publicKey.toBuffer()) <= 0
+    ) {
+      tokenCMint = anchor.web3.Keypair.generate();
+    }
+    let tokenAccountAAddress: anchor.web3.PublicKey;
+    let tokenAccountCAddress: anchor.web3.PublicKey;
+
+    const pda = (seeds: Buffer[]) =>
+      anchor.web3.PublicKey.findProgramAddressSync(seeds, program.programId)[0];
+
+    // Accounts of one hop, in the order swap_route expects them
+    const hopAccounts = (
+      mintA: anchor.web3.PublicKey,
+      mintB: anchor.web3.PublicKey
+    ) => {
+      const pair = [mintA.toBuffer(), mintB.toBuffer()];
+      return [
+        pda([Buffer.from("pool"), ...pair]),
+        pda([Buffer.from("authority"), ...pair]),
+        pda([Buffer.from("vault_token"), ...pair, Buffer.from("A")]),
+        pda([Buffer.from("vault_token"), ...pair, Buffer.from("B")]),
+        mintA,
+        mintB,
+        TOKEN_PROGRAM_ID,
+        TOKEN_PROGRAM_ID,
+      ].map((pubkey, index) => ({
+        pubkey,
+        isSigner: false,
+        isWritable: index == 0 || index == 2 || index == 3,
+      }));
+    };
+
+    const routeAccounts = () => [
+      ...hopAccounts(tokenAMint.publicKey, tokenBMint.publicKey),
+      ...hopAccounts(tokenBMint.publicKey, tokenCMint.publicKey),
+    ];
+
+    before(async () => {
+      const mintRent =
+        await provider.connection.getMinimumBalanceForRentExemption(MINT_SIZE);
+      const createMintIx = new anchor.web3.Transaction().add(
+        anchor.web3.SystemProgram.createAccount({
+          fromPubkey: initializer,
+          newAccountPubkey: tokenCMint.publicKey,
+          lamports: mintRent,
+          space: MINT_SIZE,
+          programId: TOKEN_PROGRAM_ID,
+        }),
+        createInitializeMintInstruction(
+          tokenCMint.publicKey,
+          6,
+          tokensAuthority.publicKey,
+          null
+        )
+      );
+      await provider.sendAndConfirm(createMintIx, [tokenCMint]);
+
+      await program.methods
+        .initializePool(
+          FEE_NUMERATOR,
+          FEE_DENOMINATOR,
+          PROTOCOL_FEE_NUMERATOR,
+          initializer,
+          CONSTANT_PRODUCT
+        )
+        .accounts({
+          initializer: initializer,
+          tokenAMint: tokenBMint.publicKey,
+          tokenBMint: tokenCMint.publicKey,
+          tokenAProgram: TOKEN_PROGRAM_ID,
+          tokenBProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+      tokenAccountAAddress = await getAssociatedTokenAddress(
+        tokenAMint.publicKey,
+        liquidityProvider.publicKey
+      );
+      const tokenAccountBAddress = await getAssociatedTokenAddress(
+        tokenBMint.publicKey,
+        liquidityProvider.publicKey
+      );
+      tokenAccountCAddress = await createAssociatedTokenAccount(
+        provider.connection,
+        liquidityProvider,
+        tokenCMint.publicKey,
+        liquidityProvider.publicKey
+      );
+      await createAssociatedTokenAccount(
+        provider.connection,
+        liquidityProvider,
+        pda([
+          Buffer.from("lp_mint"),
+          tokenBMint.publicKey.toBuffer(),
+          tokenCMint.publicKey.toBuffer(),
+        ]),
+        liquidityProvider.publicKey
+      );
+
+      const quantity = 50 * 1_000_000;
+      const mintIx = new anchor.web3.Transaction().add(
+        createMintToInstruction(
+          tokenBMint.publicKey,
+          tokenAccountBAddress,
+          tokensAuthority.publicKey,
+          quantity
+        ),
+        createMintToInstruction(
+          tokenCMint.publicKey,
+          tokenAccountCAddress,
+          tokensAuthority.publicKey,
+          quantity
+        )
+      );
+      await provider.sendAndConfirm(mintIx, [tokensAuthority]);
+
+      await program.methods
+        .addLiquidity(
+          new anchor.BN(quantity),
+          new anchor.BN(quantity),
+          new anchor.BN(0),
+          null
+        )
+        .accounts({
+          liquidityProvider: liquidityProvider.publicKey,
+          tokenAMint: tokenBMint.publicKey,
+          tokenBMint: tokenCMint.publicKey,
+          tokenAProgram: TOKEN_PROGRAM_ID,
+          tokenBProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([liquidityProvider])
+        .rpc();
+    });
+
+    it("should route token A to token C through token B", async () => {
+      const initialTokenA = await getAccount(
+        provider.connection,
+        tokenAccountAAddress
+      );
+      const initialTokenC = await getAccount(
+        provider.connection,
+        tokenAccountCAddress
+      );
+      const amountIn = new anchor.BN(1_000_000);
+
+      await program.methods
+        .swapRoute(amountIn, new anchor.BN(1), null)
+        .accounts({
+          user: liquidityProvider.publicKey,
+          userTokenIn: tokenAccountAAddress,
+          userTokenOut: tokenAccountCAddress,
+        })
+        .remainingAccounts(routeAccounts())
+        .signers([liquidityProvider])
+        .rpc();
+
+      const finalTokenA = await getAccount(
+        provider.connection,
+        tokenAccountAAddress
+      );
+      const finalTokenC = await getAccount(
+        provider.connection,
+        tokenAccountCAddress
+      );
+
+      assert.strictEqual(
+        (initialTokenA.amount - finalTokenA.amount).toString(),
+        amountIn.toString(),
+        "User should spend exactly the input amount of token A"
+      );
+      assert.isTrue(
+        finalTokenC.amount > initialTokenC.amount,
+        "User should receive token C"
+      );
+    });
+
+    it("should enforce the overall minimum output", async () => {
+      try {
+        await program.methods
+          .swapRoute(
+            new anchor.BN(1_000_000),
+            new anchor.BN(1_000_000_000),
+            null
+          )
+          .accounts({
+            user: liquidityProvider.publicKey,
+            userTokenIn: tokenAccountAAddress,
+            userTokenOut: tokenAccountCAddress,
+          })
+          .remainingAccounts(routeAccounts())
+          .signers([liquidityProvider])
+          .rpc();
+
+        assert.fail("Should have failed with slippage exceeded");
+      } catch (error) {
+        assert.include(
+          error.toString(),
+          "SlippageExceeded",
+          "Should fail with SlippageExceeded error"
+        );
+      }
+    });
+
+    it("should fail when a hop does not continue the route", async () => {
+      try {
+        await program.methods
+          .swapRoute(new anchor.BN(1_000_000), new anchor.BN(1), null)
+          .accounts({
+            user: liquidityProvider.publicKey,
+            userTokenIn: tokenAccountAAddress,
+            userTokenOut: tokenAccountCAddress,
+          })
+          .remainingAccounts([
+            ...hopAccounts(tokenBMint.publicKey, tokenCMint.publicKey),
+            ...hopAccounts(tokenAMint.publicKey, tokenBMint.publicKey),
+          ])
+          .signers([liquidityProvider])
+          .rpc();
+
+        assert.fail("Should have failed with invalid route");
+      } catch (error) {
+        assert.include(
+          error.toString(),
+          "InvalidRoute",
+          "Should fail with InvalidRoute error"
+        );
+      }
+    });
+  });
+
+  describe("Flash Loans", () => {
+    const flashLoanAccounts = () => ({
+      borrower: liquidityProvider.publicKey,
+      tokenAMint: tokenAMint.publicKey,
+      tokenBMint: tokenBMint.publicKey,
+      tokenAProgram: TOKEN_PROGRAM_ID,
+      tokenBProgram: TOKEN_PROGRAM_ID,
+    });
+
+    it("should lend and take back the loan plus the pool fee", async () => {
+      const amount = new anchor.BN(1_000_000);
+      const vaultABefore = await getAccount(provider.connection, vaultA);
+
+      const borrowIx = await program.methods
+        .flashBorrow(amount, true)
+        .accounts(flashLoanAccounts())
+        .instruction();
+      const repayIx = await program.methods
+        .flashRepay()
+        .accounts(flashLoanAccounts())
+        .instruction();
+
+      await provider.sendAndConfirm(
+        new anchor.web3.Transaction().add(borrowIx, repayIx),
+        [liquidityProvider]
+      );
+
+      const vaultAAfter = await getAccount(provider.connection, vaultA);
+      // ceil(1_000_000 * 30 / 10_000), the protocol takes no cut of flash loan fees
+      const expectedFee = BigInt(3_000);
+      assert.strictEqual(
+        (vaultAAfter.amount - vaultABefore.amount).toString(),
+        expectedFee.toString(),
+        "Vault A should keep the flash loan fee"
+      );
+
+      const pool = await program.account.ammPool.fetch(ammPoolPda);
+      assert.strictEqual(
+        pool.flashLoanAmount.toString(),
+        "0",
+        "Loan should be cleared"
+      );
+    });
+
+    it("should fail to borrow without a repay instruction", async () => {
+      try {
+        await program.methods
+          .flashBorrow(new anchor.BN(1_000_000), true)
+          .accounts(flashLoanAccounts())
+          .signers([liquidityProvider])
+          .rpc();
+
+        assert.fail("Should have failed with flash loan not repaid");
+      } catch (error) {
+        assert.include(
+          error.toString(),
+          "FlashLoanNotRepaid",
+          "Should fail with FlashLoanNotRepaid error"
+        );
+      }
+    });
+
+    it("should block swaps while a loan is outstanding", async () => {
+      const borrowIx = await program.methods
+        .flashBorrow(new anchor.BN(1_000_000), true)
+        .accounts(flashLoanAccounts())
+        .instruction();
+      const swapIx = await program.methods
+        .swap(new anchor.BN(1_000_000), new anchor.BN(1), true, null)
+        .accounts({
+          user: liquidityProvider.publicKey,
+          tokenAMint: tokenAMint.publicKey,
+          tokenBMint: tokenBMint.publicKey,
+          tokenAProgram: TOKEN_PROGRAM_ID,
+          tokenBProgram: TOKEN_PROGRAM_ID,
+        })
+        .instruction();
+      const repayIx = await program.methods
+        .flashRepay()
+        .accounts(flashLoanAccounts())
+        .instruction();
+
+      try {
+        await provider.sendAndConfirm(
+          new anchor.web3.Transaction().add(borrowIx, swapIx, repayIx),
+          [liquidityProvider]
+        );
+
+        assert.fail("Should have failed with flash loan in progress");
+      } catch (error) {
+        assert.include(
+          error.toString(),
+          "FlashLoanInProgress",
+          "Should fail with FlashLoanInProgress error"
+        );
+      }
+    });
+  });
+
+  describe("StableSwap", () => {
+    const [stableAMint, stableBMint] = sortedMintKeypairs();
+    const AMP = new anchor.BN(100);
+    const RESERVE = 100 * 1_000_000;
+    let stableAccountA: anchor.web3.PublicKey;
+    let stableAccountB: anchor.web3.PublicKey;
+
+    const stableAccounts = () => ({
+      tokenAMint: stableAMint.publicKey,
+      tokenBMint: stableBMint.publicKey,
+      tokenAProgram: TOKEN_PROGRAM_ID,
+      tokenBProgram: TOKEN_PROGRAM_ID,
+    });
+
+    before(async () => {
+      const mintRent =
+        await provider.connection.getMinimumBalanceForRentExemption(MINT_SIZE);
+      const createMintsIx = new anchor.web3.Transaction();
+      for (const mint of [stableAMint, stableBMint]) {
+        createMintsIx.add(
+          anchor.web3.SystemProgram.createAccount({
+            fromPubkey: initializer,
+            newAccountPubkey: mint.publicKey,
+            lamports: mintRent,
+            space: MINT_SIZE,
+            programId: TOKEN_PROGRAM_ID,
+          }),
+          createInitializeMintInstruction(
+            mint.publicKey,
+            6,
+            tokensAuthority.publicKey,
+            null
+          )
+        );
+      }
+      await provider.sendAndConfirm(createMintsIx, [
+        stableAMint,
+        stableBMint,
+      ]);
+
+      stableAccountA = await createAssociatedTokenAccount(
+        provider.connection,
+        liquidityProvider,
+        stableAMint.publicKey,
+        liquidityProvider.publicKey
+      );
+      stableAccountB = await createAssociatedTokenAccount(
+        provider.connection,
+        liquidityProvider,
+        stableBMint.publicKey,
+        liquidityProvider.publicKey
+      );
+      const mintIx = new anchor.web3.Transaction().add(
+        createMintToInstruction(
+          stableAMint.publicKey,
+          stableAccountA,
+          tokensAuthority.publicKey,
+          2 * RESERVE
+        ),
+        createMintToInstruction(
+          stableBMint.publicKey,
+          stableAccountB,
+          tokensAuthority.publicKey,
+          2 * RESERVE
+        )
+      );
+      await provider.sendAndConfirm(mintIx, [tokensAuthority]);
+    });
+
+    it("should reject a StableSwap pool without amplification", async () => {
+      try {
+        await program.methods
+          .initializePool(
+            FEE_NUMERATOR,
+            FEE_DENOMINATOR,
+            PROTOCOL_FEE_NUMERATOR,
+            initializer,
+            {
+              curveType: { stableSwap: {} },
+              amp: new anchor.BN(0),
+              weightA: new anchor.BN(0),
+            }
+          )
+          .accounts({ initializer: initializer, ...stableAccounts() })
+          .rpc();
+
+        assert.fail("Should have failed with invalid amplification");
+      } catch (error) {
+        assert.include(
+          error.toString(),
+          "InvalidAmplification",
+          "Should fail with InvalidAmplification error"
+        );
+      }
+    });
+
+    it("should swap near parity on a balanced pool", async () => {
+      await program.methods
+        .initializePool(
+          FEE_NUMERATOR,
+          FEE_DENOMINATOR,
+          PROTOCOL_FEE_NUMERATOR,
+          initializer,
+          {
+            curveType: { stableSwap: {} },
+            amp: AMP,
+            weightA: new anchor.BN(0),
+          }
+        )
+        .accounts({ initializer: initializer, ...stableAccounts() })
+        .rpc();
+
+      const [stablePoolPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("pool"),
+          stableAMint.publicKey.toBuffer(),
+          stableBMint.publicKey.toBuffer(),
+        ],
+        program.programId
+      );
+      const [stableLpMint] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("lp_mint"),
+          stableAMint.publicKey.toBuffer(),
+          stableBMint.publicKey.toBuffer(),
+        ],
+        program.programId
+      );
+      await createAssociatedTokenAccount(
+        provider.connection,
+        liquidityProvider,
+        stableLpMint,
+        liquidityProvider.publicKey
+      );
+
+      await program.methods
+        .addLiquidity(
+          new anchor.BN(RESERVE),
+          new anchor.BN(RESERVE),
+          new anchor.BN(0),
+          null
+        )
+        .accounts({
+          liquidityProvider: liquidityProvider.publicKey,
+          ...stableAccounts(),
+        })
+        .signers([liquidityProvider])
+        .rpc();
+
+      const pool = await program.account.ammPool.fetch(stablePoolPda);
+      assert.deepEqual(pool.curveType, { stableSwap: {} });
+      assert.strictEqual(pool.amp.toString(), AMP.toString());
+
+      const amountIn = 1_000_000;
+      const initialTokenB = await getAccount(
+        provider.connection,
+        stableAccountB
+      );
+      await program.methods
+        .swap(new anchor.BN(amountIn), new anchor.BN(1), true, null)
+        .accounts({ user: liquidityProvider.publicKey, ...stableAccounts() })
+        .signers([liquidityProvider])
+        .rpc();
+      const finalTokenB = await getAccount(provider.connection, stableAccountB);
+
+      // Constant product would pay out about 986_000 after fees here
+      const received = Number(finalTokenB.amount - initialTokenB.amount);
+      const afterFee = amountIn * (1 - 35 / 10_000);
+      const constantProductOut = (RESERVE * afterFee) / (RESERVE + afterFee);
+      assert.isTrue(
+        received > constantProductOut,
+        "StableSwap should beat the constant product price near parity"
+      );
+      assert.isTrue(received < afterFee, "Output should stay below the input");
+    });
+  });
+
+  describe("Dynamic Fees", () => {
+    // The admin role was handed to the liquidity provider in "Pool Admin"
+    const adminAccounts = () => ({
+      admin: liquidityProvider.publicKey,
+      tokenAMint: tokenAMint.publicKey,
+      tokenBMint: tokenBMint.publicKey,
+    });
+
+    it("should reject a ceiling below the base fee", async () => {
+      try {
+        await program.methods
+          .setDynamicFee(FEE_NUMERATOR.subn(1))
+          .accounts(adminAccounts())
+          .signers([liquidityProvider])
+          .rpc();
+
+        assert.fail("Should have failed with invalid fee");
+      } catch (error) {
+        assert.include(
+          error.toString(),
+          "InvalidFee",
+          "Should fail with InvalidFee error"
+        );
+      }
+    });
+
+    it("should build up volatility from swaps", async () => {
+      const maxFeeNumerator = new anchor.BN(100);
+      await program.methods
+        .setDynamicFee(maxFeeNumerator)
+        .accounts(adminAccounts())
+        .signers([liquidityProvider])
+        .rpc();
+
+      await program.methods
+        .swap(new anchor.BN(5_000_000), new anchor.BN(1), true, null)
+        .accounts({
+          user: liquidityProvider.publicKey,
+          tokenAMint: tokenAMint.publicKey,
+          tokenBMint: tokenBMint.publicKey,
+          tokenAProgram: TOKEN_PROGRAM_ID,
+          tokenBProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([liquidityProvider])
+        .rpc();
+
+      let pool = await program.account.ammPool.fetch(ammPoolPda);
+      assert.strictEqual(
+        pool.maxFeeNumerator.toString(),
+        maxFeeNumerator.toString()
+      );
+      assert.isTrue(
+        pool.volatilityBps.gtn(0),
+        "Swap should add its price move to the volatility"
+      );
+
+      await program.methods
+        .setDynamicFee(new anchor.BN(0))
+        .accounts(adminAccounts())
+        .signers([liquidityProvider])
+        .rpc();
+
+      pool = await program.account.ammPool.fetch(ammPoolPda);
+      assert.strictEqual(
+        pool.maxFeeNumerator.toString(),
+        "0",
+        "Zero should switch back to the static fee"
+      );
+    });
+  });
+
+  describe("Weighted Pools", () => {
+    const [weightedAMint, weightedBMint] = sortedMintKeypairs();
+    const WAD = new anchor.BN("1000000000000000000");
+    // 80% token A, 20% token B
+    const WEIGHT_A = WAD.muln(8).divn(10);
+    let weightedAccountA: anchor.web3.PublicKey;
+    let weightedAccountB: anchor.web3.PublicKey;
+
+    const weightedAccounts = () => ({
+      tokenAMint: weightedAMint.publicKey,
+      tokenBMint: weightedBMint.publicKey,
+      tokenAProgram: TOKEN_PROGRAM_ID,
+      tokenBProgram: TOKEN_PROGRAM_ID,
+    });
+
+    const weightedCurve = (weightA: anchor.BN) => ({
+      curveType: { weighted: {} },
+      amp: new anchor.BN(0),
+      weightA,
+    });
+
+    before(async () => {
+      const mintRent =
+        await provider.connection.getMinimumBalanceForRentExemption(MINT_SIZE);
+      const createMintsIx = new anchor.web3.Transaction();
+      for (const mint of [weightedAMint, weightedBMint]) {
+        createMintsIx.add(
+          anchor.web3.SystemProgram.createAccount({
+            fromPubkey: initializer,
+            newAccountPubkey: mint.publicKey,
+            lamports: mintRent,
+            space: MINT_SIZE,
+            programId: TOKEN_PROGRAM_ID,
+          }),
+          createInitializeMintInstruction(
+            mint.publicKey,
+            6,
+            tokensAuthority.publicKey,
+            null
+          )
+        );
+      }
+      await provider.sendAndConfirm(createMintsIx, [
+        weightedAMint,
+        weightedBMint,
+      ]);
+
+      weightedAccountA = await createAssociatedTokenAccount(
+        provider.connection,
+        liquidityProvider,
+        weightedAMint.publicKey,
+        liquidityProvider.publicKey
+      );
+      weightedAccountB = await createAssociatedTokenAccount(
+        provider.connection,
+        liquidityProvider,
+        weightedBMint.publicKey,
+        liquidityProvider.publicKey
+      );
+      const mintIx = new anchor.web3.Transaction().add(
+        createMintToInstruction(
+          weightedAMint.publicKey,
+          weightedAccountA,
+          tokensAuthority.publicKey,
+          100 * 1_000_000
+        ),
+        createMintToInstruction(
+          weightedBMint.publicKey,
+          weightedAccountB,
+          tokensAuthority.publicKey,
+          100 * 1_000_000
+        )
+      );
+      await provider.sendAndConfirm(mintIx, [tokensAuthority]);
+    });
+
+    it("should reject a weight below the minimum", async () => {
+      try {
+        await program.methods
+          .initializePool(
+            FEE_NUMERATOR,
+            FEE_DENOMINATOR,
+            PROTOCOL_FEE_NUMERATOR,
+            initializer,
+            weightedCurve(WAD.divn(1_000))
+          )
+          .accounts({ initializer: initializer, ...weightedAccounts() })
+          .rpc();
+
+        assert.fail("Should have failed with invalid weights");
+      } catch (error) {
+        assert.include(
+          error.toString(),
+          "InvalidWeights",
+          "Should fail with InvalidWeights error"
+        );
+      }
+    });
+
+    it("should price swaps by the weighted reserves", async () => {
+      await program.methods
+        .initializePool(
+          FEE_NUMERATOR,
+          FEE_DENOMINATOR,
+          PROTOCOL_FEE_NUMERATOR,
+          initializer,
+          weightedCurve(WEIGHT_A)
+        )
+        .accounts({ initializer: initializer, ...weightedAccounts() })
+        .rpc();
+
+      const [weightedLpMint] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("lp_mint"),
+          weightedAMint.publicKey.toBuffer(),
+          weightedBMint.publicKey.toBuffer(),
+        ],
+        program.programId
+      );
+      await createAssociatedTokenAccount(
+        provider.connection,
+        liquidityProvider,
+        weightedLpMint,
+        liquidityProvider.publicKey
+      );
+
+      // 80 A against 20 B in an 80/20 pool puts the spot price at one
+      await program.methods
+        .addLiquidity(
+          new anchor.BN(80 * 1_000_000),
+          new anchor.BN(20 * 1_000_000),
+          new anchor.BN(0),
+          null
+        )
+        .accounts({
+          liquidityProvider: liquidityProvider.publicKey,
+          ...weightedAccounts(),
+        })
+        .signers([liquidityProvider])
+        .rpc();
+
+      const initialTokenB = await getAccount(
+        provider.connection,
+        weightedAccountB
+      );
+      await program.methods
+        .swap(new anchor.BN(1_000_000), new anchor.BN(1), true, null)
+        .accounts({
+          user: liquidityProvider.publicKey,
+          ...weightedAccounts(),
+        })
+        .signers([liquidityProvider])
+        .rpc();
+      const finalTokenB = await getAccount(
+        provider.connection,
+        weightedAccountB
+      );
+
+      // A 50/50 pool with the same reserves would pay out less than 250_000
+      const received = Number(finalTokenB.amount - initialTokenB.amount);
+      assert.isTrue(
+        received > 950_000 && received < 1_000_000,
+        "Swap should execute close to the weighted spot price"
+      );
+    });
+  });
 });
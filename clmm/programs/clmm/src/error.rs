@@ -42,4 +42,26 @@ pub enum CLMMError {
     TickLowerOverflow,
     #[msg("sqrt_price_x64 out of range")]
     SqrtPriceX64,
+    #[msg("Transaction deadline has passed")]
+    DeadlineExceeded,
+    #[msg("Signer does not control this position")]
+    InvalidPositionOwner,
+    #[msg("Position doesn't hold enough liquidity")]
+    InsufficientPositionLiquidity,
+    #[msg("Position mint and position token account must be passed together")]
+    MissingPositionNftAccounts,
+    #[msg("Fee rate is above the maximum allowed")]
+    InvalidFeeRate,
+    #[msg("Signer is not allowed to perform this action")]
+    Unauthorized,
+    #[msg("Fee rate and tick spacing are not an allowed fee tier")]
+    InvalidFeeTier,
+    #[msg("A fee tier with this fee rate already exists")]
+    DuplicateFeeTier,
+    #[msg("Tick spacing must be greater than zero")]
+    InvalidTickSpacing,
+    #[msg("Tick array start index must be a multiple of the ticks covered by one array")]
+    InvalidTickArrayStart,
+    #[msg("Tick array doesn't belong to this pool or doesn't cover the expected ticks")]
+    InvalidTickArray,
 }
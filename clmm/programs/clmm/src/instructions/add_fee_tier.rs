@@ -0,0 +1,45 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::CLMMError,
+    state::{FeeTier, FeeTierConfig},
+    utils::MAX_FEE_RATE,
+};
+
+pub fn add_fee_tier(ctx: Context<AddFeeTier>, fee_rate: u32, tick_spacing: u16) -> Result<()> {
+    let config = &mut ctx.accounts.fee_tier_config;
+    config.check_admin(&ctx.accounts.admin.key())?;
+    require!(fee_rate <= MAX_FEE_RATE, CLMMError::InvalidFeeRate);
+    require!(tick_spacing > 0, CLMMError::InvalidTickSpacing);
+    require!(
+        !config
+            .fee_tiers
+            .iter()
+            .any(|tier| tier.fee_rate == fee_rate),
+        CLMMError::DuplicateFeeTier
+    );
+
+    config.fee_tiers.push(FeeTier {
+        fee_rate,
+        tick_spacing,
+    });
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AddFeeTier<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"fee_tier_config"],
+        bump = fee_tier_config.bump,
+        realloc = 8 + 32 + 4 + (4 + 2) * (fee_tier_config.fee_tiers.len() + 1) + 1,
+        realloc::payer = admin,
+        realloc::zero = false
+    )]
+    pub fee_tier_config: Account<'info, FeeTierConfig>,
+
+    pub system_program: Program<'info, System>,
+}
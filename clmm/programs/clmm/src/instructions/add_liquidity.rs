@@ -1,65 +1,67 @@
-use crate::state::Tick;
+use crate::state::{Position, TickArray};
 use crate::{error::CLMMError, state::Pool, utils::*};
 use anchor_lang::prelude::*;
-use anchor_spl::associated_token::AssociatedToken;
-use anchor_spl::token::{mint_to, transfer, Mint, MintTo, Token, TokenAccount, Transfer};
+use anchor_spl::token::{transfer, Mint, Token, TokenAccount, Transfer};
 
 pub fn add_liquidity(
     ctx: Context<AddLiquidity>,
-    tick_lower: i32,
-    tick_upper: i32,
     liquidity: u128,
+    deadline: Option<i64>,
 ) -> Result<()> {
-    require!(tick_lower < tick_upper, CLMMError::TickMismatch);
+    check_deadline(deadline)?;
+    require!(liquidity > 0, CLMMError::ZeroAmount);
+    ctx.accounts.position.check_authority(
+        &ctx.accounts.liquidity_provider.key(),
+        ctx.accounts.position_token_account.as_deref(),
+    )?;
+    let tick_lower = ctx.accounts.position.tick_lower;
+    let tick_upper = ctx.accounts.position.tick_upper;
 
     let mut pool = ctx.accounts.pool.load_mut()?;
-    let tick_lower_acc = &mut ctx.accounts.tick_lower_acc;
-    let tick_upper_acc = &mut ctx.accounts.tick_upper_acc;
     let token_a_mint = ctx.accounts.token_mint_a.key();
     let token_b_mint = ctx.accounts.token_mint_b.key();
 
-    require_eq!(
-        tick_lower_acc.index,
-        tick_lower,
-        CLMMError::InvalidTickIndex
-    );
-    require_eq!(
-        tick_upper_acc.index,
-        tick_upper,
-        CLMMError::InvalidTickIndex
-    );
     require!(pool.mint_a == token_a_mint, CLMMError::InvalidTokenMint);
     require!(pool.mint_b == token_b_mint, CLMMError::InvalidTokenMint);
 
-    require!(
-        tick_lower % TICK_SPACING as i32 == 0 && tick_upper % TICK_SPACING as i32 == 0,
-        CLMMError::UnalignedTick
-    );
-
-    tick_lower_acc.liquidity_net = tick_lower_acc
-        .liquidity_net
-        .checked_add(liquidity as i128)
-        .ok_or(CLMMError::ArithmeticOverflow)?;
-
-    tick_upper_acc.liquidity_net = tick_upper_acc
-        .liquidity_net
-        .checked_sub(liquidity as i128)
-        .ok_or(CLMMError::ArithmeticOverflow)?;
+    let liquidity_delta = i128::try_from(liquidity).map_err(|_| CLMMError::ArithmeticOverflow)?;
+    ctx.accounts.tick_array_lower.load_mut()?.update_tick(
+        tick_lower,
+        &pool,
+        liquidity_delta,
+        false,
+    )?;
+    ctx.accounts.tick_array_upper.load_mut()?.update_tick(
+        tick_upper,
+        &pool,
+        liquidity_delta,
+        true,
+    )?;
 
-    if pool.current_tick >= tick_lower && pool.current_tick <= tick_upper {
+    // Fees earned so far belong to the liquidity the position held until now
+    let lower = *ctx
+        .accounts
+        .tick_array_lower
+        .load()?
+        .tick(tick_lower, pool.tick_spacing)?;
+    let upper = *ctx
+        .accounts
+        .tick_array_upper
+        .load()?
+        .tick(tick_upper, pool.tick_spacing)?;
+    let (fee_growth_inside_a_x64, fee_growth_inside_b_x64) =
+        pool.fee_growth_inside_x64(tick_lower, &lower, tick_upper, &upper);
+    ctx.accounts
+        .position
+        .update_fees(fee_growth_inside_a_x64, fee_growth_inside_b_x64)?;
+
+    if pool.current_tick >= tick_lower && pool.current_tick < tick_upper {
         pool.active_liquidity = pool
             .active_liquidity
             .checked_add(liquidity)
             .ok_or(CLMMError::ArithmeticOverflow)?;
     }
 
-    let authority_seeds = &[
-        b"authority",
-        token_a_mint.as_ref(),
-        token_b_mint.as_ref(),
-        &[ctx.bumps.authority],
-    ];
-
     let sqrt_price_lower_x64 = tick_to_sqrt_price_x64(tick_lower)?;
     let sqrt_price_upper_x64 = tick_to_sqrt_price_x64(tick_upper)?;
 
@@ -97,78 +99,20 @@ pub fn add_liquidity(
         )?;
     }
 
-    // LP token calculation based on actual token value contributed
-    let mint_amount = if pool.total_lp_issued == 0 {
-        if amount_a > 0 && amount_b > 0 {
-            // Use geometric mean for initial liquidity: sqrt(amount_a * amount_b)
-            let product = (amount_a as u128)
-                .checked_mul(amount_b as u128)
-                .ok_or(CLMMError::ArithmeticOverflow)?;
-            integer_sqrt(product)
-        } else {
-            std::cmp::max(amount_a, amount_b)
-        }
-    } else {
-        // Calculate LP tokens based on proportional contribution to pool value
-        let pool_balance_a = ctx.accounts.vault_a.amount;
-        let pool_balance_b = ctx.accounts.vault_b.amount;
-
-        if pool_balance_a == 0 && pool_balance_b == 0 {
-            return Err(CLMMError::PoolEmpty.into());
-        }
-
-        let share_from_a = if pool_balance_a > 0 {
-            (amount_a as u128)
-                .checked_mul(pool.total_lp_issued as u128)
-                .ok_or(CLMMError::ArithmeticOverflow)?
-                .checked_div(pool_balance_a as u128)
-                .ok_or(CLMMError::ArithmeticOverflow)?
-        } else {
-            0
-        };
-
-        let share_from_b = if pool_balance_b > 0 {
-            (amount_b as u128)
-                .checked_mul(pool.total_lp_issued as u128)
-                .ok_or(CLMMError::ArithmeticOverflow)?
-                .checked_div(pool_balance_b as u128)
-                .ok_or(CLMMError::ArithmeticOverflow)?
-        } else {
-            0
-        };
-
-        std::cmp::min(share_from_a, share_from_b)
-            .try_into()
-            .map_err(|_| CLMMError::ArithmeticOverflow)?
-    };
-
-    pool.total_lp_issued = pool
-        .total_lp_issued
-        .checked_add(mint_amount)
+    let position = &mut ctx.accounts.position;
+    position.liquidity = position
+        .liquidity
+        .checked_add(liquidity)
         .ok_or(CLMMError::ArithmeticOverflow)?;
-    mint_to(
-        CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            MintTo {
-                mint: ctx.accounts.lp_token_mint.to_account_info(),
-                to: ctx.accounts.lp_token_account.to_account_info(),
-                authority: ctx.accounts.authority.to_account_info(),
-            },
-            &[authority_seeds],
-        ),
-        mint_amount,
-    )?;
     Ok(())
 }
 
 #[derive(Accounts)]
-#[instruction(tick_lower:i32,tick_upper:i32)]
 pub struct AddLiquidity<'info> {
-    #[account(mut)]
     pub liquidity_provider: Signer<'info>,
 
-    /// CHECK: This holds the complete authority for vault A and B and lp_mint_token
-    #[account(seeds = [b"authority", token_mint_a.key().as_ref(), token_mint_b.key().as_ref()], bump)]
+    /// CHECK: This holds the complete authority for vault A and B
+    #[account(seeds = [b"authority", pool.key().as_ref()], bump)]
     pub authority: UncheckedAccount<'info>,
 
     pub token_mint_a: Account<'info, Mint>,
@@ -176,24 +120,44 @@ pub struct AddLiquidity<'info> {
 
     #[account(
         mut,
-        seeds = [b"pool", token_mint_a.key().as_ref(), token_mint_b.key().as_ref()],
-        bump
+        seeds = [
+            b"pool",
+            token_mint_a.key().as_ref(),
+            token_mint_b.key().as_ref(),
+            &pool.load()?.fee_rate.to_le_bytes()
+        ],
+        bump = pool.load()?.bump
     )]
     pub pool: AccountLoader<'info, Pool>,
 
+    #[account(mut, has_one = pool)]
+    pub position: Account<'info, Position>,
+
+    // Only needed for positions with an NFT, to prove the signer holds it
+    pub position_token_account: Option<Account<'info, TokenAccount>>,
+
+    // Both bounds may fall in the same array, in which case the same account is passed twice
     #[account(
         mut,
-        seeds = [b"tick",pool.key().as_ref(),&tick_lower.to_le_bytes()],
-        bump
+        seeds = [
+            b"tick_array",
+            pool.key().as_ref(),
+            &tick_array_start_index(position.tick_lower, pool.load()?.tick_spacing).to_le_bytes()
+        ],
+        bump = tick_array_lower.load()?.bump
     )]
-    pub tick_lower_acc: Account<'info, Tick>,
+    pub tick_array_lower: AccountLoader<'info, TickArray>,
 
     #[account(
         mut,
-        seeds = [b"tick",pool.key().as_ref(),&tick_upper.to_le_bytes()],
-        bump
+        seeds = [
+            b"tick_array",
+            pool.key().as_ref(),
+            &tick_array_start_index(position.tick_upper, pool.load()?.tick_spacing).to_le_bytes()
+        ],
+        bump = tick_array_upper.load()?.bump
     )]
-    pub tick_upper_acc: Account<'info, Tick>,
+    pub tick_array_upper: AccountLoader<'info, TickArray>,
 
     #[account(
         mut,
@@ -211,7 +175,7 @@ pub struct AddLiquidity<'info> {
 
     #[account(
         mut,
-        seeds = [b"vault_token", token_mint_a.key().as_ref(), token_mint_b.key().as_ref(), b"A"],
+        seeds = [b"vault_token", pool.key().as_ref(), b"A"],
         bump,
         token::mint = token_mint_a,
         token::authority = authority
@@ -220,29 +184,12 @@ pub struct AddLiquidity<'info> {
 
     #[account(
         mut,
-        seeds = [b"vault_token", token_mint_a.key().as_ref(), token_mint_b.key().as_ref(), b"B"],
+        seeds = [b"vault_token", pool.key().as_ref(), b"B"],
         bump,
         token::mint = token_mint_b,
         token::authority = authority
     )]
     pub vault_b: Box<Account<'info, TokenAccount>>,
 
-    #[account(
-        mut,
-        seeds = [b"lp_mint", token_mint_a.key().as_ref(), token_mint_b.key().as_ref()],
-        bump,
-    )]
-    pub lp_token_mint: Account<'info, Mint>,
-
-    #[account(
-        init_if_needed,
-        payer = liquidity_provider,
-        associated_token::mint = lp_token_mint,
-        associated_token::authority = liquidity_provider
-    )]
-    pub lp_token_account: Box<Account<'info, TokenAccount>>,
-
     pub token_program: Program<'info, Token>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
-    pub system_program: Program<'info, System>,
 }
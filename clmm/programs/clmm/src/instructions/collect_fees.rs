@@ -0,0 +1,171 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{transfer, Mint, Token, TokenAccount, Transfer};
+
+use crate::{
+    error::CLMMError,
+    state::{Pool, Position, TickArray},
+    utils::tick_array_start_index,
+};
+
+// Pays out up to amount_a_max and amount_b_max of the fees the position has earned,
+// leaving the rest owed for a later collection
+pub fn collect_fees(ctx: Context<CollectFees>, amount_a_max: u64, amount_b_max: u64) -> Result<()> {
+    ctx.accounts.position.check_authority(
+        &ctx.accounts.owner.key(),
+        ctx.accounts.position_token_account.as_deref(),
+    )?;
+
+    let pool = ctx.accounts.pool.load()?;
+    require_keys_eq!(
+        pool.mint_a,
+        ctx.accounts.token_mint_a.key(),
+        CLMMError::InvalidTokenMint
+    );
+    require_keys_eq!(
+        pool.mint_b,
+        ctx.accounts.token_mint_b.key(),
+        CLMMError::InvalidTokenMint
+    );
+    let tick_lower = ctx.accounts.position.tick_lower;
+    let tick_upper = ctx.accounts.position.tick_upper;
+    let lower = *ctx
+        .accounts
+        .tick_array_lower
+        .load()?
+        .tick(tick_lower, pool.tick_spacing)?;
+    let upper = *ctx
+        .accounts
+        .tick_array_upper
+        .load()?
+        .tick(tick_upper, pool.tick_spacing)?;
+    let (fee_growth_inside_a_x64, fee_growth_inside_b_x64) =
+        pool.fee_growth_inside_x64(tick_lower, &lower, tick_upper, &upper);
+    drop(pool);
+
+    let position = &mut ctx.accounts.position;
+    position.update_fees(fee_growth_inside_a_x64, fee_growth_inside_b_x64)?;
+
+    let amount_a = position.tokens_owed_a.min(amount_a_max);
+    let amount_b = position.tokens_owed_b.min(amount_b_max);
+    position.tokens_owed_a -= amount_a;
+    position.tokens_owed_b -= amount_b;
+
+    let pool_key = ctx.accounts.pool.key();
+    let seeds: &[&[u8]] = &[b"authority", pool_key.as_ref(), &[ctx.bumps.authority]];
+    let signer = &[seeds];
+
+    if amount_a != 0 {
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.vault_a.to_account_info(),
+                    to: ctx.accounts.token_account_a.to_account_info(),
+                    authority: ctx.accounts.authority.to_account_info(),
+                },
+                signer,
+            ),
+            amount_a,
+        )?;
+    }
+
+    if amount_b != 0 {
+        transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.vault_b.to_account_info(),
+                    to: ctx.accounts.token_account_b.to_account_info(),
+                    authority: ctx.accounts.authority.to_account_info(),
+                },
+                signer,
+            ),
+            amount_b,
+        )?;
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CollectFees<'info> {
+    pub owner: Signer<'info>,
+
+    pub token_mint_a: Account<'info, Mint>,
+    pub token_mint_b: Account<'info, Mint>,
+
+    /// CHECK: This holds the complete authority for vault A and B
+    #[account(seeds = [b"authority", pool.key().as_ref()], bump)]
+    pub authority: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [
+            b"pool",
+            token_mint_a.key().as_ref(),
+            token_mint_b.key().as_ref(),
+            &pool.load()?.fee_rate.to_le_bytes()
+        ],
+        bump = pool.load()?.bump
+    )]
+    pub pool: AccountLoader<'info, Pool>,
+
+    #[account(mut, has_one = pool)]
+    pub position: Account<'info, Position>,
+
+    // Only needed for positions with an NFT, to prove the signer holds it
+    pub position_token_account: Option<Account<'info, TokenAccount>>,
+
+    #[account(
+        seeds = [
+            b"tick_array",
+            pool.key().as_ref(),
+            &tick_array_start_index(position.tick_lower, pool.load()?.tick_spacing).to_le_bytes()
+        ],
+        bump = tick_array_lower.load()?.bump
+    )]
+    pub tick_array_lower: AccountLoader<'info, TickArray>,
+
+    #[account(
+        seeds = [
+            b"tick_array",
+            pool.key().as_ref(),
+            &tick_array_start_index(position.tick_upper, pool.load()?.tick_spacing).to_le_bytes()
+        ],
+        bump = tick_array_upper.load()?.bump
+    )]
+    pub tick_array_upper: AccountLoader<'info, TickArray>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint_a,
+        associated_token::authority = owner
+    )]
+    pub token_account_a: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint_b,
+        associated_token::authority = owner
+    )]
+    pub token_account_b: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"vault_token", pool.key().as_ref(), b"A"],
+        bump,
+        token::mint = token_mint_a,
+        token::authority = authority
+    )]
+    pub vault_a: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"vault_token", pool.key().as_ref(), b"B"],
+        bump,
+        token::mint = token_mint_b,
+        token::authority = authority
+    )]
+    pub vault_b: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+}
@@ -0,0 +1,39 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::CLMMError, program::Clmm, state::FeeTierConfig};
+
+// Only the program's upgrade authority can create the config, and it becomes the first admin
+pub fn initialize_fee_tier_config(ctx: Context<InitializeFeeTierConfig>) -> Result<()> {
+    let config = &mut ctx.accounts.fee_tier_config;
+    config.admin = ctx.accounts.admin.key();
+    config.fee_tiers = Vec::new();
+    config.bump = ctx.bumps.fee_tier_config;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitializeFeeTierConfig<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    // Grown and shrunk as fee tiers get added and removed
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + 32 + 4 + 1,
+        seeds = [b"fee_tier_config"],
+        bump
+    )]
+    pub fee_tier_config: Account<'info, FeeTierConfig>,
+
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, Clmm>,
+
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(admin.key())
+            @ CLMMError::Unauthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
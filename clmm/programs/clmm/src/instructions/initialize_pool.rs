@@ -3,15 +3,27 @@ use anchor_spl::associated_token::AssociatedToken;
 use anchor_spl::token::{Mint, Token, TokenAccount};
 
 use crate::error::CLMMError;
-use crate::state::Pool;
+use crate::state::{FeeTier, FeeTierConfig, Pool};
 use crate::utils::{price_to_sqrt_price_x64, sqrt_price_x64_to_tick};
 
 //current price is the current price of a wrt b while creating the pool.
-pub fn initialize_pool(ctx: Context<InitializePool>, current_price: u64) -> Result<()> {
+pub fn initialize_pool(
+    ctx: Context<InitializePool>,
+    current_price: u64,
+    fee_rate: u32,
+    tick_spacing: u16,
+) -> Result<()> {
     require!(
         ctx.accounts.token_a_mint.key() != ctx.accounts.token_b_mint.key(),
         CLMMError::SameTokenMint
     );
+    require!(
+        ctx.accounts.fee_tier_config.fee_tiers.contains(&FeeTier {
+            fee_rate,
+            tick_spacing
+        }),
+        CLMMError::InvalidFeeTier
+    );
 
     let curr_sqrt_price_x64 = price_to_sqrt_price_x64(current_price)?;
     let current_tick = sqrt_price_x64_to_tick(curr_sqrt_price_x64)?;
@@ -21,68 +33,69 @@ pub fn initialize_pool(ctx: Context<InitializePool>, current_price: u64) -> Resu
     pool.mint_b = ctx.accounts.token_b_mint.key();
     pool.vault_a = ctx.accounts.vault_a.key();
     pool.vault_b = ctx.accounts.vault_b.key();
-    pool.lp_mint = ctx.accounts.lp_token_mint.key();
-    pool.total_lp_issued = 0;
     pool.bump = ctx.bumps.pool;
     pool.pool_authority = ctx.accounts.authority.key();
     pool.sqrt_price_x64 = curr_sqrt_price_x64;
     pool.current_tick = current_tick;
     pool.active_liquidity = 0;
+    pool.fee_growth_global_a_x64 = 0;
+    pool.fee_growth_global_b_x64 = 0;
+    pool.fee_rate = fee_rate;
+    pool.tick_spacing = tick_spacing;
 
     Ok(())
 }
 
 #[derive(Accounts)]
+#[instruction(current_price: u64, fee_rate: u32)]
 pub struct InitializePool<'info> {
     #[account(mut)]
     pub initializer: Signer<'info>,
 
-    /// CHECK: This holds the complete authority for vault A and B and lp_mint_token
-    #[account(seeds = [b"authority", token_a_mint.key().as_ref(), token_b_mint.key().as_ref()], bump)]
-    pub authority: UncheckedAccount<'info>,
-
     pub token_a_mint: Account<'info, Mint>,
     pub token_b_mint: Account<'info, Mint>,
 
+    #[account(seeds = [b"fee_tier_config"], bump = fee_tier_config.bump)]
+    pub fee_tier_config: Account<'info, FeeTierConfig>,
+
+    // A pair gets one pool per fee tier
     #[account(
         init,
         payer = initializer,
-        seeds = [b"vault_token", token_a_mint.key().as_ref(), token_b_mint.key().as_ref(), b"A"],
-        bump,
-        token::mint = token_a_mint,
-        token::authority = authority
+        space = 8 + 32 * 5 + 16 * 4 + 4 + 4 + 2 + 1 + 5,
+        seeds = [
+            b"pool",
+            token_a_mint.key().as_ref(),
+            token_b_mint.key().as_ref(),
+            &fee_rate.to_le_bytes()
+        ],
+        bump
     )]
-    pub vault_a: Account<'info, TokenAccount>,
+    pub pool: AccountLoader<'info, Pool>,
+
+    /// CHECK: This holds the complete authority for vault A and B and position mints
+    #[account(seeds = [b"authority", pool.key().as_ref()], bump)]
+    pub authority: UncheckedAccount<'info>,
 
     #[account(
         init,
         payer = initializer,
-        seeds = [b"vault_token", token_a_mint.key().as_ref(), token_b_mint.key().as_ref(), b"B"],
+        seeds = [b"vault_token", pool.key().as_ref(), b"A"],
         bump,
-        token::mint = token_b_mint,
+        token::mint = token_a_mint,
         token::authority = authority
     )]
-    pub vault_b: Account<'info, TokenAccount>,
+    pub vault_a: Account<'info, TokenAccount>,
 
     #[account(
         init,
         payer = initializer,
-        seeds = [b"lp_mint", token_a_mint.key().as_ref(), token_b_mint.key().as_ref()],
+        seeds = [b"vault_token", pool.key().as_ref(), b"B"],
         bump,
-        mint::decimals = 6,
-        mint::authority = authority,
-        mint::freeze_authority = authority
-    )]
-    pub lp_token_mint: Account<'info, Mint>,
-
-    #[account(
-        init,
-        payer = initializer,
-        space = 8 + 32 * 6 + 16 * 2 + 8 + 4 + 1 + 3,
-        seeds = [b"pool", token_a_mint.key().as_ref(), token_b_mint.key().as_ref()],
-        bump
+        token::mint = token_b_mint,
+        token::authority = authority
     )]
-    pub pool: AccountLoader<'info, Pool>,
+    pub vault_b: Account<'info, TokenAccount>,
 
     pub token_program: Program<'info, Token>,
     pub associated_token_program: Program<'info, AssociatedToken>,
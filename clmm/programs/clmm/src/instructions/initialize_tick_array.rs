@@ -0,0 +1,64 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::Mint;
+
+use crate::{
+    error::CLMMError,
+    state::{Pool, TickArray},
+    utils::{tick_array_start_index, MAX_TICK, MIN_TICK, TICK_ARRAY_SIZE},
+};
+
+// Ticks in the array start out uninitialized, positions bring them into use
+pub fn initialize_tick_array(
+    ctx: Context<InitializeTickArray>,
+    start_tick_index: i32,
+) -> Result<()> {
+    let pool = ctx.accounts.pool.load()?;
+    require_eq!(
+        tick_array_start_index(start_tick_index, pool.tick_spacing),
+        start_tick_index,
+        CLMMError::InvalidTickArrayStart
+    );
+    let ticks_in_array = TICK_ARRAY_SIZE as i32 * pool.tick_spacing as i32;
+    require!(start_tick_index <= MAX_TICK, CLMMError::TickUpperOverflow);
+    require!(
+        start_tick_index + ticks_in_array > MIN_TICK,
+        CLMMError::TickLowerOverflow
+    );
+
+    let mut tick_array = ctx.accounts.tick_array.load_init()?;
+    tick_array.pool = ctx.accounts.pool.key();
+    tick_array.start_tick_index = start_tick_index;
+    tick_array.initialized_bitmap = 0;
+    tick_array.bump = ctx.bumps.tick_array;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(start_tick_index: i32)]
+pub struct InitializeTickArray<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub token_a_mint: Account<'info, Mint>,
+    pub token_b_mint: Account<'info, Mint>,
+    #[account(
+        seeds = [
+            b"pool",
+            token_a_mint.key().as_ref(),
+            token_b_mint.key().as_ref(),
+            &pool.load()?.fee_rate.to_le_bytes()
+        ],
+        bump = pool.load()?.bump
+    )]
+    pub pool: AccountLoader<'info, Pool>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + std::mem::size_of::<TickArray>(),
+        seeds = [b"tick_array", pool.key().as_ref(), &start_tick_index.to_le_bytes()],
+        bump
+    )]
+    pub tick_array: AccountLoader<'info, TickArray>,
+    pub system_program: Program<'info, System>,
+}
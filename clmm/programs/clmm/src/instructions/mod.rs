@@ -1,11 +1,23 @@
+pub mod add_fee_tier;
 pub mod add_liquidity;
+pub mod collect_fees;
+pub mod initialize_fee_tier_config;
 pub mod initialize_pool;
-pub mod initialize_tick;
+pub mod initialize_tick_array;
+pub mod open_position;
+pub mod remove_fee_tier;
+pub mod set_fee_tier_admin;
 pub mod swap;
 pub mod withdraw_liquidity;
 
+pub use add_fee_tier::*;
 pub use add_liquidity::*;
+pub use collect_fees::*;
+pub use initialize_fee_tier_config::*;
 pub use initialize_pool::*;
-pub use initialize_tick::*;
+pub use initialize_tick_array::*;
+pub use open_position::*;
+pub use remove_fee_tier::*;
+pub use set_fee_tier_admin::*;
 pub use swap::*;
 pub use withdraw_liquidity::*;
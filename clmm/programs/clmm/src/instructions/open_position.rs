@@ -0,0 +1,153 @@
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::{
+    mint_to, set_authority, spl_token::instruction::AuthorityType, Mint, MintTo, SetAuthority,
+    Token, TokenAccount,
+};
+
+use crate::{
+    error::CLMMError,
+    state::{Pool, Position},
+    utils::{MAX_TICK, MIN_TICK},
+};
+
+// Passing position_mint and position_token_account mints a 1-of-1 NFT to the owner, and the
+// position then follows whoever holds it
+pub fn open_position(ctx: Context<OpenPosition>, tick_lower: i32, tick_upper: i32) -> Result<()> {
+    require!(tick_lower < tick_upper, CLMMError::TickMismatch);
+    require!(tick_lower >= MIN_TICK, CLMMError::TickLowerOverflow);
+    require!(tick_upper <= MAX_TICK, CLMMError::TickUpperOverflow);
+
+    let pool = ctx.accounts.pool.load()?;
+    let tick_spacing = pool.tick_spacing as i32;
+    require!(
+        tick_lower % tick_spacing == 0 && tick_upper % tick_spacing == 0,
+        CLMMError::UnalignedTick
+    );
+    require_keys_eq!(
+        pool.mint_a,
+        ctx.accounts.token_mint_a.key(),
+        CLMMError::InvalidTokenMint
+    );
+    require_keys_eq!(
+        pool.mint_b,
+        ctx.accounts.token_mint_b.key(),
+        CLMMError::InvalidTokenMint
+    );
+    drop(pool);
+
+    let position_mint = match (
+        &ctx.accounts.position_mint,
+        &ctx.accounts.position_token_account,
+    ) {
+        (Some(position_mint), Some(position_token_account)) => {
+            let pool_key = ctx.accounts.pool.key();
+            let seeds: &[&[u8]] = &[b"authority", pool_key.as_ref(), &[ctx.bumps.authority]];
+            let signer = &[seeds];
+
+            mint_to(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    MintTo {
+                        mint: position_mint.to_account_info(),
+                        to: position_token_account.to_account_info(),
+                        authority: ctx.accounts.authority.to_account_info(),
+                    },
+                    signer,
+                ),
+                1,
+            )?;
+            // No more can ever be minted, so the single token is the position
+            set_authority(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    SetAuthority {
+                        current_authority: ctx.accounts.authority.to_account_info(),
+                        account_or_mint: position_mint.to_account_info(),
+                    },
+                    signer,
+                ),
+                AuthorityType::MintTokens,
+                None,
+            )?;
+            position_mint.key()
+        }
+        (None, None) => Pubkey::default(),
+        _ => return Err(CLMMError::MissingPositionNftAccounts.into()),
+    };
+
+    let position = &mut ctx.accounts.position;
+    position.owner = ctx.accounts.owner.key();
+    position.pool = ctx.accounts.pool.key();
+    position.position_mint = position_mint;
+    position.tick_lower = tick_lower;
+    position.tick_upper = tick_upper;
+    position.liquidity = 0;
+    position.fee_growth_inside_a_last_x64 = 0;
+    position.fee_growth_inside_b_last_x64 = 0;
+    position.tokens_owed_a = 0;
+    position.tokens_owed_b = 0;
+    position.bump = ctx.bumps.position;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(tick_lower: i32, tick_upper: i32)]
+pub struct OpenPosition<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    /// CHECK: Mint authority of position NFTs, until it gives it up after minting
+    #[account(seeds = [b"authority", pool.key().as_ref()], bump)]
+    pub authority: UncheckedAccount<'info>,
+
+    pub token_mint_a: Account<'info, Mint>,
+    pub token_mint_b: Account<'info, Mint>,
+
+    #[account(
+        seeds = [
+            b"pool",
+            token_mint_a.key().as_ref(),
+            token_mint_b.key().as_ref(),
+            &pool.load()?.fee_rate.to_le_bytes()
+        ],
+        bump = pool.load()?.bump
+    )]
+    pub pool: AccountLoader<'info, Pool>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + 32 * 3 + 4 + 4 + 16 * 3 + 8 * 2 + 1,
+        seeds = [
+            b"position",
+            pool.key().as_ref(),
+            owner.key().as_ref(),
+            &tick_lower.to_le_bytes(),
+            &tick_upper.to_le_bytes()
+        ],
+        bump
+    )]
+    pub position: Account<'info, Position>,
+
+    #[account(
+        init,
+        payer = owner,
+        mint::decimals = 0,
+        mint::authority = authority
+    )]
+    pub position_mint: Option<Account<'info, Mint>>,
+
+    #[account(
+        init,
+        payer = owner,
+        associated_token::mint = position_mint,
+        associated_token::authority = owner
+    )]
+    pub position_token_account: Option<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
@@ -0,0 +1,35 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::CLMMError, state::FeeTierConfig};
+
+// Existing pools on the tier keep working, only new pools can no longer use it
+pub fn remove_fee_tier(ctx: Context<RemoveFeeTier>, fee_rate: u32) -> Result<()> {
+    let config = &mut ctx.accounts.fee_tier_config;
+    config.check_admin(&ctx.accounts.admin.key())?;
+
+    let index = config
+        .fee_tiers
+        .iter()
+        .position(|tier| tier.fee_rate == fee_rate)
+        .ok_or(CLMMError::InvalidFeeTier)?;
+    config.fee_tiers.remove(index);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct RemoveFeeTier<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"fee_tier_config"],
+        bump = fee_tier_config.bump,
+        realloc = 8 + 32 + 4 + (4 + 2) * fee_tier_config.fee_tiers.len().saturating_sub(1) + 1,
+        realloc::payer = admin,
+        realloc::zero = false
+    )]
+    pub fee_tier_config: Account<'info, FeeTierConfig>,
+
+    pub system_program: Program<'info, System>,
+}
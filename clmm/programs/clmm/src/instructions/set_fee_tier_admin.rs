@@ -0,0 +1,23 @@
+use anchor_lang::prelude::*;
+
+use crate::state::FeeTierConfig;
+
+// Hands the fee tier list over to a new admin
+pub fn set_fee_tier_admin(ctx: Context<SetFeeTierAdmin>, new_admin: Pubkey) -> Result<()> {
+    let config = &mut ctx.accounts.fee_tier_config;
+    config.check_admin(&ctx.accounts.admin.key())?;
+    config.admin = new_admin;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetFeeTierAdmin<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"fee_tier_config"],
+        bump = fee_tier_config.bump
+    )]
+    pub fee_tier_config: Account<'info, FeeTierConfig>,
+}
@@ -3,40 +3,84 @@ use anchor_spl::token::{transfer, Mint, Token, TokenAccount, Transfer};
 
 use crate::{
     error::CLMMError,
-    state::{Pool, Tick},
-    utils::{compute_swap_step, tick_to_sqrt_price_x64},
+    state::{Pool, TickArray},
+    utils::{
+        check_deadline, compute_swap_step, fee_growth_x64, sqrt_price_x64_to_tick,
+        tick_array_start_index, tick_to_sqrt_price_x64, MAX_SQRT_PRICE_X64, MAX_TICK,
+        MIN_SQRT_PRICE_X64, MIN_TICK, TICK_ARRAY_SIZE,
+    },
 };
 
-pub fn swap(
-    ctx: Context<Swap>,
+// Loads a caller-supplied tick array, making sure it is the pool's PDA for start_tick_index
+fn load_tick_array<'info>(
+    tick_array_info: &'info AccountInfo<'info>,
+    pool: &Pubkey,
+    start_tick_index: i32,
+) -> Result<AccountLoader<'info, TickArray>> {
+    let tick_array = AccountLoader::<TickArray>::try_from(tick_array_info)?;
+    let bump = {
+        let array = tick_array.load()?;
+        require_keys_eq!(array.pool, *pool, CLMMError::InvalidTickArray);
+        require_eq!(
+            array.start_tick_index,
+            start_tick_index,
+            CLMMError::InvalidTickArray
+        );
+        array.bump
+    };
+    let expected_key = Pubkey::create_program_address(
+        &[
+            b"tick_array",
+            pool.as_ref(),
+            &start_tick_index.to_le_bytes(),
+            &[bump],
+        ],
+        &crate::ID,
+    )
+    .map_err(|_| CLMMError::InvalidTickArray)?;
+    require_keys_eq!(
+        expected_key,
+        tick_array_info.key(),
+        CLMMError::InvalidTickArray
+    );
+    Ok(tick_array)
+}
+
+pub fn swap<'info>(
+    ctx: Context<'_, '_, 'info, 'info, Swap<'info>>,
     amount_in: u64,
     a_to_b: bool,
     sqrt_price_limit_x64: Option<u128>,
     min_amount_out: Option<u64>,
+    deadline: Option<i64>,
 ) -> Result<()> {
+    check_deadline(deadline)?;
     require!(amount_in > 0, CLMMError::ZeroAmount);
-    require!(
-        !ctx.remaining_accounts.is_empty(),
-        CLMMError::MissingTickAccounts
-    );
-
-    //assuming tick acc's are passed in order
-    //ascending order for A to B and descending order for B to A
-    let mut tick_infos = vec![];
-    for account_info in ctx.remaining_accounts.iter() {
-        let data = account_info.data.borrow_mut();
-        let mut tick_data_slice = &data[8..]; // skip the discriminator
-        let tick: Tick = Tick::try_deserialize(&mut tick_data_slice)?;
-        tick_infos.push((account_info.clone(), tick));
-    }
 
     let mut pool = ctx.accounts.pool.load_mut()?;
-    let sqrt_price_limit =
-        sqrt_price_limit_x64.unwrap_or_else(|| if a_to_b { 1 } else { u128::MAX });
+    let pool_key = ctx.accounts.pool.key();
+    let sqrt_price_limit = sqrt_price_limit_x64.unwrap_or(if a_to_b {
+        MIN_SQRT_PRICE_X64
+    } else {
+        MAX_SQRT_PRICE_X64 - 1
+    });
+    if a_to_b {
+        require!(
+            sqrt_price_limit >= MIN_SQRT_PRICE_X64 && sqrt_price_limit < pool.sqrt_price_x64,
+            CLMMError::SqrtPriceX64
+        );
+    } else {
+        require!(
+            sqrt_price_limit > pool.sqrt_price_x64 && sqrt_price_limit < MAX_SQRT_PRICE_X64,
+            CLMMError::SqrtPriceX64
+        );
+    }
 
     let mut curr_sqrt_price_x64 = pool.sqrt_price_x64;
     let mut curr_tick = pool.current_tick;
     let mut liquidity = pool.active_liquidity;
+    let mut fee_growth_global_a_x64 = pool.fee_growth_global_a_x64;
+    let mut fee_growth_global_b_x64 = pool.fee_growth_global_b_x64;
 
     let mut total_amount_in: u128 = 0;
     let mut total_amount_out: u128 = 0;
@@ -46,50 +90,114 @@ pub fn swap(
 
     require_keys_eq!(pool.mint_a, token_mint_a, CLMMError::InvalidTokenMint);
     require_keys_eq!(pool.mint_b, token_mint_b, CLMMError::InvalidTokenMint);
-    require!(
-        pool.active_liquidity > 0,
-        CLMMError::InsufficientFundsInPool
-    );
 
-    for (_tick_acc_info, tick) in tick_infos {
-        let next_sqrt_price_x64 = tick_to_sqrt_price_x64(tick.index)?;
+    // Tick arrays are passed as remaining accounts in swap order, starting with the one holding
+    // the current tick. A swap stops early once it runs past the last array it was given.
+    let tick_spacing = pool.tick_spacing;
+    let ticks_in_array = TICK_ARRAY_SIZE as i32 * tick_spacing as i32;
+    let mut tick_array_infos = ctx.remaining_accounts.iter();
+    let mut array_start = tick_array_start_index(curr_tick, tick_spacing);
+    let mut tick_array = load_tick_array(
+        tick_array_infos
+            .next()
+            .ok_or(CLMMError::MissingTickAccounts)?,
+        &pool_key,
+        array_start,
+    )?;
 
-        if (a_to_b && next_sqrt_price_x64 < sqrt_price_limit)
-            || (!a_to_b && next_sqrt_price_x64 > sqrt_price_limit)
-        {
-            break;
-        }
+    while remaining_amount > 0 && curr_sqrt_price_x64 != sqrt_price_limit {
+        // Without an initialized tick left in this array the price can move freely to its edge
+        let next_initialized_tick =
+            tick_array
+                .load()?
+                .next_initialized_tick(curr_tick, tick_spacing, a_to_b);
+        let (next_tick, initialized) = match next_initialized_tick {
+            Some(tick_index) => (tick_index, true),
+            None if a_to_b => (array_start.max(MIN_TICK), false),
+            None => ((array_start + ticks_in_array).min(MAX_TICK), false),
+        };
+        let next_sqrt_price_x64 = tick_to_sqrt_price_x64(next_tick)?;
+        let target_sqrt_price_x64 = if a_to_b {
+            next_sqrt_price_x64.max(sqrt_price_limit)
+        } else {
+            next_sqrt_price_x64.min(sqrt_price_limit)
+        };
 
-        let (new_sqrt_price, computed_amount_in, computed_amount_out) = compute_swap_step(
-            curr_sqrt_price_x64,
-            next_sqrt_price_x64,
-            liquidity,
-            remaining_amount as u128,
-            a_to_b,
-        )?;
+        let (new_sqrt_price, computed_amount_in, computed_amount_out, fee_amount) =
+            compute_swap_step(
+                curr_sqrt_price_x64,
+                target_sqrt_price_x64,
+                liquidity,
+                remaining_amount,
+                pool.fee_rate,
+                a_to_b,
+            )?;
+        let amount_in_with_fee = computed_amount_in
+            .checked_add(fee_amount)
+            .ok_or(CLMMError::ArithmeticOverflow)?;
 
         curr_sqrt_price_x64 = new_sqrt_price;
         remaining_amount = remaining_amount
-            .checked_sub(computed_amount_in)
+            .checked_sub(amount_in_with_fee)
             .ok_or(CLMMError::ArithmeticOverflow)?;
         total_amount_in = total_amount_in
-            .checked_add(computed_amount_in)
+            .checked_add(amount_in_with_fee)
             .ok_or(CLMMError::ArithmeticOverflow)?;
         total_amount_out = total_amount_out
             .checked_add(computed_amount_out)
             .ok_or(CLMMError::ArithmeticOverflow)?;
 
-        if curr_sqrt_price_x64 == next_sqrt_price_x64 {
-            curr_tick = tick.index;
-            if a_to_b {
-                liquidity = liquidity
-                    .checked_sub(tick.liquidity_net as u128)
-                    .ok_or(CLMMError::ArithmeticOverflow)?;
+        // Fees are paid in the input token and shared by the liquidity in range for this step
+        let fee_growth = fee_growth_x64(fee_amount, liquidity)?;
+        if a_to_b {
+            fee_growth_global_a_x64 = fee_growth_global_a_x64.wrapping_add(fee_growth);
+        } else {
+            fee_growth_global_b_x64 = fee_growth_global_b_x64.wrapping_add(fee_growth);
+        }
+
+        let reached_next_tick = curr_sqrt_price_x64 == next_sqrt_price_x64;
+        if reached_next_tick && initialized {
+            let mut array = tick_array.load_mut()?;
+            let tick = array.tick_mut(next_tick, tick_spacing)?;
+            let liquidity_delta = if a_to_b {
+                -tick.liquidity_net
             } else {
-                liquidity = liquidity
-                    .checked_add(tick.liquidity_net as u128)
-                    .ok_or(CLMMError::ArithmeticOverflow)?;
-            }
+                tick.liquidity_net
+            };
+            liquidity = liquidity
+                .checked_add_signed(liquidity_delta)
+                .ok_or(CLMMError::ArithmeticOverflow)?;
+
+            // What was outside the tick is now inside it and vice versa
+            tick.fee_growth_outside_a_x64 =
+                fee_growth_global_a_x64.wrapping_sub(tick.fee_growth_outside_a_x64);
+            tick.fee_growth_outside_b_x64 =
+                fee_growth_global_b_x64.wrapping_sub(tick.fee_growth_outside_b_x64);
+        }
+
+        // Moving down past a tick leaves the price in the range below it. So does stopping on the
+        // upper edge of an array without crossing it: the edge is the first tick of the next
+        // array, which may be initialized and still has to be crossed from there.
+        let reached_array_end = reached_next_tick && !a_to_b && !initialized;
+        curr_tick = if !reached_next_tick {
+            sqrt_price_x64_to_tick(curr_sqrt_price_x64)?
+        } else if a_to_b || reached_array_end {
+            next_tick - 1
+        } else {
+            next_tick
+        };
+
+        let next_array_start = if reached_array_end {
+            array_start + ticks_in_array
+        } else {
+            tick_array_start_index(curr_tick, tick_spacing)
+        };
+        if next_array_start != array_start {
+            let Some(tick_array_info) = tick_array_infos.next() else {
+                break;
+            };
+            array_start = next_array_start;
+            tick_array = load_tick_array(tick_array_info, &pool_key, array_start)?;
         }
     }
 
@@ -108,13 +216,10 @@ pub fn swap(
     pool.sqrt_price_x64 = curr_sqrt_price_x64;
     pool.current_tick = curr_tick;
     pool.active_liquidity = liquidity;
+    pool.fee_growth_global_a_x64 = fee_growth_global_a_x64;
+    pool.fee_growth_global_b_x64 = fee_growth_global_b_x64;
 
-    let seeds = &[
-        b"authority",
-        token_mint_a.as_ref(),
-        token_mint_b.as_ref(),
-        &[ctx.bumps.authority],
-    ];
+    let seeds = &[b"authority", pool_key.as_ref(), &[ctx.bumps.authority]];
     let signer = &[&seeds[..]];
     if a_to_b {
         transfer(
@@ -179,13 +284,18 @@ pub struct Swap<'info> {
     pub token_mint_b: Account<'info, Mint>,
 
     /// CHECK: vaults are owned by program authority
-    #[account(seeds = [b"authority", token_mint_a.key().as_ref(), token_mint_b.key().as_ref()], bump)]
+    #[account(seeds = [b"authority", pool.key().as_ref()], bump)]
     pub authority: UncheckedAccount<'info>,
 
     #[account(
         mut,
-        seeds = [b"pool", token_mint_a.key().as_ref(), token_mint_b.key().as_ref()],
-        bump
+        seeds = [
+            b"pool",
+            token_mint_a.key().as_ref(),
+            token_mint_b.key().as_ref(),
+            &pool.load()?.fee_rate.to_le_bytes()
+        ],
+        bump = pool.load()?.bump
     )]
     pub pool: AccountLoader<'info, Pool>,
 
@@ -205,7 +315,7 @@ pub struct Swap<'info> {
 
     #[account(
         mut,
-        seeds = [b"vault_token", token_mint_a.key().as_ref(), token_mint_b.key().as_ref(), b"A"],
+        seeds = [b"vault_token", pool.key().as_ref(), b"A"],
         bump,
         token::mint = token_mint_a,
         token::authority = authority
@@ -214,7 +324,7 @@ pub struct Swap<'info> {
 
     #[account(
         mut,
-        seeds = [b"vault_token", token_mint_a.key().as_ref(), token_mint_b.key().as_ref(), b"B"],
+        seeds = [b"vault_token", pool.key().as_ref(), b"B"],
         bump,
         token::mint = token_mint_b,
         token::authority = authority
@@ -222,5 +332,5 @@ pub struct Swap<'info> {
     pub vault_b: Account<'info, TokenAccount>,
 
     pub token_program: Program<'info, Token>,
-    // tick accounts will be passed in as remaining_accounts
+    // writable tick arrays will be passed in as remaining_accounts, in swap order
 }
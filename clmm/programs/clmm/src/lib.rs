@@ -8,43 +8,106 @@ use instructions::*;
 
 declare_id!("9sfBz349EJEWpqrtFS7KJsgusGfiQBn5UbEJq58DSXvN");
 
-#[program]
-mod clmm {
-
+// Anchor 0.31 expands `#[program]` into IDL handlers that call the deprecated
+// `AccountInfo::realloc`. They are generated next to the program module rather than
+// inside it, so the allow goes on a module wrapping both.
+#[allow(deprecated)]
+mod anchor_program {
     use super::*;
-    pub fn initialize_pool(ctx: Context<InitializePool>, current_price: u64) -> Result<()> {
-        instructions::initialize_pool::initialize_pool(ctx, current_price)
-    }
 
-    pub fn initialize_tick(ctx: Context<InitializeTick>, tick_index: i32) -> Result<()> {
-        instructions::initialize_tick(ctx, tick_index)
-    }
+    #[program]
+    mod clmm {
 
-    pub fn add_liquidity(
-        ctx: Context<AddLiquidity>,
-        tick_lower: i32,
-        tick_upper: i32,
-        liquidity: u128,
-    ) -> Result<()> {
-        instructions::add_liquidity(ctx, tick_lower, tick_upper, liquidity)
-    }
+        use super::*;
+        pub fn initialize_fee_tier_config(ctx: Context<InitializeFeeTierConfig>) -> Result<()> {
+            instructions::initialize_fee_tier_config(ctx)
+        }
 
-    pub fn withdraw_liquidity(
-        ctx: Context<WithdrawLiquidity>,
-        tick_lower: i32,
-        tick_upper: i32,
-        liquidity_to_remove: u128,
-    ) -> Result<()> {
-        instructions::withdraw_liquidity(ctx, tick_lower, tick_upper, liquidity_to_remove)
-    }
+        pub fn add_fee_tier(
+            ctx: Context<AddFeeTier>,
+            fee_rate: u32,
+            tick_spacing: u16,
+        ) -> Result<()> {
+            instructions::add_fee_tier(ctx, fee_rate, tick_spacing)
+        }
+
+        pub fn remove_fee_tier(ctx: Context<RemoveFeeTier>, fee_rate: u32) -> Result<()> {
+            instructions::remove_fee_tier(ctx, fee_rate)
+        }
+
+        pub fn set_fee_tier_admin(ctx: Context<SetFeeTierAdmin>, new_admin: Pubkey) -> Result<()> {
+            instructions::set_fee_tier_admin(ctx, new_admin)
+        }
+
+        pub fn initialize_pool(
+            ctx: Context<InitializePool>,
+            current_price: u64,
+            fee_rate: u32,
+            tick_spacing: u16,
+        ) -> Result<()> {
+            instructions::initialize_pool::initialize_pool(
+                ctx,
+                current_price,
+                fee_rate,
+                tick_spacing,
+            )
+        }
+
+        pub fn initialize_tick_array(
+            ctx: Context<InitializeTickArray>,
+            start_tick_index: i32,
+        ) -> Result<()> {
+            instructions::initialize_tick_array(ctx, start_tick_index)
+        }
+
+        pub fn open_position(
+            ctx: Context<OpenPosition>,
+            tick_lower: i32,
+            tick_upper: i32,
+        ) -> Result<()> {
+            instructions::open_position(ctx, tick_lower, tick_upper)
+        }
+
+        pub fn add_liquidity(
+            ctx: Context<AddLiquidity>,
+            liquidity: u128,
+            deadline: Option<i64>,
+        ) -> Result<()> {
+            instructions::add_liquidity(ctx, liquidity, deadline)
+        }
+
+        pub fn withdraw_liquidity(
+            ctx: Context<WithdrawLiquidity>,
+            liquidity_to_remove: u128,
+        ) -> Result<()> {
+            instructions::withdraw_liquidity(ctx, liquidity_to_remove)
+        }
+
+        pub fn collect_fees(
+            ctx: Context<CollectFees>,
+            amount_a_max: u64,
+            amount_b_max: u64,
+        ) -> Result<()> {
+            instructions::collect_fees(ctx, amount_a_max, amount_b_max)
+        }
 
-    pub fn swap(
-        ctx: Context<Swap>,
-        amount_in: u64,
-        a_to_b: bool,
-        sqrt_price_limit_x64: Option<u128>,
-        min_amount_out: Option<u64>,
-    ) -> Result<()> {
-        instructions::swap(ctx, amount_in, a_to_b, sqrt_price_limit_x64, min_amount_out)
+        pub fn swap<'info>(
+            ctx: Context<'_, '_, 'info, 'info, Swap<'info>>,
+            amount_in: u64,
+            a_to_b: bool,
+            sqrt_price_limit_x64: Option<u128>,
+            min_amount_out: Option<u64>,
+            deadline: Option<i64>,
+        ) -> Result<()> {
+            instructions::swap(
+                ctx,
+                amount_in,
+                a_to_b,
+                sqrt_price_limit_x64,
+                min_amount_out,
+                deadline,
+            )
+        }
     }
 }
+pub use anchor_program::*;
@@ -1,5 +1,8 @@
 use anchor_lang::prelude::*;
-use bytemuck::{Pod, Zeroable};
+use anchor_spl::token::TokenAccount;
+
+use crate::error::CLMMError;
+use crate::utils::{fee_growth_inside_x64, fees_owed, TICK_ARRAY_SIZE};
 
 #[account(zero_copy)]
 #[derive(Debug, Default)]
@@ -9,20 +12,282 @@ pub struct Pool {
     pub mint_b: Pubkey,
     pub vault_a: Pubkey,
     pub vault_b: Pubkey,
-    pub lp_mint: Pubkey,
     pub pool_authority: Pubkey,
     pub sqrt_price_x64: u128,
     pub active_liquidity: u128,
-    pub total_lp_issued: u64,
+    // Fees earned per unit of liquidity over the pool's lifetime, in Q64.64. Wraps on overflow.
+    pub fee_growth_global_a_x64: u128,
+    pub fee_growth_global_b_x64: u128,
     pub current_tick: i32,
+    // Swap fee in hundredths of a basis point, see FEE_RATE_DENOMINATOR
+    pub fee_rate: u32,
+    // Every tick a position uses has to be a multiple of this
+    pub tick_spacing: u16,
     pub bump: u8,
-    pub _padding: [u8; 3],
+    pub _padding: [u8; 5],
+}
+
+impl Pool {
+    // Fee growth inside the range between two ticks, for token A and token B
+    pub fn fee_growth_inside_x64(
+        &self,
+        tick_lower: i32,
+        lower: &TickState,
+        tick_upper: i32,
+        upper: &TickState,
+    ) -> (u128, u128) {
+        let fee_growth_inside_a_x64 = fee_growth_inside_x64(
+            self.current_tick,
+            tick_lower,
+            tick_upper,
+            self.fee_growth_global_a_x64,
+            lower.fee_growth_outside_a_x64,
+            upper.fee_growth_outside_a_x64,
+        );
+        let fee_growth_inside_b_x64 = fee_growth_inside_x64(
+            self.current_tick,
+            tick_lower,
+            tick_upper,
+            self.fee_growth_global_b_x64,
+            lower.fee_growth_outside_b_x64,
+            upper.fee_growth_outside_b_x64,
+        );
+        (fee_growth_inside_a_x64, fee_growth_inside_b_x64)
+    }
+}
+
+// A fee rate pools may be created with, and the tick spacing that goes with it
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeeTier {
+    pub fee_rate: u32,
+    pub tick_spacing: u16,
 }
 
+// Program-wide list of fee tiers, managed by admin. Pools are seeded by their fee rate, so
+// each fee rate appears at most once.
 #[account]
-pub struct Tick {
-    pub sqrt_price_x64: u128,
+pub struct FeeTierConfig {
+    pub admin: Pubkey,
+    pub fee_tiers: Vec<FeeTier>,
+    pub bump: u8,
+}
+
+impl FeeTierConfig {
+    pub fn check_admin(&self, signer: &Pubkey) -> Result<()> {
+        require_keys_eq!(self.admin, *signer, CLMMError::Unauthorized);
+        Ok(())
+    }
+}
+
+#[zero_copy]
+#[derive(Debug, Default)]
+#[repr(C)]
+pub struct TickState {
+    // Liquidity added when the price crosses this tick upwards, removed when crossing downwards
     pub liquidity_net: i128,
-    pub index: i32,
+    // Liquidity of every position bounded by this tick. The tick is initialized while non-zero.
+    pub liquidity_gross: u128,
+    // Fee growth on the side of this tick away from the current price, flipped on every crossing
+    pub fee_growth_outside_a_x64: u128,
+    pub fee_growth_outside_b_x64: u128,
+}
+
+// TICK_ARRAY_SIZE consecutive ticks of a pool, tick_spacing apart, starting at start_tick_index.
+// Bit i of initialized_bitmap is set while ticks[i] has liquidity referencing it, so swaps can
+// jump straight to the next tick that changes the active liquidity.
+#[account(zero_copy)]
+#[derive(Debug)]
+#[repr(C)]
+pub struct TickArray {
+    pub pool: Pubkey,
+    pub initialized_bitmap: u64,
+    pub start_tick_index: i32,
+    pub bump: u8,
+    pub _padding: [u8; 3],
+    pub ticks: [TickState; TICK_ARRAY_SIZE],
+}
+
+impl TickArray {
+    // Slot of tick_index in ticks, erroring if the tick isn't aligned or lives in another array
+    fn tick_offset(&self, tick_index: i32, tick_spacing: u16) -> Result<usize> {
+        let tick_spacing = tick_spacing as i32;
+        require!(tick_index % tick_spacing == 0, CLMMError::UnalignedTick);
+        let offset = tick_index
+            .checked_sub(self.start_tick_index)
+            .ok_or(CLMMError::ArithmeticOverflow)?
+            / tick_spacing;
+        require!(
+            (0..TICK_ARRAY_SIZE as i32).contains(&offset),
+            CLMMError::InvalidTickArray
+        );
+        Ok(offset as usize)
+    }
+
+    pub fn tick(&self, tick_index: i32, tick_spacing: u16) -> Result<&TickState> {
+        let offset = self.tick_offset(tick_index, tick_spacing)?;
+        Ok(&self.ticks[offset])
+    }
+
+    pub fn tick_mut(&mut self, tick_index: i32, tick_spacing: u16) -> Result<&mut TickState> {
+        let offset = self.tick_offset(tick_index, tick_spacing)?;
+        Ok(&mut self.ticks[offset])
+    }
+
+    // Applies a position's liquidity change to one of its bounds, initializing the tick when it
+    // comes into use and clearing it once no position references it anymore
+    pub fn update_tick(
+        &mut self,
+        tick_index: i32,
+        pool: &Pool,
+        liquidity_delta: i128,
+        is_upper: bool,
+    ) -> Result<()> {
+        let offset = self.tick_offset(tick_index, pool.tick_spacing)?;
+        let tick = &mut self.ticks[offset];
+
+        if tick.liquidity_gross == 0 {
+            // By convention all fees so far were earned below the current price
+            if tick_index <= pool.current_tick {
+                tick.fee_growth_outside_a_x64 = pool.fee_growth_global_a_x64;
+                tick.fee_growth_outside_b_x64 = pool.fee_growth_global_b_x64;
+            } else {
+                tick.fee_growth_outside_a_x64 = 0;
+                tick.fee_growth_outside_b_x64 = 0;
+            }
+        }
+
+        tick.liquidity_gross = tick
+            .liquidity_gross
+            .checked_add_signed(liquidity_delta)
+            .ok_or(CLMMError::ArithmeticOverflow)?;
+        tick.liquidity_net = if is_upper {
+            tick.liquidity_net.checked_sub(liquidity_delta)
+        } else {
+            tick.liquidity_net.checked_add(liquidity_delta)
+        }
+        .ok_or(CLMMError::ArithmeticOverflow)?;
+
+        if tick.liquidity_gross == 0 {
+            *tick = TickState::default();
+            self.initialized_bitmap &= !(1u64 << offset);
+        } else {
+            self.initialized_bitmap |= 1u64 << offset;
+        }
+        Ok(())
+    }
+
+    // Closest initialized tick in this array in the swap direction: at or below current_tick
+    // when the price moves down (a_to_b), strictly above it when it moves up
+    pub fn next_initialized_tick(
+        &self,
+        current_tick: i32,
+        tick_spacing: u16,
+        a_to_b: bool,
+    ) -> Option<i32> {
+        let tick_spacing = tick_spacing as i32;
+        let offset = (current_tick - self.start_tick_index).div_euclid(tick_spacing);
+
+        let bit = if a_to_b {
+            if offset < 0 {
+                return None;
+            }
+            let offset = offset.min(TICK_ARRAY_SIZE as i32 - 1) as u32;
+            let mask = u64::MAX >> (u64::BITS - 1 - offset);
+            let bits = self.initialized_bitmap & mask;
+            if bits == 0 {
+                return None;
+            }
+            u64::BITS - 1 - bits.leading_zeros()
+        } else {
+            let offset = offset + 1;
+            if offset >= TICK_ARRAY_SIZE as i32 {
+                return None;
+            }
+            let mask = u64::MAX << offset.max(0);
+            let bits = self.initialized_bitmap & mask;
+            if bits == 0 {
+                return None;
+            }
+            bits.trailing_zeros()
+        };
+
+        Some(self.start_tick_index + bit as i32 * tick_spacing)
+    }
+}
+
+// Liquidity a single owner provides to a pool between tick_lower and tick_upper
+#[account]
+pub struct Position {
+    pub owner: Pubkey,
+    pub pool: Pubkey,
+    // 1-of-1 mint whose holder controls the position, Pubkey::default() if none was minted
+    pub position_mint: Pubkey,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub liquidity: u128,
+    // Fee growth inside the range as of the last time the position was touched
+    pub fee_growth_inside_a_last_x64: u128,
+    pub fee_growth_inside_b_last_x64: u128,
+    // Fees credited to the position and not yet collected
+    pub tokens_owed_a: u64,
+    pub tokens_owed_b: u64,
     pub bump: u8,
 }
+
+impl Position {
+    // Credits the fees earned by the current liquidity to tokens_owed and moves the checkpoints.
+    // Has to run before the position's liquidity changes.
+    pub fn update_fees(
+        &mut self,
+        fee_growth_inside_a_x64: u128,
+        fee_growth_inside_b_x64: u128,
+    ) -> Result<()> {
+        let owed_a = fees_owed(
+            self.liquidity,
+            fee_growth_inside_a_x64,
+            self.fee_growth_inside_a_last_x64,
+        )?;
+        let owed_b = fees_owed(
+            self.liquidity,
+            fee_growth_inside_b_x64,
+            self.fee_growth_inside_b_last_x64,
+        )?;
+        self.tokens_owed_a = self
+            .tokens_owed_a
+            .checked_add(owed_a)
+            .ok_or(CLMMError::ArithmeticOverflow)?;
+        self.tokens_owed_b = self
+            .tokens_owed_b
+            .checked_add(owed_b)
+            .ok_or(CLMMError::ArithmeticOverflow)?;
+        self.fee_growth_inside_a_last_x64 = fee_growth_inside_a_x64;
+        self.fee_growth_inside_b_last_x64 = fee_growth_inside_b_x64;
+        Ok(())
+    }
+
+    // A position with an NFT belongs to whoever holds it, otherwise to the wallet that opened it
+    pub fn check_authority(
+        &self,
+        signer: &Pubkey,
+        position_token_account: Option<&TokenAccount>,
+    ) -> Result<()> {
+        if self.position_mint == Pubkey::default() {
+            require_keys_eq!(self.owner, *signer, CLMMError::InvalidPositionOwner);
+            return Ok(());
+        }
+
+        let token_account = position_token_account.ok_or(CLMMError::InvalidPositionOwner)?;
+        require_keys_eq!(
+            token_account.mint,
+            self.position_mint,
+            CLMMError::InvalidPositionOwner
+        );
+        require_keys_eq!(
+            token_account.owner,
+            *signer,
+            CLMMError::InvalidPositionOwner
+        );
+        require!(token_account.amount == 1, CLMMError::InvalidPositionOwner);
+        Ok(())
+    }
+}
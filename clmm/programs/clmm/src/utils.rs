@@ -1,11 +1,21 @@
 use crate::error::CLMMError;
 use anchor_lang::prelude::*;
 
+// In a module of its own because the macro expansion names `Result`, which the Anchor
+// prelude shadows here. The allowed lints fire inside the generated code.
+#[allow(clippy::assign_op_pattern, clippy::manual_div_ceil)]
+mod wide {
+    uint::construct_uint! {
+        pub struct U256(4);
+    }
+}
+use wide::U256;
+
 const Q64: u128 = 1 << 64;
-pub const TICK_SPACING: i32 = 10;
-const BASE_SQRT_PRICE_X64: u128 = Q64;
+// Pool fee rates are expressed out of this, i.e. in hundredths of a basis point
+pub const FEE_RATE_DENOMINATOR: u32 = 1_000_000;
+pub const MAX_FEE_RATE: u32 = 100_000;
 
-const SQRT_1_0001_X64: u128 = 18446758646477570048; // sqrt(1.0001) * 2^64
 pub const MIN_TICK: i32 = -443636;
 pub const MAX_TICK: i32 = -MIN_TICK;
 /// The minimum value that can be returned from #get_sqrt_price_at_tick. Equivalent to get_sqrt_price_at_tick(MIN_TICK)
@@ -13,21 +23,23 @@ pub const MIN_SQRT_PRICE_X64: u128 = 4295048016;
 /// The maximum value that can be returned from #get_sqrt_price_at_tick. Equivalent to get_sqrt_price_at_tick(MAX_TICK)
 pub const MAX_SQRT_PRICE_X64: u128 = 79226673521066979257578248091;
 const BIT_PRECISION: u32 = 16;
-
-pub fn integer_sqrt(value: u128) -> u64 {
-    if value == 0 {
-        return 0;
-    }
-
-    let mut x = value;
-    let mut y = (value + 1) / 2;
-
-    while y < x {
-        x = y;
-        y = (x + value / x) / 2;
+// Ticks held by one TickArray account, one bit each in its initialized bitmap
+pub const TICK_ARRAY_SIZE: usize = 64;
+
+pub fn check_deadline(deadline: Option<i64>) -> Result<()> {
+    if let Some(deadline) = deadline {
+        require!(
+            Clock::get()?.unix_timestamp <= deadline,
+            CLMMError::DeadlineExceeded
+        );
     }
+    Ok(())
+}
 
-    x as u64
+// Start index of the tick array that holds tick_index, rounding towards negative infinity
+pub fn tick_array_start_index(tick_index: i32, tick_spacing: u16) -> i32 {
+    let ticks_in_array = TICK_ARRAY_SIZE as i32 * tick_spacing as i32;
+    tick_index.div_euclid(ticks_in_array) * ticks_in_array
 }
 
 /// Convert a u64 price to sqrt_price_x64 format
@@ -40,7 +52,7 @@ pub fn price_to_sqrt_price_x64(price: u64) -> Result<u128> {
     let price_scaled = price as u128;
 
     let mut x = price_scaled;
-    let mut y = (price_scaled + 1) / 2;
+    let mut y = price_scaled.div_ceil(2);
 
     while y < x {
         x = y;
@@ -54,7 +66,7 @@ pub fn price_to_sqrt_price_x64(price: u64) -> Result<u128> {
 
 // formula = sqrt(log(1.0001^tick)) * 2^64
 pub fn tick_to_sqrt_price_x64(tick: i32) -> Result<u128> {
-    let abs_tick = tick.abs() as u32;
+    let abs_tick = tick.unsigned_abs();
     require!(abs_tick <= MAX_TICK as u32, CLMMError::TickUpperOverflow);
 
     // i = 0
@@ -143,14 +155,14 @@ pub fn tick_to_sqrt_price_x64(tick: i32) -> Result<u128> {
         ratio = u128::MAX / ratio;
     }
 
-    Ok(ratio as u128)
+    Ok(ratio)
 }
 
 // tick = log base(sqrt(1.0001) ( sqrt_price_x64 / Q64) )
 //to efficiently compute the above we find the log2 of above and divide by log(sqrt(1.0001))
 pub fn sqrt_price_x64_to_tick(sqrt_price_x64: u128) -> Result<i32> {
     require!(
-        sqrt_price_x64 >= MIN_SQRT_PRICE_X64 && sqrt_price_x64 < MAX_SQRT_PRICE_X64,
+        (MIN_SQRT_PRICE_X64..MAX_SQRT_PRICE_X64).contains(&sqrt_price_x64),
         CLMMError::SqrtPriceX64
     );
 
@@ -175,7 +187,7 @@ pub fn sqrt_price_x64_to_tick(sqrt_price_x64: u128) -> Result<i32> {
 
     while bit > 0 && precision < BIT_PRECISION {
         r *= r;
-        let is_r_more_than_two = r >> 127 as u32;
+        let is_r_more_than_two = r >> 127;
         r >>= 63 + is_r_more_than_two;
         log2p_fraction_x64 += bit * is_r_more_than_two as i128;
         bit >>= 1;
@@ -205,85 +217,77 @@ pub fn sqrt_price_x64_to_tick(sqrt_price_x64: u128) -> Result<i32> {
     })
 }
 
+// Token A held by liquidity between two sqrt prices: L * (upper - lower) * Q64 / (upper * lower).
+// The numerator overflows u128 for any realistic liquidity, so this runs in U256.
+pub fn amount_a_delta(
+    sqrt_price_lower_x64: u128,
+    sqrt_price_upper_x64: u128,
+    liquidity: u128,
+) -> Result<u128> {
+    let price_diff = sqrt_price_upper_x64
+        .checked_sub(sqrt_price_lower_x64)
+        .ok_or(CLMMError::ArithmeticOverflow)?;
+    let numerator = U256::from(liquidity)
+        .checked_mul(U256::from(price_diff))
+        .and_then(|product| product.checked_mul(U256::from(Q64)))
+        .ok_or(CLMMError::ArithmeticOverflow)?;
+    let denominator = U256::from(sqrt_price_upper_x64) * U256::from(sqrt_price_lower_x64);
+    let amount = numerator
+        .checked_div(denominator)
+        .ok_or(CLMMError::ArithmeticOverflow)?;
+    Ok(u128::try_from(amount).map_err(|_| CLMMError::ArithmeticOverflow)?)
+}
+
+// Token B held by liquidity between two sqrt prices: L * (upper - lower) / Q64
+pub fn amount_b_delta(
+    sqrt_price_lower_x64: u128,
+    sqrt_price_upper_x64: u128,
+    liquidity: u128,
+) -> Result<u128> {
+    let price_diff = sqrt_price_upper_x64
+        .checked_sub(sqrt_price_lower_x64)
+        .ok_or(CLMMError::ArithmeticOverflow)?;
+    let amount = U256::from(liquidity)
+        .checked_mul(U256::from(price_diff))
+        .ok_or(CLMMError::ArithmeticOverflow)?
+        >> 64;
+    Ok(u128::try_from(amount).map_err(|_| CLMMError::ArithmeticOverflow)?)
+}
+
 pub fn calculate_liquidity_amounts(
     sqrt_price_current_x64: u128,
     sqrt_price_lower_x64: u128,
     sqrt_price_upper_x64: u128,
     liquidity: u128,
 ) -> Result<(u64, u64)> {
-    let amount_a: u64;
-    let amount_b: u64;
-
-    if sqrt_price_current_x64 <= sqrt_price_lower_x64 {
-        // Token A only: amount_a = L * (upper - lower) * Q64 / (upper * lower)
-        let numerator = liquidity
-            .checked_mul(
-                sqrt_price_upper_x64
-                    .checked_sub(sqrt_price_lower_x64)
-                    .ok_or(CLMMError::ArithmeticOverflow)?,
-            )
-            .ok_or(CLMMError::ArithmeticOverflow)?
-            .checked_mul(Q64)
-            .ok_or(CLMMError::ArithmeticOverflow)?;
-
-        let denominator = sqrt_price_upper_x64
-            .checked_mul(sqrt_price_lower_x64)
-            .ok_or(CLMMError::ArithmeticOverflow)?;
-
-        amount_a = (numerator / denominator)
-            .try_into()
-            .map_err(|_| CLMMError::ArithmeticOverflow)?;
-        amount_b = 0;
+    let (amount_a, amount_b) = if sqrt_price_current_x64 <= sqrt_price_lower_x64 {
+        // Token A only
+        (
+            amount_a_delta(sqrt_price_lower_x64, sqrt_price_upper_x64, liquidity)?,
+            0,
+        )
     } else if sqrt_price_current_x64 >= sqrt_price_upper_x64 {
-        // Token B only: amount_b = L * (upper - lower) / Q64
-        amount_a = 0;
-        amount_b = (liquidity
-            .checked_mul(
-                sqrt_price_upper_x64
-                    .checked_sub(sqrt_price_lower_x64)
-                    .ok_or(CLMMError::ArithmeticOverflow)?,
-            )
-            .ok_or(CLMMError::ArithmeticOverflow)?
-            .checked_div(Q64)
-            .ok_or(CLMMError::ArithmeticOverflow)?)
-        .try_into()
-        .map_err(|_| CLMMError::ArithmeticOverflow)?;
+        // Token B only
+        (
+            0,
+            amount_b_delta(sqrt_price_lower_x64, sqrt_price_upper_x64, liquidity)?,
+        )
     } else {
-        // Both tokens:
-        // amount_a = L * (upper - current) * Q64 / (upper * current)
-        let numerator_a = liquidity
-            .checked_mul(
-                sqrt_price_upper_x64
-                    .checked_sub(sqrt_price_current_x64)
-                    .ok_or(CLMMError::ArithmeticOverflow)?,
-            )
-            .ok_or(CLMMError::ArithmeticOverflow)?
-            .checked_mul(Q64)
-            .ok_or(CLMMError::ArithmeticOverflow)?;
-
-        let denominator_a = sqrt_price_upper_x64
-            .checked_mul(sqrt_price_current_x64)
-            .ok_or(CLMMError::ArithmeticOverflow)?;
+        // Token A above the current price, token B below it
+        (
+            amount_a_delta(sqrt_price_current_x64, sqrt_price_upper_x64, liquidity)?,
+            amount_b_delta(sqrt_price_lower_x64, sqrt_price_current_x64, liquidity)?,
+        )
+    };
 
-        amount_a = (numerator_a / denominator_a)
+    Ok((
+        amount_a
             .try_into()
-            .map_err(|_| CLMMError::ArithmeticOverflow)?;
-
-        // amount_b = L * (current - lower) / Q64
-        amount_b = (liquidity
-            .checked_mul(
-                sqrt_price_current_x64
-                    .checked_sub(sqrt_price_lower_x64)
-                    .ok_or(CLMMError::ArithmeticOverflow)?,
-            )
-            .ok_or(CLMMError::ArithmeticOverflow)?
-            .checked_div(Q64)
-            .ok_or(CLMMError::ArithmeticOverflow)?)
-        .try_into()
-        .map_err(|_| CLMMError::ArithmeticOverflow)?;
-    }
-
-    Ok((amount_a, amount_b))
+            .map_err(|_| CLMMError::ArithmeticOverflow)?,
+        amount_b
+            .try_into()
+            .map_err(|_| CLMMError::ArithmeticOverflow)?,
+    ))
 }
 
 pub fn compute_swap_step(
@@ -291,83 +295,54 @@ pub fn compute_swap_step(
     sqrt_price_target_x64: u128,
     liquidity: u128,
     amount_remaining: u128,
+    fee_rate: u32,
     a_to_b: bool,
-) -> Result<(u128, u128, u128)> {
+) -> Result<(u128, u128, u128, u128)> {
     let next_price: u128;
     let amount_in: u128;
     let amount_out: u128;
 
+    // Only what is left after the fee moves the price
+    let fee_rate = fee_rate as u128;
+    let fee_rate_complement = (FEE_RATE_DENOMINATOR as u128)
+        .checked_sub(fee_rate)
+        .ok_or(CLMMError::ArithmeticOverflow)?;
+    let amount_remaining_less_fee = amount_remaining
+        .checked_mul(fee_rate_complement)
+        .ok_or(CLMMError::ArithmeticOverflow)?
+        / FEE_RATE_DENOMINATOR as u128;
+
     if a_to_b {
         // Calculate required input for full step
-        let price_diff = sqrt_price_current_x64
-            .checked_sub(sqrt_price_target_x64)
-            .ok_or(CLMMError::ArithmeticOverflow)?;
+        let required_in = amount_a_delta(sqrt_price_target_x64, sqrt_price_current_x64, liquidity)?;
 
-        let required_in = liquidity
-            .checked_mul(price_diff)
-            .ok_or(CLMMError::ArithmeticOverflow)?
-            .checked_mul(Q64)
-            .ok_or(CLMMError::ArithmeticOverflow)?
-            .checked_div(
-                sqrt_price_current_x64
-                    .checked_mul(sqrt_price_target_x64)
-                    .ok_or(CLMMError::ArithmeticOverflow)?,
-            )
-            .ok_or(CLMMError::ArithmeticOverflow)?;
-
-        if amount_remaining >= required_in {
+        if amount_remaining_less_fee >= required_in {
             // Full step
             next_price = sqrt_price_target_x64;
             amount_in = required_in;
         } else {
-            // Partial step
-            let numerator = liquidity
-                .checked_mul(sqrt_price_current_x64)
-                .ok_or(CLMMError::ArithmeticOverflow)?
-                .checked_mul(sqrt_price_current_x64)
+            // Partial step: L * current * Q64 / (L * Q64 + amount * current)
+            let liquidity_x64 = U256::from(liquidity) << 64;
+            let numerator = liquidity_x64
+                .checked_mul(U256::from(sqrt_price_current_x64))
                 .ok_or(CLMMError::ArithmeticOverflow)?;
-
-            let denominator = liquidity
-                .checked_mul(sqrt_price_current_x64)
-                .ok_or(CLMMError::ArithmeticOverflow)?
+            let denominator = liquidity_x64
                 .checked_add(
-                    amount_remaining
-                        .checked_mul(sqrt_price_current_x64)
-                        .ok_or(CLMMError::ArithmeticOverflow)?
-                        .checked_div(Q64)
-                        .ok_or(CLMMError::ArithmeticOverflow)?,
+                    U256::from(amount_remaining_less_fee) * U256::from(sqrt_price_current_x64),
                 )
                 .ok_or(CLMMError::ArithmeticOverflow)?;
 
-            next_price = numerator
-                .checked_div(denominator)
-                .ok_or(CLMMError::ArithmeticOverflow)?;
-            amount_in = amount_remaining;
+            next_price = u128::try_from(numerator / denominator)
+                .map_err(|_| CLMMError::ArithmeticOverflow)?;
+            amount_in = amount_remaining_less_fee;
         }
 
-        // Calculate output
-        amount_out = liquidity
-            .checked_mul(
-                sqrt_price_current_x64
-                    .checked_sub(next_price)
-                    .ok_or(CLMMError::ArithmeticOverflow)?,
-            )
-            .ok_or(CLMMError::ArithmeticOverflow)?
-            .checked_div(Q64)
-            .ok_or(CLMMError::ArithmeticOverflow)?;
+        amount_out = amount_b_delta(next_price, sqrt_price_current_x64, liquidity)?;
     } else {
         // B to A swap
-        let price_diff = sqrt_price_target_x64
-            .checked_sub(sqrt_price_current_x64)
-            .ok_or(CLMMError::ArithmeticOverflow)?;
+        let required_in = amount_b_delta(sqrt_price_current_x64, sqrt_price_target_x64, liquidity)?;
 
-        let required_in = liquidity
-            .checked_mul(price_diff)
-            .ok_or(CLMMError::ArithmeticOverflow)?
-            .checked_div(Q64)
-            .ok_or(CLMMError::ArithmeticOverflow)?;
-
-        if amount_remaining >= required_in {
+        if amount_remaining_less_fee >= required_in {
             // Full step
             next_price = sqrt_price_target_x64;
             amount_in = required_in;
@@ -375,32 +350,81 @@ pub fn compute_swap_step(
             // Partial step
             next_price = sqrt_price_current_x64
                 .checked_add(
-                    amount_remaining
+                    amount_remaining_less_fee
                         .checked_mul(Q64)
                         .ok_or(CLMMError::ArithmeticOverflow)?
                         .checked_div(liquidity)
                         .ok_or(CLMMError::ArithmeticOverflow)?,
                 )
                 .ok_or(CLMMError::ArithmeticOverflow)?;
-            amount_in = amount_remaining;
+            amount_in = amount_remaining_less_fee;
         }
 
-        // Calculate output
-        let price_diff_out = next_price
-            .checked_sub(sqrt_price_current_x64)
-            .ok_or(CLMMError::ArithmeticOverflow)?;
-        amount_out = liquidity
-            .checked_mul(price_diff_out)
+        amount_out = amount_a_delta(sqrt_price_current_x64, next_price, liquidity)?;
+    }
+
+    // A step that stops short of the target consumed all of the input, so the rest is fee
+    let fee_amount = if next_price == sqrt_price_target_x64 {
+        amount_in
+            .checked_mul(fee_rate)
             .ok_or(CLMMError::ArithmeticOverflow)?
-            .checked_mul(Q64)
+            .div_ceil(fee_rate_complement)
+    } else {
+        amount_remaining
+            .checked_sub(amount_in)
             .ok_or(CLMMError::ArithmeticOverflow)?
-            .checked_div(
-                sqrt_price_current_x64
-                    .checked_mul(next_price)
-                    .ok_or(CLMMError::ArithmeticOverflow)?,
-            )
-            .ok_or(CLMMError::ArithmeticOverflow)?;
+    };
+
+    Ok((next_price, amount_in, amount_out, fee_amount))
+}
+
+// Fee earned per unit of liquidity, in Q64.64
+pub fn fee_growth_x64(fee_amount: u128, liquidity: u128) -> Result<u128> {
+    if liquidity == 0 {
+        return Ok(0);
     }
+    let fee_growth = fee_amount
+        .checked_mul(Q64)
+        .ok_or(CLMMError::ArithmeticOverflow)?
+        / liquidity;
+    Ok(fee_growth)
+}
 
-    Ok((next_price, amount_in, amount_out))
+// Fee growth per unit of liquidity earned between tick_lower and tick_upper, for one token.
+// Everything wraps, only differences between two readings are meaningful.
+pub fn fee_growth_inside_x64(
+    current_tick: i32,
+    tick_lower: i32,
+    tick_upper: i32,
+    fee_growth_global_x64: u128,
+    fee_growth_outside_lower_x64: u128,
+    fee_growth_outside_upper_x64: u128,
+) -> u128 {
+    let fee_growth_below_x64 = if current_tick >= tick_lower {
+        fee_growth_outside_lower_x64
+    } else {
+        fee_growth_global_x64.wrapping_sub(fee_growth_outside_lower_x64)
+    };
+    let fee_growth_above_x64 = if current_tick < tick_upper {
+        fee_growth_outside_upper_x64
+    } else {
+        fee_growth_global_x64.wrapping_sub(fee_growth_outside_upper_x64)
+    };
+    fee_growth_global_x64
+        .wrapping_sub(fee_growth_below_x64)
+        .wrapping_sub(fee_growth_above_x64)
+}
+
+// Tokens earned by liquidity since fee growth inside its range was last checkpointed
+pub fn fees_owed(
+    liquidity: u128,
+    fee_growth_inside_x64: u128,
+    fee_growth_inside_last_x64: u128,
+) -> Result<u64> {
+    let fee_growth_delta_x64 = fee_growth_inside_x64.wrapping_sub(fee_growth_inside_last_x64);
+    let fees = liquidity
+        .checked_mul(fee_growth_delta_x64)
+        .ok_or(CLMMError::ArithmeticOverflow)?
+        / Q64;
+    Ok(fees.try_into().map_err(|_| CLMMError::ArithmeticOverflow)?)
 }
@@ -1,11 +1,34 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { Clmm } from "../target/types/clmm";
-import { createMint } from "@solana/spl-token";
+import {
+  createMint,
+  getAccount,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+  getAssociatedTokenAddressSync,
+  getMint,
+  transfer,
+} from "@solana/spl-token";
 import { assert } from "chai";
-import { BASE_SQRT_PRICE_X64, tickToSqrtPriceX64 } from "./utils";
+import {
+  amountADelta,
+  amountBDelta,
+  BASE_SQRT_PRICE_X64,
+  tickToSqrtPriceX64,
+} from "./utils";
 import { sqrt } from "bn-sqrt";
 
+// 0.3%, in hundredths of a basis point
+const FEE_RATE = 3000;
+const TICK_SPACING = 10;
+
+const u32ToLeBytes = (value: number): Buffer => {
+  const buffer = Buffer.allocUnsafe(4);
+  buffer.writeUInt32LE(value, 0);
+  return buffer;
+};
+
 describe("clmm", () => {
   // Configure the client to use the local cluster.
   anchor.setProvider(anchor.AnchorProvider.env());
@@ -17,15 +40,269 @@ describe("clmm", () => {
   let poolAuthorityBump: Number;
   let tokenAMint: anchor.web3.PublicKey;
   let tokenBMint: anchor.web3.PublicKey;
-  let lpMintPda: anchor.web3.PublicKey;
-  let lpMintBump: Number;
   let vaultAPda: anchor.web3.PublicKey;
   let vaultABump: Number;
   let vaultBPda: anchor.web3.PublicKey;
   let vaultBBump: Number;
   let poolPda: anchor.web3.PublicKey;
   let poolBump: Number;
-  let lpCreatorTokenAccount: anchor.web3.PublicKey;
+  const [feeTierConfigPda] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("fee_tier_config")],
+    program.programId
+  );
+  const [programDataPda] = anchor.web3.PublicKey.findProgramAddressSync(
+    [program.programId.toBytes()],
+    new anchor.web3.PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+  );
+
+  const fundedKeypair = async (): Promise<anchor.web3.Keypair> => {
+    const keypair = anchor.web3.Keypair.generate();
+    const signature = await provider.connection.requestAirdrop(
+      keypair.publicKey,
+      anchor.web3.LAMPORTS_PER_SOL
+    );
+    await provider.connection.confirmTransaction(signature);
+    return keypair;
+  };
+
+  const TICKS_IN_ARRAY = 64 * TICK_SPACING;
+  const i32ToLeBytes = (value: number): Buffer => {
+    const buffer = Buffer.allocUnsafe(4);
+    buffer.writeInt32LE(value, 0);
+    return buffer;
+  };
+
+  type TestPool = {
+    mintA: anchor.web3.PublicKey;
+    mintB: anchor.web3.PublicKey;
+    pool: anchor.web3.PublicKey;
+    vaultA: anchor.web3.PublicKey;
+    vaultB: anchor.web3.PublicKey;
+  };
+
+  // Pool at price 1, i.e. tick 0, on a fresh pair of mints the provider wallet holds plenty of
+  const createPool = async (): Promise<TestPool> => {
+    const [mintA, mintB] = [
+      await createMint(
+        provider.connection,
+        poolCreator.payer,
+        poolCreator.publicKey,
+        null,
+        6
+      ),
+      await createMint(
+        provider.connection,
+        poolCreator.payer,
+        poolCreator.publicKey,
+        null,
+        6
+      ),
+    ];
+    const [pool] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("pool"),
+        mintA.toBytes(),
+        mintB.toBytes(),
+        u32ToLeBytes(FEE_RATE),
+      ],
+      program.programId
+    );
+    const [vaultA] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("vault_token"), pool.toBytes(), Buffer.from("A")],
+      program.programId
+    );
+    const [vaultB] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("vault_token"), pool.toBytes(), Buffer.from("B")],
+      program.programId
+    );
+
+    await program.methods
+      .initializePool(new anchor.BN(1), FEE_RATE, TICK_SPACING)
+      .accounts({
+        initializer: poolCreator.publicKey,
+        tokenAMint: mintA,
+        tokenBMint: mintB,
+      })
+      .rpc();
+
+    const testPool = { mintA, mintB, pool, vaultA, vaultB };
+    await fundTokenAccounts(testPool, poolCreator.publicKey);
+    return testPool;
+  };
+
+  // Creates owner's token accounts for both of the pool's mints and mints to them
+  const fundTokenAccounts = async (
+    testPool: TestPool,
+    owner: anchor.web3.PublicKey
+  ) => {
+    for (const mint of [testPool.mintA, testPool.mintB]) {
+      const tokenAccount = await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        poolCreator.payer,
+        mint,
+        owner
+      );
+      await mintTo(
+        provider.connection,
+        poolCreator.payer,
+        mint,
+        tokenAccount.address,
+        poolCreator.publicKey,
+        1_000_000_000_000
+      );
+    }
+  };
+
+  // Start index of the tick array holding tickIndex
+  const tickArrayStart = (tickIndex: number): number =>
+    Math.floor(tickIndex / TICKS_IN_ARRAY) * TICKS_IN_ARRAY;
+
+  const tickArrayAddress = (
+    testPool: TestPool,
+    tickIndex: number
+  ): anchor.web3.PublicKey =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("tick_array"),
+        testPool.pool.toBytes(),
+        i32ToLeBytes(tickArrayStart(tickIndex)),
+      ],
+      program.programId
+    )[0];
+
+  // Initializes the tick arrays holding each of tickIndices that don't exist yet
+  const initializeTickArrays = async (
+    testPool: TestPool,
+    tickIndices: number[]
+  ) => {
+    for (const tickIndex of tickIndices) {
+      const tickArray = tickArrayAddress(testPool, tickIndex);
+      if (await provider.connection.getAccountInfo(tickArray)) {
+        continue;
+      }
+      await program.methods
+        .initializeTickArray(tickArrayStart(tickIndex))
+        .accountsPartial({
+          payer: poolCreator.publicKey,
+          tokenAMint: testPool.mintA,
+          tokenBMint: testPool.mintB,
+          pool: testPool.pool,
+          tickArray,
+        })
+        .rpc();
+    }
+  };
+
+  const fetchTick = async (testPool: TestPool, tickIndex: number) => {
+    const tickArray = await program.account.tickArray.fetch(
+      tickArrayAddress(testPool, tickIndex)
+    );
+    return tickArray.ticks[
+      (tickIndex - tickArrayStart(tickIndex)) / TICK_SPACING
+    ];
+  };
+
+  const positionAddress = (
+    testPool: TestPool,
+    owner: anchor.web3.PublicKey,
+    tickLower: number,
+    tickUpper: number
+  ): anchor.web3.PublicKey =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("position"),
+        testPool.pool.toBytes(),
+        owner.toBytes(),
+        i32ToLeBytes(tickLower),
+        i32ToLeBytes(tickUpper),
+      ],
+      program.programId
+    )[0];
+
+  // Accounts add_liquidity, withdraw_liquidity and collect_fees share for a position
+  const positionAccounts = (
+    testPool: TestPool,
+    position: anchor.web3.PublicKey,
+    tickLower: number,
+    tickUpper: number,
+    positionTokenAccount: anchor.web3.PublicKey | null = null
+  ) => ({
+    tokenMintA: testPool.mintA,
+    tokenMintB: testPool.mintB,
+    pool: testPool.pool,
+    position,
+    positionTokenAccount,
+    tickArrayLower: tickArrayAddress(testPool, tickLower),
+    tickArrayUpper: tickArrayAddress(testPool, tickUpper),
+  });
+
+  // Opens a position for the provider wallet, without an NFT, and deposits liquidity into it
+  const openPositionWithLiquidity = async (
+    testPool: TestPool,
+    tickLower: number,
+    tickUpper: number,
+    liquidity: anchor.BN
+  ): Promise<anchor.web3.PublicKey> => {
+    await initializeTickArrays(testPool, [tickLower, tickUpper]);
+    await program.methods
+      .openPosition(tickLower, tickUpper)
+      .accountsPartial({
+        owner: poolCreator.publicKey,
+        tokenMintA: testPool.mintA,
+        tokenMintB: testPool.mintB,
+        pool: testPool.pool,
+        positionMint: null,
+        positionTokenAccount: null,
+      })
+      .rpc();
+
+    const position = positionAddress(
+      testPool,
+      poolCreator.publicKey,
+      tickLower,
+      tickUpper
+    );
+    await program.methods
+      .addLiquidity(liquidity, null)
+      .accountsPartial({
+        liquidityProvider: poolCreator.publicKey,
+        ...positionAccounts(testPool, position, tickLower, tickUpper),
+      })
+      .rpc();
+    return position;
+  };
+
+  // Swaps from the provider wallet, passing the tick arrays holding tickIndices in swap order
+  const swap = async (
+    testPool: TestPool,
+    amountIn: number,
+    aToB: boolean,
+    sqrtPriceLimitX64: anchor.BN | null,
+    tickIndices: number[]
+  ) =>
+    program.methods
+      .swap(new anchor.BN(amountIn), aToB, sqrtPriceLimitX64, null, null)
+      .accountsPartial({
+        user: poolCreator.publicKey,
+        tokenMintA: testPool.mintA,
+        tokenMintB: testPool.mintB,
+        pool: testPool.pool,
+      })
+      .remainingAccounts(
+        tickIndices.map((tickIndex) => ({
+          pubkey: tickArrayAddress(testPool, tickIndex),
+          isSigner: false,
+          isWritable: true,
+        }))
+      )
+      .rpc();
+
+  const tokenBalance = async (
+    tokenAccount: anchor.web3.PublicKey
+  ): Promise<anchor.BN> =>
+    new anchor.BN(
+      (await getAccount(provider.connection, tokenAccount)).amount.toString()
+    );
 
   before(async () => {
     // Create token A mint,assuming tokenA as base as Sol
@@ -46,41 +323,189 @@ describe("clmm", () => {
       6
     );
 
+    [poolPda, poolBump] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("pool"),
+        tokenAMint.toBytes(),
+        tokenBMint.toBytes(),
+        u32ToLeBytes(FEE_RATE),
+      ],
+      program.programId
+    );
+
     [poolAuthorityPda, poolAuthorityBump] =
       anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from("authority"), tokenAMint.toBytes(), tokenBMint.toBytes()],
+        [Buffer.from("authority"), poolPda.toBytes()],
         program.programId
       );
 
-    [lpMintPda, lpMintBump] = anchor.web3.PublicKey.findProgramAddressSync(
-      [Buffer.from("lp_mint"), tokenAMint.toBytes(), tokenBMint.toBytes()],
-      program.programId
-    );
-
     [vaultAPda, vaultABump] = anchor.web3.PublicKey.findProgramAddressSync(
-      [
-        Buffer.from("vault_token"),
-        tokenAMint.toBytes(),
-        tokenBMint.toBytes(),
-        Buffer.from("A"),
-      ],
+      [Buffer.from("vault_token"), poolPda.toBytes(), Buffer.from("A")],
       program.programId
     );
 
     [vaultBPda, vaultBBump] = anchor.web3.PublicKey.findProgramAddressSync(
-      [
-        Buffer.from("vault_token"),
-        tokenAMint.toBytes(),
-        tokenBMint.toBytes(),
-        Buffer.from("B"),
-      ],
+      [Buffer.from("vault_token"), poolPda.toBytes(), Buffer.from("B")],
       program.programId
     );
+  });
 
-    [poolPda, poolBump] = anchor.web3.PublicKey.findProgramAddressSync(
-      [Buffer.from("pool"), tokenAMint.toBytes(), tokenBMint.toBytes()],
-      program.programId
-    );
+  describe("Fee Tiers", () => {
+    it("should fail to initialize the fee tier config when not the upgrade authority", async () => {
+      const stranger = await fundedKeypair();
+
+      try {
+        await program.methods
+          .initializeFeeTierConfig()
+          .accountsPartial({
+            admin: stranger.publicKey,
+            programData: programDataPda,
+          })
+          .signers([stranger])
+          .rpc();
+
+        assert.fail("Expected transaction to fail");
+      } catch (error) {
+        assert.include(
+          error.message,
+          "Signer is not allowed to perform this action"
+        );
+      }
+      assert.isNull(await provider.connection.getAccountInfo(feeTierConfigPda));
+    });
+
+    it("should initialize the fee tier config with the upgrade authority as admin", async () => {
+      await program.methods
+        .initializeFeeTierConfig()
+        .accountsPartial({
+          admin: poolCreator.publicKey,
+          programData: programDataPda,
+        })
+        .rpc();
+
+      const config = await program.account.feeTierConfig.fetch(
+        feeTierConfigPda
+      );
+      assert.equal(config.admin.toString(), poolCreator.publicKey.toString());
+      assert.equal(config.feeTiers.length, 0);
+    });
+
+    it("should add fee tiers", async () => {
+      await program.methods
+        .addFeeTier(FEE_RATE, TICK_SPACING)
+        .accounts({ admin: poolCreator.publicKey })
+        .rpc();
+      await program.methods
+        .addFeeTier(500, 1)
+        .accounts({ admin: poolCreator.publicKey })
+        .rpc();
+
+      const config = await program.account.feeTierConfig.fetch(
+        feeTierConfigPda
+      );
+      assert.deepEqual(
+        config.feeTiers.map((tier) => [tier.feeRate, tier.tickSpacing]),
+        [
+          [FEE_RATE, TICK_SPACING],
+          [500, 1],
+        ]
+      );
+    });
+
+    it("should fail to add a fee tier twice", async () => {
+      try {
+        await program.methods
+          .addFeeTier(FEE_RATE, 60)
+          .accounts({ admin: poolCreator.publicKey })
+          .rpc();
+
+        assert.fail("Expected transaction to fail");
+      } catch (error) {
+        assert.include(
+          error.message,
+          "A fee tier with this fee rate already exists"
+        );
+      }
+    });
+
+    it("should fail to add a fee tier with a fee rate above the maximum", async () => {
+      try {
+        await program.methods
+          .addFeeTier(100_001, 1)
+          .accounts({ admin: poolCreator.publicKey })
+          .rpc();
+
+        assert.fail("Expected transaction to fail");
+      } catch (error) {
+        assert.include(error.message, "Fee rate is above the maximum allowed");
+      }
+    });
+
+    it("should fail to add a fee tier when not the admin", async () => {
+      const stranger = await fundedKeypair();
+
+      try {
+        await program.methods
+          .addFeeTier(10_000, 200)
+          .accounts({ admin: stranger.publicKey })
+          .signers([stranger])
+          .rpc();
+
+        assert.fail("Expected transaction to fail");
+      } catch (error) {
+        assert.include(
+          error.message,
+          "Signer is not allowed to perform this action"
+        );
+      }
+    });
+
+    it("should remove a fee tier", async () => {
+      await program.methods
+        .removeFeeTier(500)
+        .accounts({ admin: poolCreator.publicKey })
+        .rpc();
+
+      const config = await program.account.feeTierConfig.fetch(
+        feeTierConfigPda
+      );
+      assert.equal(config.feeTiers.length, 1);
+      assert.equal(config.feeTiers[0].feeRate, FEE_RATE);
+    });
+
+    it("should hand the admin role over and back", async () => {
+      const newAdmin = await fundedKeypair();
+
+      await program.methods
+        .setFeeTierAdmin(newAdmin.publicKey)
+        .accounts({ admin: poolCreator.publicKey })
+        .rpc();
+      let config = await program.account.feeTierConfig.fetch(feeTierConfigPda);
+      assert.equal(config.admin.toString(), newAdmin.publicKey.toString());
+
+      // The previous admin has lost the role
+      try {
+        await program.methods
+          .setFeeTierAdmin(poolCreator.publicKey)
+          .accounts({ admin: poolCreator.publicKey })
+          .rpc();
+
+        assert.fail("Expected transaction to fail");
+      } catch (error) {
+        assert.include(
+          error.message,
+          "Signer is not allowed to perform this action"
+        );
+      }
+
+      await program.methods
+        .setFeeTierAdmin(poolCreator.publicKey)
+        .accounts({ admin: newAdmin.publicKey })
+        .signers([newAdmin])
+        .rpc();
+      config = await program.account.feeTierConfig.fetch(feeTierConfigPda);
+      assert.equal(config.admin.toString(), poolCreator.publicKey.toString());
+    });
   });
 
   describe("Initialize Pool", () => {
@@ -91,7 +516,7 @@ describe("clmm", () => {
         .mul(BASE_SQRT_PRICE_X64)
         .toString();
       const tx = await program.methods
-        .initializePool(currentPrice)
+        .initializePool(currentPrice, FEE_RATE, TICK_SPACING)
         .accounts({
           initializer: poolCreator.publicKey,
           tokenAMint: tokenAMint,
@@ -111,13 +536,15 @@ describe("clmm", () => {
       );
       assert.equal(poolAccount.vaultA.toString(), vaultAPda.toString());
       assert.equal(poolAccount.vaultB.toString(), vaultBPda.toString());
-      assert.equal(poolAccount.lpMint.toString(), lpMintPda.toString());
 
       assert.equal(poolAccount.activeLiquidity.toString(), "0");
-      assert.equal(poolAccount.totalLpIssued.toString(), "0");
 
       assert.equal(poolAccount.sqrtPriceX64.toString(), currentPriceSqrtX64);
       assert.equal(poolAccount.currentTick, 0);
+      assert.equal(poolAccount.feeRate, FEE_RATE);
+      assert.equal(poolAccount.tickSpacing, TICK_SPACING);
+      assert.equal(poolAccount.feeGrowthGlobalAX64.toString(), "0");
+      assert.equal(poolAccount.feeGrowthGlobalBX64.toString(), "0");
 
       // Verify vault accounts were created
       const vaultAInfo = await provider.connection.getAccountInfo(vaultAPda);
@@ -125,15 +552,12 @@ describe("clmm", () => {
 
       assert.isNotNull(vaultAInfo);
       assert.isNotNull(vaultBInfo);
-
-      const lpMintInfo = await provider.connection.getAccountInfo(lpMintPda);
-      assert.isNotNull(lpMintInfo);
     });
 
     it("should fail to initialize pool with same token mints", async () => {
       try {
         await program.methods
-          .initializePool(new anchor.BN(100))
+          .initializePool(new anchor.BN(100), FEE_RATE, TICK_SPACING)
           .accounts({
             initializer: poolCreator.publicKey,
             tokenAMint: tokenAMint,
@@ -148,10 +572,30 @@ describe("clmm", () => {
       }
     });
 
+    it("should fail to initialize pool on a fee tier that isn't listed", async () => {
+      try {
+        await program.methods
+          .initializePool(new anchor.BN(100), 500, 1)
+          .accounts({
+            initializer: poolCreator.publicKey,
+            tokenAMint: tokenAMint,
+            tokenBMint: tokenBMint,
+          })
+          .rpc();
+
+        assert.fail("Expected transaction to fail");
+      } catch (error) {
+        assert.include(
+          error.message,
+          "Fee rate and tick spacing are not an allowed fee tier"
+        );
+      }
+    });
+
     it("should fail to initialize pool twice", async () => {
       try {
         await program.methods
-          .initializePool(new anchor.BN(200))
+          .initializePool(new anchor.BN(200), FEE_RATE, TICK_SPACING)
           .accounts({
             initializer: poolCreator.publicKey,
             tokenAMint: tokenAMint,
@@ -168,286 +612,887 @@ describe("clmm", () => {
     });
   });
 
-  describe("Initialize Tick", () => {
-    const i32ToLeBytes = (value: number): Buffer => {
-      const buffer = Buffer.allocUnsafe(4);
-      buffer.writeInt32LE(value, 0);
-      return buffer;
-    };
+  describe("Initialize Tick Array", () => {
+    const tickArrayPda = (startTickIndex: number) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("tick_array"),
+          poolPda.toBytes(),
+          i32ToLeBytes(startTickIndex),
+        ],
+        program.programId
+      );
 
-    it("should successfully initialize a tick with positive index", async () => {
-      const tickIndex = 100;
+    it("should successfully initialize a tick array", async () => {
+      const startTickIndex = 0;
 
       const tx = await program.methods
-        .initializeTick(tickIndex)
-        .accounts({
+        .initializeTickArray(startTickIndex)
+        .accountsPartial({
           payer: poolCreator.publicKey,
           tokenAMint: tokenAMint,
           tokenBMint: tokenBMint,
+          pool: poolPda,
         })
         .rpc();
 
-      console.log("Initialize tick transaction signature:", tx);
+      console.log("Initialize tick array transaction signature:", tx);
 
-      const [tickPda, tickBump] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from("tick"), poolPda.toBytes(), i32ToLeBytes(tickIndex)],
-        program.programId
-      );
+      const [tickArray, tickArrayBump] = tickArrayPda(startTickIndex);
+      const tickArrayAccount = await program.account.tickArray.fetch(tickArray);
 
-      const tickAccount = await program.account.tick.fetch(tickPda);
-
-      assert.equal(tickAccount.index, tickIndex);
-      assert.equal(tickAccount.liquidityNet.toString(), "0");
-      assert.equal(tickAccount.bump, tickBump);
-
-      // Verify sqrt_price_x64 is calculated correctly
-      const expectedSqrtPrice = tickToSqrtPriceX64(tickIndex);
-      assert.equal(
-        tickAccount.sqrtPriceX64.toString(),
-        expectedSqrtPrice.toString()
-      );
+      assert.equal(tickArrayAccount.pool.toString(), poolPda.toString());
+      assert.equal(tickArrayAccount.startTickIndex, startTickIndex);
+      assert.equal(tickArrayAccount.bump, tickArrayBump);
+      // No position uses any of its ticks yet
+      assert.equal(tickArrayAccount.initializedBitmap.toString(), "0");
+      assert.equal(tickArrayAccount.ticks.length, 64);
+      assert.equal(tickArrayAccount.ticks[0].liquidityGross.toString(), "0");
     });
 
-    it("should successfully initialize a tick with negative index", async () => {
-      const tickIndex = -50;
-
-      const [tickPda, tickBump] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from("tick"), poolPda.toBytes(), i32ToLeBytes(tickIndex)],
-        program.programId
-      );
+    it("should successfully initialize a tick array with negative start", async () => {
+      const startTickIndex = -TICKS_IN_ARRAY;
+      const [tickArray] = tickArrayPda(startTickIndex);
 
-      const tx = await program.methods
-        .initializeTick(tickIndex)
+      await program.methods
+        .initializeTickArray(startTickIndex)
         .accountsStrict({
           payer: poolCreator.publicKey,
           tokenAMint: tokenAMint,
           tokenBMint: tokenBMint,
           pool: poolPda,
-          tick: tickPda,
+          tickArray,
           systemProgram: anchor.web3.SystemProgram.programId,
         })
         .rpc();
 
-      console.log("Initialize negative tick transaction signature:", tx);
-      const tickAccount = await program.account.tick.fetch(tickPda);
+      const tickArrayAccount = await program.account.tickArray.fetch(tickArray);
+      assert.equal(tickArrayAccount.startTickIndex, startTickIndex);
+    });
+
+    it("should fail to initialize a tick array with unaligned start", async () => {
+      const startTickIndex = TICK_SPACING;
 
-      assert.equal(tickAccount.index, tickIndex);
-      assert.equal(tickAccount.liquidityNet.toString(), "0");
-      assert.equal(tickAccount.bump, tickBump);
+      try {
+        await program.methods
+          .initializeTickArray(startTickIndex)
+          .accountsPartial({
+            payer: poolCreator.publicKey,
+            tokenAMint: tokenAMint,
+            tokenBMint: tokenBMint,
+            pool: poolPda,
+          })
+          .rpc();
 
-      const expectedSqrtPrice = tickToSqrtPriceX64(tickIndex);
-      assert.equal(
-        tickAccount.sqrtPriceX64.toString(),
-        expectedSqrtPrice.toString()
-      );
+        assert.fail("Expected transaction to fail");
+      } catch (error) {
+        assert.include(
+          error.message,
+          "Tick array start index must be a multiple of the ticks covered by one array"
+        );
+      }
     });
 
-    it("should successfully initialize a tick with zero index", async () => {
-      const tickIndex = 0;
-
-      const [tickPda, tickBump] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from("tick"), poolPda.toBytes(), i32ToLeBytes(tickIndex)],
-        program.programId
-      );
+    it("should fail to initialize the same tick array twice", async () => {
+      const startTickIndex = TICKS_IN_ARRAY;
 
-      const tx = await program.methods
-        .initializeTick(tickIndex)
-        .accounts({
+      await program.methods
+        .initializeTickArray(startTickIndex)
+        .accountsPartial({
           payer: poolCreator.publicKey,
           tokenAMint: tokenAMint,
           tokenBMint: tokenBMint,
+          pool: poolPda,
         })
         .rpc();
 
-      console.log("Initialize zero tick transaction signature:", tx);
+      try {
+        await program.methods
+          .initializeTickArray(startTickIndex)
+          .accountsPartial({
+            payer: poolCreator.publicKey,
+            tokenAMint: tokenAMint,
+            tokenBMint: tokenBMint,
+            pool: poolPda,
+          })
+          .rpc();
+
+        assert.fail("Expected transaction to fail");
+      } catch (error) {
+        assert.include(error.message.toLowerCase(), "account");
+      }
+    });
+  });
+
+  describe("Open Position", () => {
+    const positionPda = (tickLower: number, tickUpper: number) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("position"),
+          poolPda.toBytes(),
+          poolCreator.publicKey.toBytes(),
+          i32ToLeBytes(tickLower),
+          i32ToLeBytes(tickUpper),
+        ],
+        program.programId
+      );
 
-      const tickAccount = await program.account.tick.fetch(tickPda);
+    it("should open a position without an NFT", async () => {
+      const [positionAddress, positionBump] = positionPda(-100, 100);
 
-      assert.equal(tickAccount.index, tickIndex);
-      assert.equal(tickAccount.liquidityNet.toString(), "0");
-      assert.equal(tickAccount.bump, tickBump);
+      await program.methods
+        .openPosition(-100, 100)
+        .accountsPartial({
+          owner: poolCreator.publicKey,
+          tokenMintA: tokenAMint,
+          tokenMintB: tokenBMint,
+          pool: poolPda,
+          positionMint: null,
+          positionTokenAccount: null,
+        })
+        .rpc();
 
-      // For tick 0: sqrt_price_x64 should equal BASE_SQRT_PRICE_X64
+      const position = await program.account.position.fetch(positionAddress);
+      assert.equal(position.owner.toString(), poolCreator.publicKey.toString());
+      assert.equal(position.pool.toString(), poolPda.toString());
       assert.equal(
-        tickAccount.sqrtPriceX64.toString(),
-        BASE_SQRT_PRICE_X64.toString()
+        position.positionMint.toString(),
+        anchor.web3.PublicKey.default.toString()
       );
+      assert.equal(position.tickLower, -100);
+      assert.equal(position.tickUpper, 100);
+      assert.equal(position.liquidity.toString(), "0");
+      assert.equal(position.tokensOwedA.toString(), "0");
+      assert.equal(position.tokensOwedB.toString(), "0");
+      assert.equal(position.bump, positionBump);
     });
 
-    it("should initialize multiple different ticks successfully", async () => {
-      const tickIndices = [200, -200, 1000, -1000];
+    it("should open a position with a 1-of-1 NFT", async () => {
+      const [positionAddress] = positionPda(-200, 200);
+      const positionMint = anchor.web3.Keypair.generate();
+      const positionTokenAccount = getAssociatedTokenAddressSync(
+        positionMint.publicKey,
+        poolCreator.publicKey
+      );
+
+      await program.methods
+        .openPosition(-200, 200)
+        .accountsPartial({
+          owner: poolCreator.publicKey,
+          tokenMintA: tokenAMint,
+          tokenMintB: tokenBMint,
+          pool: poolPda,
+          positionMint: positionMint.publicKey,
+          positionTokenAccount,
+        })
+        .signers([positionMint])
+        .rpc();
 
-      for (const tickIndex of tickIndices) {
-        const [tickPda, tickBump] =
-          anchor.web3.PublicKey.findProgramAddressSync(
-            [Buffer.from("tick"), poolPda.toBytes(), i32ToLeBytes(tickIndex)],
-            program.programId
-          );
+      const position = await program.account.position.fetch(positionAddress);
+      assert.equal(
+        position.positionMint.toString(),
+        positionMint.publicKey.toString()
+      );
 
-        const tx = await program.methods
-          .initializeTick(tickIndex)
-          .accountsStrict({
-            payer: poolCreator.publicKey,
-            tokenAMint: tokenAMint,
-            tokenBMint: tokenBMint,
+      const mint = await getMint(provider.connection, positionMint.publicKey);
+      assert.equal(mint.supply.toString(), "1");
+      assert.equal(mint.decimals, 0);
+      assert.isNull(mint.mintAuthority);
+
+      const tokenAccount = await getAccount(
+        provider.connection,
+        positionTokenAccount
+      );
+      assert.equal(tokenAccount.amount.toString(), "1");
+    });
+
+    it("should fail to open a position with unaligned ticks", async () => {
+      try {
+        await program.methods
+          .openPosition(-105, 100)
+          .accountsPartial({
+            owner: poolCreator.publicKey,
+            tokenMintA: tokenAMint,
+            tokenMintB: tokenBMint,
             pool: poolPda,
-            tick: tickPda,
-            systemProgram: anchor.web3.SystemProgram.programId,
+            positionMint: null,
+            positionTokenAccount: null,
           })
           .rpc();
 
-        console.log(`Initialize tick ${tickIndex} transaction signature:`, tx);
-
-        const tickAccount = await program.account.tick.fetch(tickPda);
-        assert.equal(tickAccount.index, tickIndex);
-        assert.equal(tickAccount.liquidityNet.toString(), "0");
-        assert.equal(tickAccount.bump, tickBump);
+        assert.fail("Expected transaction to fail");
+      } catch (error) {
+        assert.include(
+          error.message,
+          "Tick values must be aligned with tick spacing"
+        );
       }
     });
+  });
 
-    it("should fail to initialize the same tick twice", async () => {
-      const tickIndex = 300;
+  describe("Swap", () => {
+    const LIQUIDITY = new anchor.BN(1_000_000_000);
+    let mintA: anchor.web3.PublicKey;
+    let mintB: anchor.web3.PublicKey;
+    let pool: anchor.web3.PublicKey;
+    let userA: anchor.web3.PublicKey;
+    let userB: anchor.web3.PublicKey;
 
-      const [tickPda] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from("tick"), poolPda.toBytes(), i32ToLeBytes(tickIndex)],
+    const tickArrayPda = (startTickIndex: number) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("tick_array"),
+          pool.toBytes(),
+          i32ToLeBytes(startTickIndex),
+        ],
+        program.programId
+      )[0];
+
+    before(async () => {
+      mintA = await createMint(
+        provider.connection,
+        poolCreator.payer,
+        poolCreator.publicKey,
+        null,
+        6
+      );
+      mintB = await createMint(
+        provider.connection,
+        poolCreator.payer,
+        poolCreator.publicKey,
+        null,
+        6
+      );
+      [pool] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("pool"),
+          mintA.toBytes(),
+          mintB.toBytes(),
+          u32ToLeBytes(FEE_RATE),
+        ],
         program.programId
       );
 
-      // First initialization should succeed
+      userA = (
+        await getOrCreateAssociatedTokenAccount(
+          provider.connection,
+          poolCreator.payer,
+          mintA,
+          poolCreator.publicKey
+        )
+      ).address;
+      userB = (
+        await getOrCreateAssociatedTokenAccount(
+          provider.connection,
+          poolCreator.payer,
+          mintB,
+          poolCreator.publicKey
+        )
+      ).address;
+      await mintTo(
+        provider.connection,
+        poolCreator.payer,
+        mintA,
+        userA,
+        poolCreator.publicKey,
+        1_000_000_000
+      );
+      await mintTo(
+        provider.connection,
+        poolCreator.payer,
+        mintB,
+        userB,
+        poolCreator.publicKey,
+        1_000_000_000
+      );
+
+      // Price 1, so the pool starts at tick 0
       await program.methods
-        .initializeTick(tickIndex)
-        .accountsStrict({
-          payer: poolCreator.publicKey,
-          tokenAMint: tokenAMint,
-          tokenBMint: tokenBMint,
-          pool: poolPda,
-          tick: tickPda,
-          systemProgram: anchor.web3.SystemProgram.programId,
+        .initializePool(new anchor.BN(1), FEE_RATE, TICK_SPACING)
+        .accounts({
+          initializer: poolCreator.publicKey,
+          tokenAMint: mintA,
+          tokenBMint: mintB,
         })
         .rpc();
 
-      // Second initialization should fail
-      try {
+      for (const startTickIndex of [0, TICKS_IN_ARRAY]) {
         await program.methods
-          .initializeTick(tickIndex)
-          .accountsStrict({
+          .initializeTickArray(startTickIndex)
+          .accountsPartial({
             payer: poolCreator.publicKey,
-            tokenAMint: tokenAMint,
-            tokenBMint: tokenBMint,
-            pool: poolPda,
-            tick: tickPda,
-            systemProgram: anchor.web3.SystemProgram.programId,
+            tokenAMint: mintA,
+            tokenBMint: mintB,
+            pool,
           })
           .rpc();
-
-        assert.fail("Expected transaction to fail");
-      } catch (error) {
-        assert.include(error.message.toLowerCase(), "account");
       }
     });
 
-    it("should handle large positive and negative tick indices", async () => {
-      const largePositiveTickIndex = 10000;
-      const largeNegativeTickIndex = -10000;
-
-      // Test large positive tick
-      const [positiveTickPda, positiveTickBump] =
-        anchor.web3.PublicKey.findProgramAddressSync(
-          [
-            Buffer.from("tick"),
-            poolPda.toBytes(),
-            i32ToLeBytes(largePositiveTickIndex),
-          ],
-          program.programId
-        );
+    it("should cross an initialized tick at the start of the next tick array", async () => {
+      // Lower tick is the first tick of the second array, above the current price
+      const tickLower = TICKS_IN_ARRAY;
+      const tickUpper = TICKS_IN_ARRAY + 10 * TICK_SPACING;
+      const [position] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("position"),
+          pool.toBytes(),
+          poolCreator.publicKey.toBytes(),
+          i32ToLeBytes(tickLower),
+          i32ToLeBytes(tickUpper),
+        ],
+        program.programId
+      );
 
       await program.methods
-        .initializeTick(largePositiveTickIndex)
-        .accountsStrict({
-          payer: poolCreator.publicKey,
-          tokenAMint: tokenAMint,
-          tokenBMint: tokenBMint,
-          pool: poolPda,
-          tick: positiveTickPda,
-          systemProgram: anchor.web3.SystemProgram.programId,
+        .openPosition(tickLower, tickUpper)
+        .accountsPartial({
+          owner: poolCreator.publicKey,
+          tokenMintA: mintA,
+          tokenMintB: mintB,
+          pool,
+          positionMint: null,
+          positionTokenAccount: null,
         })
         .rpc();
 
-      const positiveTickAccount = await program.account.tick.fetch(
-        positiveTickPda
-      );
-      assert.equal(positiveTickAccount.index, largePositiveTickIndex);
+      await program.methods
+        .addLiquidity(LIQUIDITY, null)
+        .accountsPartial({
+          liquidityProvider: poolCreator.publicKey,
+          tokenMintA: mintA,
+          tokenMintB: mintB,
+          pool,
+          position,
+          positionTokenAccount: null,
+          tickArrayLower: tickArrayPda(tickLower),
+          tickArrayUpper: tickArrayPda(tickUpper),
+        })
+        .rpc();
 
-      // Test large negative tick
-      const [negativeTickPda, negativeTickBump] =
-        anchor.web3.PublicKey.findProgramAddressSync(
-          [
-            Buffer.from("tick"),
-            poolPda.toBytes(),
-            i32ToLeBytes(largeNegativeTickIndex),
-          ],
-          program.programId
-        );
+      let poolAccount = await program.account.pool.fetch(pool);
+      assert.equal(poolAccount.activeLiquidity.toString(), "0");
+
+      const balanceABefore = (await getAccount(provider.connection, userA))
+        .amount;
 
       await program.methods
-        .initializeTick(largeNegativeTickIndex)
-        .accountsStrict({
-          payer: poolCreator.publicKey,
-          tokenAMint: tokenAMint,
-          tokenBMint: tokenBMint,
-          pool: poolPda,
-          tick: negativeTickPda,
-          systemProgram: anchor.web3.SystemProgram.programId,
+        .swap(new anchor.BN(10_000), false, null, null, null)
+        .accountsPartial({
+          user: poolCreator.publicKey,
+          tokenMintA: mintA,
+          tokenMintB: mintB,
+          pool,
         })
+        .remainingAccounts(
+          [0, TICKS_IN_ARRAY].map((startTickIndex) => ({
+            pubkey: tickArrayPda(startTickIndex),
+            isSigner: false,
+            isWritable: true,
+          }))
+        )
         .rpc();
 
-      const negativeTickAccount = await program.account.tick.fetch(
-        negativeTickPda
+      // The position's liquidity became active once the price crossed its lower tick
+      poolAccount = await program.account.pool.fetch(pool);
+      assert.equal(
+        poolAccount.activeLiquidity.toString(),
+        LIQUIDITY.toString()
+      );
+      assert.isAtLeast(poolAccount.currentTick, tickLower);
+      assert.isBelow(poolAccount.currentTick, tickUpper);
+
+      const balanceAAfter = (await getAccount(provider.connection, userA))
+        .amount;
+      assert.isTrue(balanceAAfter > balanceABefore);
+    });
+  });
+
+  describe("Tick Prices", () => {
+    const LIQUIDITY = new anchor.BN(1_000_000_000);
+    let testPool: TestPool;
+
+    before(async () => {
+      testPool = await createPool();
+    });
+
+    it("should handle large positive and negative tick indices", async () => {
+      // The widest aligned range, with its bounds in the first and last tick arrays
+      const tickLower = -443630;
+      const tickUpper = 443630;
+      const liquidity = new anchor.BN(1_000_000);
+
+      await openPositionWithLiquidity(
+        testPool,
+        tickLower,
+        tickUpper,
+        liquidity
+      );
+
+      const lowerArray = await program.account.tickArray.fetch(
+        tickArrayAddress(testPool, tickLower)
+      );
+      const upperArray = await program.account.tickArray.fetch(
+        tickArrayAddress(testPool, tickUpper)
+      );
+      assert.equal(lowerArray.startTickIndex, -444160);
+      assert.equal(upperArray.startTickIndex, 443520);
+
+      // The price sits in the middle of the range, so both sides are priced off the extremes
+      assert.equal(
+        (await tokenBalance(testPool.vaultA)).toString(),
+        amountADelta(
+          BASE_SQRT_PRICE_X64,
+          tickToSqrtPriceX64(tickUpper),
+          liquidity
+        ).toString()
       );
-      assert.equal(negativeTickAccount.index, largeNegativeTickIndex);
+      assert.equal(
+        (await tokenBalance(testPool.vaultB)).toString(),
+        amountBDelta(
+          tickToSqrtPriceX64(tickLower),
+          BASE_SQRT_PRICE_X64,
+          liquidity
+        ).toString()
+      );
+    });
+
+    it("should fail to open a position past the maximum tick", async () => {
+      try {
+        await program.methods
+          .openPosition(0, 443640)
+          .accountsPartial({
+            owner: poolCreator.publicKey,
+            tokenMintA: testPool.mintA,
+            tokenMintB: testPool.mintB,
+            pool: testPool.pool,
+            positionMint: null,
+            positionTokenAccount: null,
+          })
+          .rpc();
+
+        assert.fail("Expected transaction to fail");
+      } catch (error) {
+        assert.include(
+          error.message,
+          "The tick must be lesser than, or equal to the maximum tick"
+        );
+      }
     });
 
     it("should correctly calculate sqrt_price_x64 for various tick indices", async () => {
       const testCases = [
-        { tickIndex: 500, description: "moderate positive tick" },
-        { tickIndex: -500, description: "moderate negative tick" },
-        { tickIndex: 2000, description: "large positive tick" },
-        { tickIndex: -2000, description: "large negative tick" },
+        { tickLower: 500, tickUpper: 2000, description: "above the price" },
+        { tickLower: -2000, tickUpper: -500, description: "below the price" },
+        { tickLower: -500, tickUpper: 500, description: "around the price" },
       ];
 
       for (const testCase of testCases) {
-        const [tickPda] = anchor.web3.PublicKey.findProgramAddressSync(
-          [
-            Buffer.from("tick"),
-            poolPda.toBytes(),
-            i32ToLeBytes(testCase.tickIndex),
-          ],
-          program.programId
+        const sqrtPriceLowerX64 = tickToSqrtPriceX64(testCase.tickLower);
+        const sqrtPriceUpperX64 = tickToSqrtPriceX64(testCase.tickUpper);
+        const sqrtPriceX64 = anchor.BN.min(
+          anchor.BN.max(BASE_SQRT_PRICE_X64, sqrtPriceLowerX64),
+          sqrtPriceUpperX64
+        );
+        const vaultABefore = await tokenBalance(testPool.vaultA);
+        const vaultBBefore = await tokenBalance(testPool.vaultB);
+
+        await openPositionWithLiquidity(
+          testPool,
+          testCase.tickLower,
+          testCase.tickUpper,
+          LIQUIDITY
+        );
+
+        // Token A covers the range above the price and token B the range below it
+        assert.equal(
+          (await tokenBalance(testPool.vaultA)).sub(vaultABefore).toString(),
+          amountADelta(sqrtPriceX64, sqrtPriceUpperX64, LIQUIDITY).toString(),
+          `token A deposit priced wrong ${testCase.description}`
+        );
+        assert.equal(
+          (await tokenBalance(testPool.vaultB)).sub(vaultBBefore).toString(),
+          amountBDelta(sqrtPriceLowerX64, sqrtPriceX64, LIQUIDITY).toString(),
+          `token B deposit priced wrong ${testCase.description}`
         );
+      }
+    });
+  });
+
+  describe("Position Authority", () => {
+    const LIQUIDITY = new anchor.BN(1_000_000_000);
+    let testPool: TestPool;
+    let position: anchor.web3.PublicKey;
+    let stranger: anchor.web3.Keypair;
+
+    before(async () => {
+      testPool = await createPool();
+      position = await openPositionWithLiquidity(
+        testPool,
+        -100,
+        100,
+        LIQUIDITY
+      );
+      stranger = await fundedKeypair();
+      await fundTokenAccounts(testPool, stranger.publicKey);
+    });
 
+    it("should fail to add liquidity to someone else's position", async () => {
+      try {
         await program.methods
-          .initializeTick(testCase.tickIndex)
-          .accountsStrict({
-            payer: poolCreator.publicKey,
-            tokenAMint: tokenAMint,
-            tokenBMint: tokenBMint,
-            pool: poolPda,
-            tick: tickPda,
-            systemProgram: anchor.web3.SystemProgram.programId,
+          .addLiquidity(new anchor.BN(1_000), null)
+          .accountsPartial({
+            liquidityProvider: stranger.publicKey,
+            ...positionAccounts(testPool, position, -100, 100),
           })
+          .signers([stranger])
           .rpc();
 
-        const tickAccount = await program.account.tick.fetch(tickPda);
+        assert.fail("Expected transaction to fail");
+      } catch (error) {
+        assert.include(error.message, "Signer does not control this position");
+      }
+    });
 
-        // Calculate expected sqrt_price_x64 using the same utility function as other tests
-        const expectedSqrtPrice = tickToSqrtPriceX64(testCase.tickIndex);
+    it("should fail to withdraw liquidity from someone else's position", async () => {
+      try {
+        await program.methods
+          .withdrawLiquidity(new anchor.BN(1_000))
+          .accountsPartial({
+            liquidityProvider: stranger.publicKey,
+            ...positionAccounts(testPool, position, -100, 100),
+          })
+          .signers([stranger])
+          .rpc();
 
-        assert.equal(
-          tickAccount.sqrtPriceX64.toString(),
-          expectedSqrtPrice.toString(),
-          `sqrt_price_x64 calculation failed for ${testCase.description}`
-        );
+        assert.fail("Expected transaction to fail");
+      } catch (error) {
+        assert.include(error.message, "Signer does not control this position");
+      }
+
+      const positionAccount = await program.account.position.fetch(position);
+      assert.equal(positionAccount.liquidity.toString(), LIQUIDITY.toString());
+    });
+
+    it("should fail to withdraw more liquidity than the position holds", async () => {
+      try {
+        await program.methods
+          .withdrawLiquidity(LIQUIDITY.addn(1))
+          .accountsPartial({
+            liquidityProvider: poolCreator.publicKey,
+            ...positionAccounts(testPool, position, -100, 100),
+          })
+          .rpc();
+
+        assert.fail("Expected transaction to fail");
+      } catch (error) {
+        assert.include(error.message, "Position doesn't hold enough liquidity");
+      }
+    });
+
+    it("should hand control of a position over with its NFT", async () => {
+      const positionMint = anchor.web3.Keypair.generate();
+      const ownerTokenAccount = getAssociatedTokenAddressSync(
+        positionMint.publicKey,
+        poolCreator.publicKey
+      );
+      await program.methods
+        .openPosition(-200, 200)
+        .accountsPartial({
+          owner: poolCreator.publicKey,
+          tokenMintA: testPool.mintA,
+          tokenMintB: testPool.mintB,
+          pool: testPool.pool,
+          positionMint: positionMint.publicKey,
+          positionTokenAccount: ownerTokenAccount,
+        })
+        .signers([positionMint])
+        .rpc();
+      const nftPosition = positionAddress(
+        testPool,
+        poolCreator.publicKey,
+        -200,
+        200
+      );
+      await program.methods
+        .addLiquidity(LIQUIDITY, null)
+        .accountsPartial({
+          liquidityProvider: poolCreator.publicKey,
+          ...positionAccounts(
+            testPool,
+            nftPosition,
+            -200,
+            200,
+            ownerTokenAccount
+          ),
+        })
+        .rpc();
+
+      const holder = await fundedKeypair();
+      await fundTokenAccounts(testPool, holder.publicKey);
+      const holderTokenAccount = (
+        await getOrCreateAssociatedTokenAccount(
+          provider.connection,
+          poolCreator.payer,
+          positionMint.publicKey,
+          holder.publicKey
+        )
+      ).address;
+      await transfer(
+        provider.connection,
+        poolCreator.payer,
+        ownerTokenAccount,
+        holderTokenAccount,
+        poolCreator.publicKey,
+        1
+      );
+
+      // Whoever holds the NFT controls the position, whoever opened it
+      const holderA = getAssociatedTokenAddressSync(
+        testPool.mintA,
+        holder.publicKey
+      );
+      const holderABefore = await tokenBalance(holderA);
+      await program.methods
+        .withdrawLiquidity(LIQUIDITY.divn(2))
+        .accountsPartial({
+          liquidityProvider: holder.publicKey,
+          ...positionAccounts(
+            testPool,
+            nftPosition,
+            -200,
+            200,
+            holderTokenAccount
+          ),
+        })
+        .signers([holder])
+        .rpc();
+
+      const positionAccount = await program.account.position.fetch(nftPosition);
+      assert.equal(
+        positionAccount.liquidity.toString(),
+        LIQUIDITY.divn(2).toString()
+      );
+      assert.isTrue((await tokenBalance(holderA)).gt(holderABefore));
+
+      try {
+        await program.methods
+          .withdrawLiquidity(new anchor.BN(1))
+          .accountsPartial({
+            liquidityProvider: poolCreator.publicKey,
+            ...positionAccounts(
+              testPool,
+              nftPosition,
+              -200,
+              200,
+              ownerTokenAccount
+            ),
+          })
+          .rpc();
+
+        assert.fail("Expected transaction to fail");
+      } catch (error) {
+        assert.include(error.message, "Signer does not control this position");
       }
     });
   });
+
+  describe("Fee Growth", () => {
+    const LIQUIDITY = new anchor.BN(1_000_000_000);
+    let testPool: TestPool;
+
+    before(async () => {
+      testPool = await createPool();
+      await openPositionWithLiquidity(testPool, -100, 100, LIQUIDITY);
+      await openPositionWithLiquidity(testPool, 100, 200, LIQUIDITY);
+    });
+
+    it("should grow the global fee growth by the fee per unit of liquidity", async () => {
+      // 0.3% of 10_000 token A, all paid to the liquidity around the price
+      await swap(testPool, 10_000, true, null, [0, -TICKS_IN_ARRAY]);
+
+      const pool = await program.account.pool.fetch(testPool.pool);
+      assert.equal(
+        pool.feeGrowthGlobalAX64.toString(),
+        new anchor.BN(30).shln(64).div(LIQUIDITY).toString()
+      );
+      assert.equal(pool.feeGrowthGlobalBX64.toString(), "0");
+
+      const tick = await fetchTick(testPool, 100);
+      assert.equal(tick.feeGrowthOutsideAX64.toString(), "0");
+      assert.equal(tick.feeGrowthOutsideBX64.toString(), "0");
+    });
+
+    it("should flip the fee growth outside a tick when the price crosses it", async () => {
+      // Stops right on tick 100, crossing it upwards
+      await swap(testPool, 10_000_000, false, tickToSqrtPriceX64(100), [
+        -TICKS_IN_ARRAY,
+        0,
+      ]);
+
+      let pool = await program.account.pool.fetch(testPool.pool);
+      let tick = await fetchTick(testPool, 100);
+      assert.equal(pool.currentTick, 100);
+      assert.equal(
+        tick.feeGrowthOutsideAX64.toString(),
+        pool.feeGrowthGlobalAX64.toString()
+      );
+      assert.equal(
+        tick.feeGrowthOutsideBX64.toString(),
+        pool.feeGrowthGlobalBX64.toString()
+      );
+      assert.notEqual(tick.feeGrowthOutsideBX64.toString(), "0");
+
+      // Crossing back down flips it again, before any of this swap's fees accrue
+      const feeGrowthGlobalABefore = pool.feeGrowthGlobalAX64;
+      await swap(testPool, 1_000_000, true, tickToSqrtPriceX64(50), [0]);
+
+      pool = await program.account.pool.fetch(testPool.pool);
+      tick = await fetchTick(testPool, 100);
+      assert.equal(tick.feeGrowthOutsideAX64.toString(), "0");
+      assert.equal(tick.feeGrowthOutsideBX64.toString(), "0");
+      assert.equal(
+        pool.feeGrowthGlobalAX64.sub(feeGrowthGlobalABefore).toString(),
+        new anchor.BN(3000).shln(64).div(LIQUIDITY).toString()
+      );
+    });
+  });
+
+  describe("Collect Fees", () => {
+    const LIQUIDITY = new anchor.BN(1_000_000_000);
+    const U64_MAX = new anchor.BN("18446744073709551615");
+    let testPool: TestPool;
+    let position: anchor.web3.PublicKey;
+    let outOfRangePosition: anchor.web3.PublicKey;
+    let userA: anchor.web3.PublicKey;
+    let userB: anchor.web3.PublicKey;
+    // The position holds all the liquidity the swap goes through, so it earns the whole fee
+    const feeGrowthX64 = new anchor.BN(3000).shln(64).div(LIQUIDITY);
+    const feesOwed = LIQUIDITY.mul(feeGrowthX64).shrn(64);
+
+    const collectFees = async (
+      position: anchor.web3.PublicKey,
+      tickLower: number,
+      tickUpper: number,
+      amountAMax: anchor.BN,
+      amountBMax: anchor.BN
+    ) =>
+      program.methods
+        .collectFees(amountAMax, amountBMax)
+        .accountsPartial({
+          owner: poolCreator.publicKey,
+          ...positionAccounts(testPool, position, tickLower, tickUpper),
+        })
+        .rpc();
+
+    before(async () => {
+      testPool = await createPool();
+      position = await openPositionWithLiquidity(
+        testPool,
+        -100,
+        100,
+        LIQUIDITY
+      );
+      outOfRangePosition = await openPositionWithLiquidity(
+        testPool,
+        200,
+        300,
+        LIQUIDITY
+      );
+      userA = getAssociatedTokenAddressSync(
+        testPool.mintA,
+        poolCreator.publicKey
+      );
+      userB = getAssociatedTokenAddressSync(
+        testPool.mintB,
+        poolCreator.publicKey
+      );
+
+      // Pays a 3000 token A fee, without leaving the range of the first position
+      await swap(testPool, 1_000_000, true, null, [0, -TICKS_IN_ARRAY]);
+    });
+
+    it("should fail to collect fees of someone else's position", async () => {
+      const stranger = await fundedKeypair();
+      await fundTokenAccounts(testPool, stranger.publicKey);
+
+      try {
+        await program.methods
+          .collectFees(U64_MAX, U64_MAX)
+          .accountsPartial({
+            owner: stranger.publicKey,
+            ...positionAccounts(testPool, position, -100, 100),
+          })
+          .signers([stranger])
+          .rpc();
+
+        assert.fail("Expected transaction to fail");
+      } catch (error) {
+        assert.include(error.message, "Signer does not control this position");
+      }
+    });
+
+    it("should collect no more than the requested amounts", async () => {
+      const balanceABefore = await tokenBalance(userA);
+      const balanceBBefore = await tokenBalance(userB);
+
+      await collectFees(
+        position,
+        -100,
+        100,
+        new anchor.BN(1_000),
+        new anchor.BN(0)
+      );
+
+      assert.equal(
+        (await tokenBalance(userA)).sub(balanceABefore).toString(),
+        "1000"
+      );
+      assert.equal(
+        (await tokenBalance(userB)).sub(balanceBBefore).toString(),
+        "0"
+      );
+
+      // The rest stays owed to the position
+      const positionAccount = await program.account.position.fetch(position);
+      assert.equal(
+        positionAccount.tokensOwedA.toString(),
+        feesOwed.subn(1_000).toString()
+      );
+      assert.equal(positionAccount.tokensOwedB.toString(), "0");
+    });
+
+    it("should collect the rest of the fees the swap paid", async () => {
+      const balanceABefore = await tokenBalance(userA);
+
+      await collectFees(position, -100, 100, U64_MAX, U64_MAX);
+
+      assert.equal(
+        (await tokenBalance(userA)).sub(balanceABefore).toString(),
+        feesOwed.subn(1_000).toString()
+      );
+      const positionAccount = await program.account.position.fetch(position);
+      assert.equal(positionAccount.tokensOwedA.toString(), "0");
+      assert.equal(positionAccount.tokensOwedB.toString(), "0");
+      assert.equal(
+        positionAccount.feeGrowthInsideALastX64.toString(),
+        feeGrowthX64.toString()
+      );
+    });
+
+    it("should not accrue fees to a position out of range", async () => {
+      const balanceABefore = await tokenBalance(userA);
+      const balanceBBefore = await tokenBalance(userB);
+
+      await collectFees(outOfRangePosition, 200, 300, U64_MAX, U64_MAX);
+
+      assert.isTrue((await tokenBalance(userA)).eq(balanceABefore));
+      assert.isTrue((await tokenBalance(userB)).eq(balanceBBefore));
+      const positionAccount = await program.account.position.fetch(
+        outOfRangePosition
+      );
+      assert.equal(positionAccount.tokensOwedA.toString(), "0");
+      assert.equal(positionAccount.feeGrowthInsideALastX64.toString(), "0");
+    });
+  });
 });
@@ -1,8 +1,30 @@
 import BN from "bn.js";
 
 export const BASE_SQRT_PRICE_X64 = new BN(1).shln(64); // 2^64,Q64.64; base tick repr or tick 0
-export const TICK_PER_BASE = new BN(20000);
-export const TICK_STEP_SIZE = BASE_SQRT_PRICE_X64.div(TICK_PER_BASE); // Distance between ticks in sqrt_price_x64 space
+const U128_MAX = new BN(1).shln(128).subn(1);
+
+// sqrt(1.0001^-(2^i)) in Q64.64 for each bit i of a tick, as used by tick_to_sqrt_price_x64
+const TICK_BIT_RATIOS_X64 = [
+  "fffcb933bd6fb800",
+  "fff97272373d4000",
+  "fff2e50f5f657000",
+  "ffe5caca7e10f000",
+  "ffcb9843d60f7000",
+  "ff973b41fa98e800",
+  "ff2ea16466c9b000",
+  "fe5dee046a9a3800",
+  "fcbe86c7900bb000",
+  "f987a7253ac65800",
+  "f3392b0822bb6000",
+  "e7159475a2caf000",
+  "d097f3bdfd2f2000",
+  "a9f746462d9f8000",
+  "70d869a156f31c00",
+  "31be135f97ed3200",
+  "9aa508b5b85a500",
+  "5d6af8dedc582c",
+  "2216e584f5fa",
+].map((ratio) => new BN(ratio, 16));
 
 export const integer_sqrt = (value: number): number => {
   if (value == 0) {
@@ -19,12 +41,33 @@ export const integer_sqrt = (value: number): number => {
   return x;
 };
 
+// Mirrors tick_to_sqrt_price_x64 in the program, rounding included
 export const tickToSqrtPriceX64 = (tick: number): BN => {
-  const tickAdjustment = new BN(Math.abs(tick)).mul(TICK_STEP_SIZE);
-
-  if (tick >= 0) {
-    return BASE_SQRT_PRICE_X64.add(tickAdjustment);
-  } else {
-    return BASE_SQRT_PRICE_X64.sub(tickAdjustment);
+  const absTick = Math.abs(tick);
+  let ratio = absTick & 1 ? TICK_BIT_RATIOS_X64[0] : BASE_SQRT_PRICE_X64;
+  for (let bit = 1; bit < TICK_BIT_RATIOS_X64.length; bit++) {
+    if (absTick & (1 << bit)) {
+      ratio = ratio.mul(TICK_BIT_RATIOS_X64[bit]).shrn(64);
+    }
   }
+
+  return tick > 0 ? U128_MAX.div(ratio) : ratio;
 };
+
+// Token A held by liquidity between two sqrt prices, rounded down like the program does
+export const amountADelta = (
+  sqrtPriceLowerX64: BN,
+  sqrtPriceUpperX64: BN,
+  liquidity: BN
+): BN =>
+  liquidity
+    .mul(sqrtPriceUpperX64.sub(sqrtPriceLowerX64))
+    .shln(64)
+    .div(sqrtPriceUpperX64.mul(sqrtPriceLowerX64));
+
+// Token B held by liquidity between two sqrt prices, rounded down like the program does
+export const amountBDelta = (
+  sqrtPriceLowerX64: BN,
+  sqrtPriceUpperX64: BN,
+  liquidity: BN
+): BN => liquidity.mul(sqrtPriceUpperX64.sub(sqrtPriceLowerX64)).shrn(64);